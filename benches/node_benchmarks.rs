@@ -1,48 +1,141 @@
-use cpal::SampleRate;
 use criterion::{criterion_group, criterion_main, Criterion};
-use dasp_graph::{Buffer, Node, NodeData};
-use klingt::nodes::effect::SlewLimiter;
+use klingt::nodes::effect::{Gain, GainMessage, Mixer, SlewLimiter};
+use klingt::nodes::sink::RtrbSink;
 use klingt::nodes::source::Sine;
+use klingt::{AudioNode, Buffer, Input, Klingt, ProcessContext, Sample};
 
-use klingt::AudioNode;
-use klingt::NodeVariants::*;
+/// A stereo source that costs next to nothing, so the nodes it feeds dominate
+struct Dc(Sample);
 
-type Graph = petgraph::graph::Graph<NodeData<klingt::NodeVariants>, ()>;
-type Processor = dasp_graph::Processor<Graph>;
+impl AudioNode for Dc {
+    type Message = ();
+    type Event = ();
+    type State = ();
+
+    fn process(&mut self, _: &ProcessContext, _: impl Iterator<Item = ()>, _: &[Input], outputs: &mut [Buffer]) {
+        for buffer in outputs.iter_mut() {
+            buffer.iter_mut().for_each(|s| *s = self.0);
+        }
+    }
+
+    fn num_outputs(&self) -> usize { 2 }
+}
+
+/// Mono voices feeding a mixer, the shape of a typical synth patch
+///
+/// Every layout matches, so no channel mixers are put in between.
+fn voices(klingt: &mut Klingt, count: usize) {
+    let mixer = klingt.add(Mixer::mono());
+    for i in 0..count {
+        let sine = klingt.add(Sine::new(110.0 * (i + 1) as f32));
+        let gain = klingt.add(Gain::new(0.5).with_channels(1));
+        klingt.connect(&sine, &gain);
+        klingt.connect(&gain, &mixer);
+    }
+    klingt.output(&mixer);
+}
 
 pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("Sine.process()", |b| {
-        let mut source = Sine::new(SampleRate(48000), 480);
-        let mut output = [Buffer::default()];
-        let input = [];
+        let mut sine = Sine::new(440.0);
+        let ctx = ProcessContext {
+            sample_rate: 48000,
+            buffer_size: 64,
+            frame: 0,
+            transport: Default::default(),
+        };
+        let mut output = [Buffer::new(64)];
 
-        b.iter(move || source.process(&input, &mut output))
+        b.iter(|| sine.process(&ctx, core::iter::empty(), &[], &mut output))
     });
 
-    c.bench_function("Sine.process(), integrated", |b| {
-        let sine = Sine::new(SampleRate(48000), 480);
-        let _slewlimiter = SlewLimiter::new();
+    c.bench_function("Sine, integrated", |b| {
+        let mut klingt = Klingt::offline(48000).with_channels(1);
+        let sine = klingt.add(Sine::new(440.0));
+        klingt.output(&sine);
 
-        let mut g = Graph::with_capacity(64, 64);
-        let mut p = Processor::with_capacity(64);
+        b.iter(|| klingt.process())
+    });
 
-        let i_in = g.add_node(NodeData::new1(Sine(sine)));
+    c.bench_function("SlewLimiter, integrated", |b| {
+        let mut klingt = Klingt::offline(48000).with_channels(1);
+        let sine = klingt.add(Sine::new(440.0));
+        let slew = klingt.add(SlewLimiter::new(1000.0).with_channels(1));
+        klingt.connect(&sine, &slew);
+        klingt.output(&slew);
 
-        b.iter(move || p.process(&mut g, i_in))
+        b.iter(|| klingt.process())
     });
 
-    c.bench_function("SlewLimiter.process()", |b| {
-        let sine = Sine::new(SampleRate(48000), 480);
-        let slewlimiter = SlewLimiter::new();
+    c.bench_function("64 voices into a mixer", |b| {
+        let mut klingt = Klingt::offline(48000).with_channels(1);
+        voices(&mut klingt, 64);
+
+        b.iter(|| klingt.process())
+    });
 
-        let mut g = Graph::with_capacity(64, 64);
-        let mut p = Processor::with_capacity(64);
+    c.bench_function("chain of 64 gains", |b| {
+        let mut klingt = Klingt::offline(48000).with_channels(1);
+        let sine = klingt.add(Sine::new(440.0));
+        let mut last = klingt.add(Gain::new(1.0).with_channels(1));
+        klingt.connect(&sine, &last);
+        for _ in 1..64 {
+            let gain = klingt.add(Gain::new(1.0).with_channels(1));
+            klingt.connect(&last, &gain);
+            last = gain;
+        }
+        klingt.output(&last);
+
+        b.iter(|| klingt.process())
+    });
+
+    c.bench_function("Gain while smoothing, integrated", |b| {
+        let mut klingt = Klingt::offline(48000);
+        let dc = klingt.add(Dc(0.5));
+        let mut gain = klingt.add(Gain::new(1.0));
+        klingt.connect(&dc, &gain);
+        klingt.output(&gain);
+
+        let mut target = 0.0;
+        b.iter(|| {
+            // Never settles
+            target = 1.0 - target;
+            gain.send(GainMessage::SetGain(target)).ok();
+            klingt.process()
+        })
+    });
+
+    c.bench_function("64 inputs into a mixer", |b| {
+        let mut klingt = Klingt::offline(48000);
+        let mixer = klingt.add(Mixer::stereo());
+        for i in 0..64 {
+            let dc = klingt.add(Dc(i as Sample / 64.0));
+            klingt.connect(&dc, &mixer);
+        }
+        klingt.output(&mixer);
+
+        b.iter(|| klingt.process())
+    });
+
+    c.bench_function("RtrbSink, stereo", |b| {
+        let (producer, mut consumer) = rtrb::RingBuffer::<f32>::new(4096);
+        let mut klingt = Klingt::new(48000).with_output(RtrbSink::stereo(producer));
+        let dc = klingt.add(Dc(0.5));
+        klingt.output(&dc);
+
+        b.iter(|| {
+            klingt.process();
+            let chunk = consumer.read_chunk(consumer.slots()).unwrap();
+            chunk.commit_all();
+        })
+    });
 
-        let i_in = g.add_node(NodeData::new1(Sine(sine)));
-        let i_out = g.add_node(NodeData::new1(SlewLimiter(slewlimiter)));
-        g.add_edge(i_in, i_out.clone(), ());
+    #[cfg(feature = "std")]
+    c.bench_function("64 voices into a mixer, 4 threads", |b| {
+        let mut klingt = Klingt::offline(48000).with_channels(1).with_threads(4).with_block_size(512);
+        voices(&mut klingt, 64);
 
-        b.iter(move || p.process(&mut g, i_out))
+        b.iter(|| klingt.process())
     });
 }
 
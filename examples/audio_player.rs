@@ -3,15 +3,17 @@
 //! Run with: cargo run --example audio_player --features cpal_sink
 
 use std::thread::sleep;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use symphonium::SymphoniumLoader;
 
-use klingt::Klingt;
+use klingt::{CpalDevice, Klingt};
 use klingt::nodes::SamplePlayer;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut klingt = Klingt::default_output().ok_or("No audio device")?;
+    let device = CpalDevice::default_output().ok_or("No audio device")?;
+    let (mut klingt, processor) = Klingt::for_device(&device).split();
+    let _stream = device.play(processor)?;
 
     // create file player
     let decoded = SymphoniumLoader::new().load_f32("lowtide.ogg", None)?;
@@ -29,19 +31,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     
 
 
-    // and main loop type shit
+    // and main loop type shit (the device callback does the actual work)
     println!("Playing... Ctrl+C to stop");
-    
-    let start = Instant::now();
-    let rate = klingt.sample_rate() as f64;
-    let mut blocks = 0u64;
 
     loop {
-        let target = (start.elapsed().as_secs_f64() * rate / 64.0) as u64 + 6; // 6 blocks buffer
-        while blocks < target {
-            klingt.process();
-            blocks += 1;
-        }
-        sleep(Duration::from_micros(500));
+        sleep(Duration::from_secs(1));
     }
 }
@@ -8,8 +8,7 @@
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-use dasp_graph::{Buffer, Input};
-use klingt::{AudioNode, CpalDevice, Klingt, ProcessContext};
+use klingt::{AudioNode, Buffer, CpalDevice, Input, Klingt, ProcessContext, Sample};
 use klingt::nodes::Gain;
 
 // =============================================================================
@@ -63,6 +62,8 @@ impl Square {
 
 impl AudioNode for Square {
     type Message = SquareMessage;
+    type Event = ();
+    type State = ();
 
     fn process(
         &mut self,
@@ -86,13 +87,15 @@ impl AudioNode for Square {
         }
 
         let phase_inc = self.frequency / ctx.sample_rate as f32;
+        // Buffers hold `Sample`s: f32, or f64 with the `f64` feature
+        let amplitude = self.amplitude as Sample;
 
         for sample in outputs[0].iter_mut() {
             // Square wave: high when phase < pulse_width, low otherwise
             *sample = if self.phase < self.pulse_width {
-                self.amplitude
+                amplitude
             } else {
-                -self.amplitude
+                -amplitude
             };
 
             // Advance and wrap phase
@@ -117,8 +120,9 @@ fn main() {
     let device = CpalDevice::default_output().expect("No audio device found");
     println!("Using: {} @ {} Hz", device.name(), device.sample_rate());
 
-    let mut klingt = Klingt::new(device.sample_rate())
-        .with_output(device.create_sink());
+    // The processor lives in the device's audio callback; we keep the controller
+    let (mut klingt, processor) = Klingt::for_device(&device).split();
+    let _stream = device.play(processor).expect("Failed to start audio stream");
 
     // Create our custom square wave oscillator
     let mut square = klingt.add(Square::new(220.0).with_pulse_width(0.5));
@@ -131,10 +135,8 @@ fn main() {
 
     println!("Playing square wave with PWM modulation... Ctrl+C to stop\n");
 
-    // Audio processing loop with pulse width modulation
+    // Control loop with pulse width modulation - audio is generated on the device thread
     let start = Instant::now();
-    let rate = klingt.sample_rate() as f64;
-    let mut blocks = 0u64;
 
     loop {
         let elapsed = start.elapsed().as_secs_f32();
@@ -147,13 +149,6 @@ fn main() {
         let freq = 220.0 + 110.0 * (elapsed * 0.2).sin();
         square.send(SquareMessage::SetFrequency(freq)).ok();
 
-        // Process audio blocks to stay ahead of playback
-        let target = (start.elapsed().as_secs_f64() * rate / 64.0) as u64 + 4;
-        while blocks < target {
-            klingt.process();
-            blocks += 1;
-        }
-
         sleep(Duration::from_millis(10));
     }
 }
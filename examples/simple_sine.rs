@@ -3,7 +3,7 @@
 //! Run with: cargo run --example simple_sine --features cpal_sink
 
 use std::thread::sleep;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use klingt::{CpalDevice, Klingt};
 use klingt::nodes::{Sine, Mixer, Gain};
@@ -19,9 +19,9 @@ fn main() {
     println!("Using: {}\n", device.name());
 
 
-    // Build Klingt with selected device
-    let mut klingt = Klingt::new(device.sample_rate())
-        .with_output(device.create_sink());
+    // Build Klingt for the selected device and let its audio callback drive processing
+    let (mut klingt, processor) = Klingt::for_device(&device).split();
+    let _stream = device.play(processor).expect("Failed to start audio stream");
 
     // A major chord: A3 + C#4 + E4
     let root = klingt.add(Sine::new(220.0));    // A3
@@ -40,16 +40,8 @@ fn main() {
 
     println!("Playing A major chord... Ctrl+C to stop");
 
-    let start = Instant::now();
-    let rate = klingt.sample_rate() as f64;
-    let mut blocks = 0u64;
-
+    // Audio runs on the device's thread - nothing to do here
     loop {
-        let target = (start.elapsed().as_secs_f64() * rate / 64.0) as u64 + 6;
-        while blocks < target {
-            klingt.process();
-            blocks += 1;
-        }
-        sleep(Duration::from_micros(500));
+        sleep(Duration::from_secs(1));
     }
 }
@@ -0,0 +1,211 @@
+//! Audio buffers passed between nodes.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+/// The type of the samples nodes process: `f32`, or `f64` with the `f64` feature.
+///
+/// Audio leaves the graph as `f32` through [`CpalSink`](crate::nodes::CpalSink)
+/// and [`Klingt::render`](crate::Klingt::render), and as any [`OutputSample`]
+/// through [`RtrbSink`](crate::nodes::RtrbSink).
+#[cfg(not(feature = "f64"))]
+pub type Sample = f32;
+
+/// The type of the samples nodes process: `f32`, or `f64` with the `f64` feature.
+///
+/// Audio leaves the graph as `f32` through [`CpalSink`](crate::nodes::CpalSink)
+/// and [`Klingt::render`](crate::Klingt::render), and as any [`OutputSample`]
+/// through [`RtrbSink`](crate::nodes::RtrbSink).
+#[cfg(feature = "f64")]
+pub type Sample = f64;
+
+/// A type samples can be converted to on their way out of the graph.
+pub trait OutputSample: Copy + Default + Send + 'static {
+    /// Convert a processed sample, rounding if it's narrower than [`Sample`]
+    fn from_sample(sample: Sample) -> Self;
+}
+
+// One of these casts is always a no-op, depending on the `f64` feature
+impl OutputSample for f32 {
+    #[inline]
+    #[allow(clippy::unnecessary_cast)]
+    fn from_sample(sample: Sample) -> Self {
+        sample as f32
+    }
+}
+
+impl OutputSample for f64 {
+    #[inline]
+    #[allow(clippy::unnecessary_cast)]
+    fn from_sample(sample: Sample) -> Self {
+        sample as f64
+    }
+}
+
+/// One channel of audio for one block.
+///
+/// Derefs to [`[Sample]`](Sample), so it can be indexed and iterated like a slice. Its length
+/// is [`ProcessContext::buffer_size`](crate::ProcessContext::buffer_size), the same
+/// for every buffer a node sees: usually the engine's block size, less while a
+/// block is split at a timestamped message (see [`Handle::send_at`](crate::Handle::send_at)).
+///
+/// A buffer remembers when it's known to be silent, so nodes can skip work on
+/// silence. See [`is_silent`](Self::is_silent).
+#[derive(Clone, Default)]
+pub struct Buffer {
+    data: Vec<Sample>,
+    // The part of `data` nodes currently see
+    start: usize,
+    end: usize,
+    // All of `data` is zero. Cleared by every mutable access.
+    silent: bool,
+}
+
+impl Buffer {
+    /// Create a silent buffer of `len` samples.
+    pub fn new(len: usize) -> Self {
+        Self {
+            data: alloc::vec![0.0; len],
+            start: 0,
+            end: len,
+            silent: true,
+        }
+    }
+
+    /// Short-hand for writing silence to the whole buffer.
+    ///
+    /// Also marks the buffer as silent, which lets the nodes reading it skip
+    /// work. Nothing is written if it already is.
+    pub fn silence(&mut self) {
+        if !self.silent {
+            self.data[self.start..self.end].iter_mut().for_each(|s| *s = 0.0);
+        }
+        // While a block is split, only part of the buffer is ours to silence
+        self.silent |= self.start == 0 && self.end == self.data.len();
+    }
+
+    /// Whether the buffer is known to hold nothing but silence.
+    ///
+    /// True after [`silence`](Self::silence), until the buffer is written to in
+    /// any other way. Zeros written sample by sample don't count, so this is
+    /// only a hint: `false` doesn't mean there's sound.
+    pub fn is_silent(&self) -> bool {
+        self.silent
+    }
+
+    /// Change the length, filling new samples with silence
+    pub(crate) fn resize(&mut self, len: usize) {
+        self.data.resize(len, 0.0);
+        self.set_window(0, len);
+    }
+
+    /// Copy all of `other`, which has the same length, including whether it's silent
+    pub(crate) fn copy_from(&mut self, other: &Buffer) {
+        if !(self.silent && other.silent) {
+            self.data.copy_from_slice(&other.data);
+        }
+        self.silent = other.silent;
+    }
+
+    /// Only expose `data[start..end]` until the window is changed again
+    pub(crate) fn set_window(&mut self, start: usize, end: usize) {
+        self.start = start;
+        self.end = end;
+    }
+}
+
+impl From<Vec<Sample>> for Buffer {
+    fn from(data: Vec<Sample>) -> Self {
+        let end = data.len();
+        Self { data, start: 0, end, silent: false }
+    }
+}
+
+impl PartialEq for Buffer {
+    fn eq(&self, other: &Self) -> bool {
+        // Whether it's known to be silent doesn't change what's in it
+        self.data == other.data && self.start == other.start && self.end == other.end
+    }
+}
+
+impl fmt::Debug for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self[..], f)
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [Sample];
+    fn deref(&self) -> &Self::Target {
+        &self.data[self.start..self.end]
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.silent = false;
+        &mut self.data[self.start..self.end]
+    }
+}
+
+/// The output buffers of one upstream node, as seen by the node it feeds.
+///
+/// A node receives one `Input` per incoming connection. A plain
+/// [`connect`](crate::Klingt::connect) hands over one [`Buffer`] per channel of the
+/// upstream node. A [`connect_ports`](crate::Klingt::connect_ports) connection hands
+/// over only the selected output channel, and tells the node which of its input
+/// ports it's meant for via [`port`](Self::port).
+pub struct Input {
+    // Points into the upstream node's buffers, which aren't touched while the
+    // downstream node is processed
+    buffers_ptr: *const Buffer,
+    buffers_len: usize,
+    port: Option<usize>,
+}
+
+impl Input {
+    pub(crate) fn new(buffers: &[Buffer], port: Option<usize>) -> Self {
+        Self {
+            buffers_ptr: buffers.as_ptr(),
+            buffers_len: buffers.len(),
+            port,
+        }
+    }
+
+    /// The input port this signal was connected to, if it was connected with
+    /// [`connect_ports`](crate::Klingt::connect_ports).
+    ///
+    /// `None` for plain connections, which aren't addressed to a specific port.
+    pub fn port(&self) -> Option<usize> {
+        self.port
+    }
+
+    /// Whether every buffer of this input is known to be silent.
+    ///
+    /// See [`Buffer::is_silent`].
+    pub fn is_silent(&self) -> bool {
+        self.buffers().iter().all(Buffer::is_silent)
+    }
+
+    /// The upstream node's output buffers, one per channel.
+    ///
+    /// Holds a single buffer for port-addressed connections.
+    pub fn buffers(&self) -> &[Buffer] {
+        // Safety: only constructed by the graph while processing, from buffers that
+        // outlive the `process` call the input is handed to
+        unsafe { core::slice::from_raw_parts(self.buffers_ptr, self.buffers_len) }
+    }
+}
+
+// Safety: an `Input` is only a view of buffers owned by the graph, which is `Send`
+unsafe impl Send for Input {}
+
+impl fmt::Debug for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Input")
+            .field("port", &self.port)
+            .field("buffers", &self.buffers())
+            .finish()
+    }
+}
@@ -0,0 +1,51 @@
+//! Structural edits sent from the controller to the processor
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::graph::{AudioGraph, Edge, GraphNode};
+use crate::node::NodeId;
+use crate::transport::{TempoMap, TransportCommand};
+
+/// Identifies a graph: 0 is the main graph, anything else is the sample rate of a sub-graph
+pub(crate) type GraphId = usize;
+
+/// Capacity of the controller -> processor command queue (and the garbage queue back)
+pub(crate) const COMMAND_QUEUE_SIZE: usize = 1024;
+
+/// One graph's slot in the processing order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Scheduled {
+    pub graph_id: GraphId,
+    /// Whether the graph runs ahead of the output. True for graphs that don't
+    /// depend on the main graph, so whatever they feed into it is ready in time.
+    pub ahead: bool,
+}
+
+/// A change to the graph structure, applied by the processor at the next block boundary
+pub(crate) enum Command {
+    AddNode { graph_id: GraphId, node: GraphNode },
+    RemoveNode { graph_id: GraphId, node_id: NodeId },
+    Connect { graph_id: GraphId, from: NodeId, to: NodeId, edge: Edge },
+    Disconnect { graph_id: GraphId, from: NodeId, to: NodeId },
+    DisconnectAll { graph_id: GraphId, node_id: NodeId },
+    SetTerminal { graph_id: GraphId, node_id: NodeId },
+    AddTerminal { graph_id: GraphId, node_id: NodeId },
+    /// Boxed since a whole graph is much larger than the other commands
+    AddSubGraph { rate: u32, graph: Box<AudioGraph> },
+    RemoveSubGraph { rate: u32 },
+    /// The order graphs are processed in, upstream first
+    SetSchedule(Vec<Scheduled>),
+    Transport(TransportCommand),
+}
+
+/// Things the processor is done with, sent back so they get dropped off the audio thread
+///
+/// The contents are never read - holding them until the controller pops them is the point.
+#[allow(dead_code)]
+pub(crate) enum Garbage {
+    Node(GraphNode),
+    Graph(Box<AudioGraph>),
+    Schedule(Vec<Scheduled>),
+    TempoMap(TempoMap),
+}
@@ -0,0 +1,1342 @@
+//! Graph-building half of the engine
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::marker::PhantomData;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use hashbrown::HashMap;
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::buffer::Sample;
+use crate::command::{Command, Garbage, GraphId, Scheduled};
+use crate::description::{GraphDescription, NamedHandle, NamedHandles, NodeRegistry, PendingNode};
+use crate::error::KlingtError;
+use crate::graph::{prepare_node, AudioGraph, Edge, Ports, Timed, DEFAULT_BLOCK_SIZE};
+use crate::handle::{Handle, NodeHandle};
+use crate::layout::ChannelLayout;
+use crate::node::{AudioNode, NodeId};
+use crate::nodes::{ChannelMixer, ResamplingSource, ResamplingSourceMessage, RtrbSink};
+use crate::processor::LEAD_BLOCKS;
+use crate::transport::{TempoMap, TimeSignature, TransportCommand};
+
+/// Size of each node's message queue
+const MESSAGE_QUEUE_SIZE: usize = 64;
+
+/// Node IDs are unique across engines, so handles from another engine are rejected
+static NEXT_NODE_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Most commands a connection through a new bridge takes
+const ROUTE_COMMANDS: usize = 6;
+
+/// Most commands a connection through a new channel mixer takes: the mixer,
+/// a route into it and the connection out of it
+const REMIX_COMMANDS: usize = ROUTE_COMMANDS + 2;
+
+/// Most commands removing a node takes: disconnecting and removing it, then its
+/// sub-graph and the new schedule if it was the last one there
+const REMOVE_COMMANDS: usize = 4;
+
+/// Most commands dropping a route takes: both bridge nodes and the new schedule
+const UNROUTE_COMMANDS: usize = 3;
+
+/// Controller-side record of a node added through the public API
+#[derive(Clone, Copy)]
+struct NodeInfo {
+    graph_id: GraphId,
+    num_inputs: usize,
+    num_outputs: usize,
+    /// Type name, for [`KlingtController::to_dot`]
+    name: &'static str,
+    /// Processed every block as an extra terminal (see `add_sink`)
+    terminal: bool,
+    /// Layouts, to put a channel mixer on connections where they differ
+    output_layout: ChannelLayout,
+    input_layout: Option<ChannelLayout>,
+}
+
+/// Controller-side bookkeeping for a sub-graph running at a different sample rate
+struct SubGraphInfo {
+    /// User nodes and channel mixers living in the sub-graph (excludes bridge nodes)
+    node_count: usize,
+}
+
+/// A resampling bridge carrying one node's output into a graph at another sample rate
+///
+/// Keyed by the source node and the destination graph, so every destination
+/// in that graph shares one resampler.
+struct Bridge {
+    from_graph: GraphId,
+    /// Channels carried across
+    channels: usize,
+    /// RtrbSink next to the source node, an extra terminal of its graph
+    sink_node: NodeId,
+    /// ResamplingSource in the destination graph
+    source_node: NodeId,
+    source_sender: Producer<Timed<ResamplingSourceMessage>>,
+    /// Frames of silence the resampler has been told to output so far
+    delay: usize,
+    /// Nodes fed by the resampler
+    routes: Vec<(NodeId, Option<Ports>)>,
+}
+
+/// The graph-building half of a split [`Klingt`](crate::Klingt).
+///
+/// The controller adds, connects and removes nodes. It never touches the audio
+/// itself: every structural change is sent as a command over a lock-free queue
+/// to the matching [`KlingtProcessor`](crate::KlingtProcessor), which applies it
+/// at the start of its next block. Nodes are boxed and their buffers allocated
+/// here, so the audio thread doesn't have to.
+///
+/// Get one from [`Klingt::split`](crate::Klingt::split).
+///
+/// # Example
+///
+/// ```
+/// use klingt::{Klingt, nodes::Sine};
+///
+/// let (mut controller, mut processor) = Klingt::offline(48000).split();
+///
+/// // The processor can now live on another thread...
+/// let audio = std::thread::spawn(move || processor.render(4800));
+///
+/// // ...while the controller keeps editing the graph
+/// let sine = controller.add(Sine::new(440.0));
+/// controller.output(&sine);
+///
+/// let samples = audio.join().unwrap();
+/// ```
+///
+/// # Queue Capacity
+///
+/// Commands are buffered until the processor runs. If the processor isn't running,
+/// building a very large graph can fill the queue, which panics - or, with the
+/// `try_*` methods, returns [`KlingtError::CommandQueueFull`].
+pub struct KlingtController {
+    /// Output sample rate
+    sample_rate: u32,
+    /// Number of output channels
+    channels: usize,
+    /// Samples per block, for every graph
+    block_size: usize,
+
+    /// Every node added through `add`, so handles can be validated
+    nodes: HashMap<NodeId, NodeInfo>,
+
+    /// Sub-graphs for nodes at different sample rates
+    /// Key: the sample rate of the sub-graph
+    sub_graphs: HashMap<u32, SubGraphInfo>,
+
+    /// Bridges between graphs, keyed by source node and destination graph
+    bridges: HashMap<(NodeId, GraphId), Bridge>,
+
+    /// Connections within a graph, to reject cycles and draw the graph
+    edges: Vec<(NodeId, NodeId, Edge)>,
+
+    /// Channel mixers put between connected nodes whose layouts differ, keyed by
+    /// the nodes they connect
+    remixers: HashMap<(NodeId, NodeId), NodeId>,
+
+    /// The output sink node in main graph (e.g., CpalSink)
+    sink_node: Option<NodeId>,
+
+    commands: Producer<Command>,
+    garbage: Consumer<Garbage>,
+}
+
+impl KlingtController {
+    pub(crate) fn new(
+        sample_rate: u32,
+        channels: usize,
+        commands: Producer<Command>,
+        garbage: Consumer<Garbage>,
+    ) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            block_size: DEFAULT_BLOCK_SIZE,
+            nodes: HashMap::new(),
+            sub_graphs: HashMap::new(),
+            bridges: HashMap::new(),
+            edges: Vec::new(),
+            remixers: HashMap::new(),
+            sink_node: None,
+            commands,
+            garbage,
+        }
+    }
+
+    /// Get the output sample rate in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Get the number of output channels.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Get the number of samples processed per block.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub(crate) fn set_channels(&mut self, channels: usize) {
+        self.channels = channels;
+    }
+
+    pub(crate) fn set_block_size(&mut self, block_size: usize) {
+        self.block_size = block_size;
+    }
+
+    /// Add a sink node and make it the output.
+    ///
+    /// Replaces the previous output, which stays in the graph but is no
+    /// longer processed.
+    pub fn set_output<S: AudioNode<Message = ()>>(&mut self, sink: S) {
+        let id = self.next_id();
+        let info = NodeInfo {
+            graph_id: 0,
+            num_inputs: sink.num_inputs(),
+            num_outputs: sink.num_outputs(),
+            name: short_type_name::<S>(),
+            terminal: true,
+            output_layout: sink.output_layout(),
+            input_layout: sink.input_layout(),
+        };
+        let (node, _) = prepare_node(id, sink, self.block_size, 1);
+
+        self.send(Command::AddNode { graph_id: 0, node });
+        self.send(Command::SetTerminal { graph_id: 0, node_id: id });
+        if let Some(old) = self.sink_node.and_then(|old| self.nodes.get_mut(&old)) {
+            old.terminal = false;
+        }
+        self.sink_node = Some(id);
+        self.nodes.insert(id, info);
+    }
+
+    /// Add a node to the audio graph.
+    ///
+    /// See [`Klingt::add`](crate::Klingt::add).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_add`](Self::try_add) would return an error.
+    pub fn add<N: AudioNode>(&mut self, node: N) -> NodeHandle<N> {
+        self.try_add(node).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Add a node to the audio graph, reporting failures instead of panicking.
+    ///
+    /// See [`Klingt::try_add`](crate::Klingt::try_add).
+    pub fn try_add<N: AudioNode>(&mut self, node: N) -> Result<NodeHandle<N>, KlingtError> {
+        let rate = node.native_sample_rate().unwrap_or(self.sample_rate);
+        self.try_add_at_rate(node, rate)
+    }
+
+    /// Add a sink that's processed every block, alongside the output.
+    ///
+    /// See [`Klingt::add_sink`](crate::Klingt::add_sink).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_add_sink`](Self::try_add_sink) would return an error.
+    pub fn add_sink<S: AudioNode>(&mut self, sink: S) -> NodeHandle<S> {
+        self.try_add_sink(sink).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Add a sink that's processed every block, reporting failures instead of panicking.
+    ///
+    /// See [`Klingt::try_add_sink`](crate::Klingt::try_add_sink).
+    pub fn try_add_sink<S: AudioNode>(&mut self, sink: S) -> Result<NodeHandle<S>, KlingtError> {
+        // Worst case: a new sub-graph and schedule, the node and its terminal
+        self.reserve(4)?;
+        let handle = self.try_add(sink)?;
+        self.send(Command::AddTerminal { graph_id: handle.graph_id, node_id: handle.node_id });
+        if let Some(info) = self.nodes.get_mut(&handle.node_id) {
+            info.terminal = true;
+        }
+        Ok(handle)
+    }
+
+    /// Add a node to the graph running at `sample_rate`.
+    ///
+    /// See [`Klingt::add_at_rate`](crate::Klingt::add_at_rate).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_add_at_rate`](Self::try_add_at_rate) would return an error.
+    pub fn add_at_rate<N: AudioNode>(&mut self, node: N, sample_rate: u32) -> NodeHandle<N> {
+        self.try_add_at_rate(node, sample_rate).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Add a node to the graph running at `sample_rate`, reporting failures instead of panicking.
+    ///
+    /// See [`Klingt::try_add_at_rate`](crate::Klingt::try_add_at_rate).
+    pub fn try_add_at_rate<N: AudioNode>(
+        &mut self,
+        node: N,
+        sample_rate: u32,
+    ) -> Result<NodeHandle<N>, KlingtError> {
+        // Rates double as graph ids, and 0 is the main graph's
+        if sample_rate == 0 {
+            return Err(KlingtError::InvalidSampleRate);
+        }
+        self.collect_garbage();
+
+        let graph_id = if sample_rate == self.sample_rate {
+            // Node matches output rate - add to main graph
+            self.reserve(1)?;
+            0
+        } else {
+            // Node needs its own sub-graph with resampling
+            if self.sub_graphs.contains_key(&sample_rate) {
+                self.reserve(1)?;
+            } else {
+                // Sub-graph, new schedule and the node itself
+                self.reserve(3)?;
+            }
+            sample_rate as GraphId
+        };
+
+        if graph_id != 0 && !self.sub_graphs.contains_key(&sample_rate) {
+            self.create_subgraph(sample_rate);
+        }
+
+        let id = self.next_id();
+        self.nodes.insert(id, NodeInfo {
+            graph_id,
+            num_inputs: node.num_inputs(),
+            num_outputs: node.num_outputs(),
+            name: short_type_name::<N>(),
+            terminal: false,
+            output_layout: node.output_layout(),
+            input_layout: node.input_layout(),
+        });
+
+        let (graph_node, channels) = prepare_node(id, node, self.block_size, MESSAGE_QUEUE_SIZE);
+        self.send(Command::AddNode { graph_id, node: graph_node });
+        if let Some(sub) = self.sub_graphs.get_mut(&sample_rate) {
+            sub.node_count += 1;
+        }
+
+        Ok(Handle {
+            node_id: id,
+            graph_id,
+            sender: channels.sender,
+            events: channels.events,
+            state: channels.state,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Create a new, empty sub-graph
+    ///
+    /// Bridges to other graphs are created as connections need them.
+    fn create_subgraph(&mut self, rate: u32) {
+        let sub_graph = AudioGraph::new(rate, self.block_size);
+        self.send(Command::AddSubGraph { rate, graph: Box::new(sub_graph) });
+
+        self.sub_graphs.insert(rate, SubGraphInfo { node_count: 0 });
+        self.update_schedule();
+    }
+
+    /// Connect two nodes together.
+    ///
+    /// See [`Klingt::connect`](crate::Klingt::connect).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_connect`](Self::try_connect) would return an error.
+    pub fn connect<M1, E1, S1, M2, E2, S2>(&mut self, from: &Handle<M1, E1, S1>, to: &Handle<M2, E2, S2>)
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.try_connect(from, to).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Connect two nodes together, reporting failures instead of panicking.
+    ///
+    /// See [`Klingt::try_connect`](crate::Klingt::try_connect).
+    pub fn try_connect<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        to: &Handle<M2, E2, S2>,
+    ) -> Result<(), KlingtError>
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.connect_with(from.node_id, to.node_id, None, false)
+    }
+
+    /// Connect two nodes through a one-block delay, allowing feedback loops.
+    ///
+    /// See [`Klingt::connect_feedback`](crate::Klingt::connect_feedback).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_connect_feedback`](Self::try_connect_feedback) would return an error.
+    pub fn connect_feedback<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        to: &Handle<M2, E2, S2>,
+    )
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.try_connect_feedback(from, to).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Connect two nodes through a one-block delay, reporting failures instead of panicking.
+    ///
+    /// See [`Klingt::try_connect_feedback`](crate::Klingt::try_connect_feedback).
+    pub fn try_connect_feedback<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        to: &Handle<M2, E2, S2>,
+    ) -> Result<(), KlingtError>
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.connect_with(from.node_id, to.node_id, None, true)
+    }
+
+    /// Connect one output channel of a node to one input port of another.
+    ///
+    /// See [`Klingt::connect_ports`](crate::Klingt::connect_ports).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_connect_ports`](Self::try_connect_ports) would return an error.
+    pub fn connect_ports<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        out_port: usize,
+        to: &Handle<M2, E2, S2>,
+        in_port: usize,
+    )
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.try_connect_ports(from, out_port, to, in_port).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Connect one output channel of a node to one input port of another,
+    /// reporting failures instead of panicking.
+    ///
+    /// See [`Klingt::try_connect_ports`](crate::Klingt::try_connect_ports).
+    pub fn try_connect_ports<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        out_port: usize,
+        to: &Handle<M2, E2, S2>,
+        in_port: usize,
+    ) -> Result<(), KlingtError>
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        let ports = Ports { output: out_port, input: in_port };
+        self.connect_with(from.node_id, to.node_id, Some(ports), false)
+    }
+
+    fn connect_with(&mut self, from: NodeId, to: NodeId, ports: Option<Ports>, feedback: bool) -> Result<(), KlingtError> {
+        self.collect_garbage();
+
+        let from_info = self.node_info(from)?;
+        let to_info = self.node_info(to)?;
+
+        if let Some(ports) = ports {
+            if ports.output >= from_info.num_outputs {
+                return Err(KlingtError::InvalidOutputPort { port: ports.output, num_outputs: from_info.num_outputs });
+            }
+            if ports.input >= to_info.num_inputs {
+                return Err(KlingtError::InvalidInputPort { port: ports.input, num_inputs: to_info.num_inputs });
+            }
+        }
+
+        match self.remix_layout(from_info, to, to_info, ports) {
+            Some(layout) => self.remix(from, from_info, to, to_info, layout, feedback),
+            None => self.connect_direct(from, from_info, to, to_info, ports, feedback),
+        }
+    }
+
+    /// The layout a connection into `to` has to be mixed to, if it needs a channel mixer
+    fn remix_layout(&self, from_info: NodeInfo, to: NodeId, to_info: NodeInfo, ports: Option<Ports>) -> Option<ChannelLayout> {
+        // The output takes the engine's channels, unless its sink says otherwise
+        let input_layout = match to_info.input_layout {
+            None if self.sink_node == Some(to) => Some(ChannelLayout::from_channels(self.channels)),
+            layout => layout,
+        };
+        // Picked channels go as they are, everything else in the layout `to` expects
+        input_layout.filter(|&layout| ports.is_none() && layout != from_info.output_layout)
+    }
+
+    /// Connect two nodes as they are, through a bridge if they're in different graphs
+    fn connect_direct(
+        &mut self,
+        from: NodeId,
+        from_info: NodeInfo,
+        to: NodeId,
+        to_info: NodeInfo,
+        ports: Option<Ports>,
+        feedback: bool,
+    ) -> Result<(), KlingtError> {
+        // graph_id: 0 = main graph, otherwise it's the sample rate of a sub-graph
+        match (from_info.graph_id, to_info.graph_id) {
+            // Both in the same graph
+            (r1, r2) if r1 == r2 => {
+                if !feedback && self.creates_cycle(from, to) {
+                    return Err(KlingtError::Cycle { from, to });
+                }
+                self.reserve(1)?;
+                let edge = Edge { ports, feedback };
+                self.send(Command::Connect { graph_id: r1, from, to, edge });
+                self.edges.push((from, to, edge));
+            }
+            // Different graphs - connect through a resampling bridge, which already
+            // delays the audio, so feedback edges need nothing extra
+            (r1, r2) => {
+                self.reserve(ROUTE_COMMANDS)?;
+                self.route(from, r1, to, r2, ports);
+            }
+        }
+        Ok(())
+    }
+
+    /// Connect two nodes through a new channel mixer next to `to`, converting
+    /// the output of `from` to `layout`
+    fn remix(
+        &mut self,
+        from: NodeId,
+        from_info: NodeInfo,
+        to: NodeId,
+        to_info: NodeInfo,
+        layout: ChannelLayout,
+        feedback: bool,
+    ) -> Result<(), KlingtError> {
+        if self.remixers.contains_key(&(from, to)) {
+            return Ok(());
+        }
+        // The mixer can't close a loop the direct connection wouldn't
+        if !feedback && from_info.graph_id == to_info.graph_id && self.creates_cycle(from, to) {
+            return Err(KlingtError::Cycle { from, to });
+        }
+        self.reserve(REMIX_COMMANDS)?;
+
+        let mixer = ChannelMixer::new(from_info.output_layout, layout);
+        let id = self.next_id();
+        let info = NodeInfo {
+            graph_id: to_info.graph_id,
+            num_inputs: mixer.num_inputs(),
+            num_outputs: mixer.num_outputs(),
+            name: short_type_name::<ChannelMixer>(),
+            terminal: false,
+            output_layout: mixer.output_layout(),
+            input_layout: mixer.input_layout(),
+        };
+        let (node, _) = prepare_node(id, mixer, self.block_size, 1);
+        self.send(Command::AddNode { graph_id: info.graph_id, node });
+        self.nodes.insert(id, info);
+        if let Some(sub) = self.sub_graphs.get_mut(&(info.graph_id as u32)) {
+            sub.node_count += 1;
+        }
+        self.remixers.insert((from, to), id);
+
+        self.connect_direct(from, from_info, id, info, None, feedback)?;
+        self.connect_direct(id, info, to, to_info, None, false)
+    }
+
+    /// Connect a node directly to the audio output.
+    ///
+    /// See [`Klingt::output`](crate::Klingt::output).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_output`](Self::try_output) would return an error, e.g. if
+    /// no output sink is configured.
+    pub fn output<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: &Handle<M, E, S>,
+    ) {
+        self.try_output(handle).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Connect a node directly to the audio output, reporting failures instead of panicking.
+    ///
+    /// See [`Klingt::try_output`](crate::Klingt::try_output).
+    pub fn try_output<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: &Handle<M, E, S>,
+    ) -> Result<(), KlingtError> {
+        self.output_with(handle.node_id)
+    }
+
+    fn output_with(&mut self, id: NodeId) -> Result<(), KlingtError> {
+        self.collect_garbage();
+
+        let sink_id = self.sink_node.ok_or(KlingtError::NoOutputSink)?;
+        self.connect_with(id, sink_id, None, false)
+    }
+
+    /// Connect nodes in different graphs through the bridge carrying `from` into `to_graph`
+    ///
+    /// The bridge carries every channel, so port selection happens on the receiving side.
+    fn route(&mut self, from: NodeId, from_graph: GraphId, to: NodeId, to_graph: GraphId, ports: Option<Ports>) {
+        let key = (from, to_graph);
+        if !self.bridges.contains_key(&key) {
+            self.create_bridge(from, from_graph, to_graph);
+        }
+
+        let bridge = self.bridges.get_mut(&key).unwrap();
+        bridge.routes.push((to, ports));
+        let source_node = bridge.source_node;
+
+        self.send(Command::Connect { graph_id: to_graph, from: source_node, to, edge: Edge { ports, feedback: false } });
+    }
+
+    /// Create a bridge resampling the output of `from` into `to_graph`
+    ///
+    /// It's as wide as `from`, so every route out of it can share it.
+    fn create_bridge(&mut self, from: NodeId, from_graph: GraphId, to_graph: GraphId) {
+        let from_rate = self.graph_rate(from_graph);
+        let to_rate = self.graph_rate(to_graph);
+        let channels = self.nodes.get(&from).map_or(1, |info| info.num_outputs).max(1);
+
+        // Ring buffer between the graphs, with room for the blocks the producer runs
+        // ahead (see `KlingtProcessor::process`) and for the longest resampler delay
+        let delay = BridgeTiming { producer_ahead: true, back_edge: true }.delay(self.block_size, self.sample_rate, from_rate, to_rate);
+        let frames = ((from_rate as f32 * 0.1) as usize).max(self.block_size * 8)
+            + delay * from_rate as usize / to_rate as usize;
+        let buffer_size = (frames * channels).next_power_of_two().max(8192);
+        let (producer, consumer) = RingBuffer::<Sample>::new(buffer_size);
+
+        // RtrbSink in the source graph, processed as an extra terminal there
+        let sink_node = self.next_id();
+        let start_frame = Arc::new(AtomicU64::new(0));
+        let sink = RtrbSink::new(producer, channels).with_start_frame(start_frame.clone());
+        let (sink, _) = prepare_node(sink_node, sink, self.block_size, 1);
+
+        // Resampling source in the destination graph, lined up with the sink
+        let source_node = self.next_id();
+        let source = ResamplingSource::new(consumer, channels, from_rate).with_producer_start(start_frame);
+        let (source, source_channels) = prepare_node(source_node, source, self.block_size, 4);
+
+        self.send(Command::AddNode { graph_id: from_graph, node: sink });
+        self.send(Command::AddTerminal { graph_id: from_graph, node_id: sink_node });
+        self.send(Command::Connect { graph_id: from_graph, from, to: sink_node, edge: Edge::default() });
+        self.send(Command::AddNode { graph_id: to_graph, node: source });
+
+        self.bridges.insert((from, to_graph), Bridge {
+            from_graph,
+            channels,
+            sink_node,
+            source_node,
+            source_sender: source_channels.sender,
+            delay: 0,
+            routes: Vec::new(),
+        });
+        self.update_schedule();
+    }
+
+    /// Remove a bridge along with its sink and resampler
+    fn remove_bridge(&mut self, key: (NodeId, GraphId)) {
+        if let Some(bridge) = self.bridges.remove(&key) {
+            self.send(Command::RemoveNode { graph_id: bridge.from_graph, node_id: bridge.sink_node });
+            self.send(Command::RemoveNode { graph_id: key.1, node_id: bridge.source_node });
+            self.update_schedule();
+        }
+    }
+
+    /// Work out the processing order of the graphs and send it to the processor
+    ///
+    /// Graphs are ordered so that each one runs after the graphs feeding it. The
+    /// main graph breaks cycles: whatever it feeds runs after it, even if it also
+    /// feeds back into it.
+    fn update_schedule(&mut self) {
+        let mut graphs: Vec<GraphId> = self.sub_graphs.keys().map(|&rate| rate as GraphId).collect();
+        graphs.sort_unstable();
+        graphs.insert(0, 0);
+
+        let mut edges: Vec<(GraphId, GraphId)> = self
+            .bridges
+            .iter()
+            .map(|(&(_, to_graph), bridge)| (bridge.from_graph, to_graph))
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+
+        // Graphs downstream of the main graph can't run ahead of it
+        let mut downstream = alloc::vec![0];
+        let mut i = 0;
+        while i < downstream.len() {
+            let graph = downstream[i];
+            for &(from, to) in &edges {
+                if from == graph && !downstream.contains(&to) {
+                    downstream.push(to);
+                }
+            }
+            i += 1;
+        }
+
+        let mut schedule: Vec<Scheduled> = Vec::with_capacity(graphs.len());
+        while !graphs.is_empty() {
+            let scheduled = |g: GraphId| schedule.iter().any(|s: &Scheduled| s.graph_id == g);
+            // First graph whose inputs are all ready, or the first one left if there's a cycle
+            let next = graphs
+                .iter()
+                .position(|&g| edges.iter().all(|&(from, to)| to != g || from == g || scheduled(from)))
+                .unwrap_or(0);
+
+            let graph_id = graphs.remove(next);
+            schedule.push(Scheduled { graph_id, ahead: !downstream.contains(&graph_id) });
+        }
+
+        // Resamplers that could get ahead of their producer are delayed until they can't.
+        // Delays only ever grow: shrinking one would mean skipping audio.
+        let position = |g: GraphId| schedule.iter().position(|s| s.graph_id == g);
+        let (block_size, main_rate) = (self.block_size, self.sample_rate);
+        for (&(_, to_graph), bridge) in self.bridges.iter_mut() {
+            let timing = BridgeTiming {
+                producer_ahead: !downstream.contains(&bridge.from_graph),
+                back_edge: position(bridge.from_graph) > position(to_graph),
+            };
+            // Graphs running ahead feed the output in time - the lead is there for that
+            if timing.producer_ahead && to_graph == 0 {
+                continue;
+            }
+
+            let from_rate = if bridge.from_graph == 0 { main_rate } else { bridge.from_graph as u32 };
+            let to_rate = if to_graph == 0 { main_rate } else { to_graph as u32 };
+            let delay = timing.delay(block_size, main_rate, from_rate, to_rate);
+            if delay > bridge.delay
+                && bridge.source_sender.push(Timed::now(ResamplingSourceMessage::Delay(delay - bridge.delay))).is_ok()
+            {
+                bridge.delay = delay;
+            }
+        }
+
+        self.send(Command::SetSchedule(schedule));
+    }
+
+    /// Disconnect `from` from `to`.
+    ///
+    /// See [`Klingt::disconnect`](crate::Klingt::disconnect).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_disconnect`](Self::try_disconnect) would return an error.
+    pub fn disconnect<M1, E1, S1, M2, E2, S2>(&mut self, from: &Handle<M1, E1, S1>, to: &Handle<M2, E2, S2>)
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.try_disconnect(from, to).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Disconnect `from` from `to`, reporting failures instead of panicking.
+    ///
+    /// See [`Klingt::try_disconnect`](crate::Klingt::try_disconnect).
+    pub fn try_disconnect<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        to: &Handle<M2, E2, S2>,
+    ) -> Result<(), KlingtError>
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.collect_garbage();
+
+        let commands = if self.remixers.contains_key(&(from.node_id, to.node_id)) {
+            REMOVE_COMMANDS + 2 * UNROUTE_COMMANDS
+        } else if from.graph_id == to.graph_id {
+            1
+        } else {
+            self.unroute_commands((from.node_id, to.graph_id), to.node_id)
+        };
+        self.reserve(commands)?;
+
+        if let Some(mixer) = self.remixers.remove(&(from.node_id, to.node_id)) {
+            self.remove_with(mixer, to.graph_id);
+        } else if from.graph_id == to.graph_id {
+            self.send(Command::Disconnect { graph_id: from.graph_id, from: from.node_id, to: to.node_id });
+            self.edges.retain(|&(f, t, _)| (f, t) != (from.node_id, to.node_id));
+        } else {
+            let to_id = to.node_id;
+            self.unroute((from.node_id, to.graph_id), |t| t == to_id);
+        }
+        Ok(())
+    }
+
+    /// Disconnect a node from everything it's connected to.
+    ///
+    /// See [`Klingt::disconnect_all`](crate::Klingt::disconnect_all).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_disconnect_all`](Self::try_disconnect_all) would return an error.
+    pub fn disconnect_all<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: &Handle<M, E, S>,
+    ) {
+        self.try_disconnect_all(handle).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Disconnect a node from everything it's connected to, reporting failures
+    /// instead of panicking.
+    ///
+    /// See [`Klingt::try_disconnect_all`](crate::Klingt::try_disconnect_all).
+    pub fn try_disconnect_all<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: &Handle<M, E, S>,
+    ) -> Result<(), KlingtError> {
+        self.collect_garbage();
+        self.reserve(self.disconnect_all_commands(handle.node_id, handle.graph_id))?;
+        self.disconnect_all_with(handle.node_id, handle.graph_id);
+        Ok(())
+    }
+
+    /// Most commands [`disconnect_all_with`](Self::disconnect_all_with) takes
+    fn disconnect_all_commands(&self, id: NodeId, graph_id: GraphId) -> usize {
+        // Each channel mixer goes as a node, along with a route on either side of it
+        let remixers = self.remixers.keys().filter(|&&(from, to)| from == id || to == id).count();
+        let bridges: usize = self
+            .bridges
+            .keys()
+            .map(|&key| match key {
+                (from, _) if from == id => UNROUTE_COMMANDS,
+                (_, to_graph) if to_graph == graph_id => self.unroute_commands(key, id),
+                _ => 0,
+            })
+            .sum();
+        1 + remixers * (REMOVE_COMMANDS + 2 * UNROUTE_COMMANDS) + bridges
+    }
+
+    /// Most commands dropping the routes from the bridge at `key` to `to` takes
+    fn unroute_commands(&self, key: (NodeId, GraphId), to: NodeId) -> usize {
+        let routes = self.bridges.get(&key).map_or(0, |bridge| bridge.routes.iter().filter(|&&(t, _)| t == to).count());
+        match routes {
+            0 => 0,
+            // Either a disconnect per route, or the whole bridge
+            routes => routes.max(UNROUTE_COMMANDS),
+        }
+    }
+
+    fn disconnect_all_with(&mut self, id: NodeId, graph_id: GraphId) {
+        self.collect_garbage();
+
+        let remixed: Vec<(NodeId, NodeId)> = self.remixers.keys().copied().filter(|&(from, to)| from == id || to == id).collect();
+        for key in remixed {
+            if let Some(mixer) = self.remixers.remove(&key) {
+                let mixer_graph = self.nodes.get(&mixer).map_or(graph_id, |info| info.graph_id);
+                self.remove_with(mixer, mixer_graph);
+            }
+        }
+
+        self.send(Command::DisconnectAll { graph_id, node_id: id });
+        self.edges.retain(|&(from, to, _)| from != id && to != id);
+
+        let keys: Vec<(NodeId, GraphId)> = self.bridges.keys().copied().collect();
+        for key in keys {
+            if key.0 == id {
+                self.remove_bridge(key);
+            } else if key.1 == graph_id {
+                self.unroute(key, |t| t == id);
+            }
+        }
+    }
+
+    /// Drop the routes of a bridge whose destination matches `pred`, removing the
+    /// bridge once it carries nothing
+    fn unroute(&mut self, key: (NodeId, GraphId), pred: impl Fn(NodeId) -> bool) {
+        let bridge = match self.bridges.get_mut(&key) {
+            Some(bridge) => bridge,
+            None => return,
+        };
+
+        let source_node = bridge.source_node;
+        let mut removed = Vec::new();
+        bridge.routes.retain(|&(to, _)| {
+            if pred(to) {
+                removed.push(to);
+                false
+            } else {
+                true
+            }
+        });
+        let empty = bridge.routes.is_empty();
+
+        if empty {
+            self.remove_bridge(key);
+        } else {
+            for to in removed {
+                self.send(Command::Disconnect { graph_id: key.1, from: source_node, to });
+            }
+        }
+    }
+
+    /// Remove a node from the audio graph.
+    ///
+    /// See [`Klingt::remove`](crate::Klingt::remove).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the command queue is full, see [`try_remove`](Self::try_remove).
+    pub fn remove<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: Handle<M, E, S>,
+    ) {
+        if self.try_remove(handle).is_err() {
+            panic!("{}", KlingtError::CommandQueueFull);
+        }
+    }
+
+    /// Remove a node from the audio graph, handing the handle back if the
+    /// command queue is full.
+    ///
+    /// See [`Klingt::try_remove`](crate::Klingt::try_remove).
+    pub fn try_remove<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: Handle<M, E, S>,
+    ) -> Result<(), Handle<M, E, S>> {
+        self.collect_garbage();
+
+        if self.nodes.contains_key(&handle.node_id) {
+            // Disconnecting, then the node and maybe its sub-graph and schedule
+            let commands = self.disconnect_all_commands(handle.node_id, handle.graph_id) + 3;
+            if self.reserve(commands).is_err() {
+                return Err(handle);
+            }
+        }
+        self.remove_with(handle.node_id, handle.graph_id);
+        Ok(())
+    }
+
+    fn remove_with(&mut self, id: NodeId, graph_id: GraphId) {
+        if self.nodes.remove(&id).is_none() {
+            return;
+        }
+
+        self.disconnect_all_with(id, graph_id);
+        self.send(Command::RemoveNode { graph_id, node_id: id });
+
+        if graph_id == 0 {
+            if self.sink_node == Some(id) {
+                self.sink_node = None;
+            }
+            return;
+        }
+
+        let rate = graph_id as u32;
+        let sub = match self.sub_graphs.get_mut(&rate) {
+            Some(sub) => sub,
+            None => return,
+        };
+        sub.node_count -= 1;
+
+        // Its bridges went with its nodes - nothing left to run
+        if sub.node_count == 0 {
+            self.sub_graphs.remove(&rate);
+            self.send(Command::RemoveSubGraph { rate });
+            self.update_schedule();
+        }
+    }
+
+    /// Build the nodes and connections of a graph description.
+    ///
+    /// See [`Klingt::from_description`](crate::Klingt::from_description).
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_from_description`](Self::try_from_description) would return an error.
+    pub fn from_description(&mut self, description: &GraphDescription, registry: &NodeRegistry) -> NamedHandles {
+        self.try_from_description(description, registry).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Build the nodes and connections of a graph description, reporting failures
+    /// instead of panicking.
+    ///
+    /// See [`Klingt::try_from_description`](crate::Klingt::try_from_description).
+    pub fn try_from_description(
+        &mut self,
+        description: &GraphDescription,
+        registry: &NodeRegistry,
+    ) -> Result<NamedHandles, KlingtError> {
+        // Check everything that doesn't need the graph first
+        let mut names: Vec<&str> = Vec::with_capacity(description.nodes.len());
+        for node in &description.nodes {
+            if names.contains(&node.name.as_str()) {
+                return Err(KlingtError::DuplicateName(node.name.clone()));
+            }
+            names.push(&node.name);
+        }
+        let referenced = description.connections.iter().flat_map(|c| [&c.from, &c.to]).chain(&description.output);
+        for name in referenced {
+            if !names.contains(&name.as_str()) {
+                return Err(KlingtError::UnknownName(name.clone()));
+            }
+        }
+        let pending = description
+            .nodes
+            .iter()
+            .map(|node| registry.create(node))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut handles = HashMap::with_capacity(pending.len());
+        let result = self.build_description(description, pending, &mut handles);
+        if let Err(e) = result {
+            // Don't leave half a patch behind
+            for handle in handles.values() {
+                self.remove_with(handle.node_id, handle.graph_id);
+            }
+            return Err(e);
+        }
+        Ok(NamedHandles::new(handles))
+    }
+
+    fn build_description(
+        &mut self,
+        description: &GraphDescription,
+        pending: Vec<Box<dyn PendingNode>>,
+        handles: &mut HashMap<String, NamedHandle>,
+    ) -> Result<(), KlingtError> {
+        // Commands taking down everything built so far. Each step only goes ahead
+        // if the queue has room for it and for that, so the rollback never finds
+        // the queue full.
+        let mut rollback = 0;
+
+        for (node, pending) in description.nodes.iter().zip(pending) {
+            // The node may bring a sub-graph and its schedule
+            rollback += REMOVE_COMMANDS;
+            self.reserve(3 + rollback)?;
+            let handle = pending.add(self)?;
+            handles.insert(node.name.clone(), handle);
+        }
+
+        for connection in &description.connections {
+            let (from, to) = (handles[&connection.from].node_id, handles[&connection.to].node_id);
+            let ports = connection.ports.map(|(output, input)| Ports { output, input });
+            rollback += self.reserve_connection(from, to, ports, rollback)?;
+            self.connect_with(from, to, ports, connection.feedback)?;
+        }
+        for name in &description.output {
+            let (from, to) = (handles[name].node_id, self.sink_node.ok_or(KlingtError::NoOutputSink)?);
+            rollback += self.reserve_connection(from, to, None, rollback)?;
+            self.output_with(from)?;
+        }
+        Ok(())
+    }
+
+    /// Make sure connecting `from` to `to` fits in the queue, along with `rollback`
+    /// and the commands taking the connection down again, which are returned
+    fn reserve_connection(&self, from: NodeId, to: NodeId, ports: Option<Ports>, rollback: usize) -> Result<usize, KlingtError> {
+        let from_info = self.node_info(from)?;
+        let to_info = self.node_info(to)?;
+        let (commands, undo) = if self.remix_layout(from_info, to, to_info, ports).is_some() {
+            // The mixer, and a route on either side of it
+            (REMIX_COMMANDS, REMOVE_COMMANDS + 2 * UNROUTE_COMMANDS)
+        } else if from_info.graph_id == to_info.graph_id {
+            // Goes when either node does
+            (1, 0)
+        } else {
+            (ROUTE_COMMANDS, UNROUTE_COMMANDS)
+        };
+        self.reserve(commands + rollback + undo)?;
+        Ok(undo)
+    }
+
+    /// Describe the live graph structure in Graphviz DOT format.
+    ///
+    /// See [`Klingt::to_dot`](crate::Klingt::to_dot).
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        // Writing to a String can't fail
+        let _ = self.write_dot(&mut dot);
+        dot
+    }
+
+    fn write_dot(&self, out: &mut String) -> fmt::Result {
+        let mut graphs: Vec<GraphId> = self.sub_graphs.keys().map(|&rate| rate as GraphId).collect();
+        graphs.sort_unstable();
+        graphs.insert(0, 0);
+
+        // Sorted, so the same graph always comes out the same
+        let mut nodes: Vec<(NodeId, NodeInfo)> = self.nodes.iter().map(|(&id, &info)| (id, info)).collect();
+        nodes.sort_unstable_by_key(|&(id, _)| id.0);
+        let mut bridges: Vec<((NodeId, GraphId), &Bridge)> = self.bridges.iter().map(|(&key, b)| (key, b)).collect();
+        bridges.sort_unstable_by_key(|&(_, bridge)| bridge.sink_node.0);
+
+        writeln!(out, "digraph klingt {{")?;
+        writeln!(out, "    rankdir=LR;")?;
+        writeln!(out, "    node [shape=box];")?;
+
+        for &graph_id in &graphs {
+            let rate = self.graph_rate(graph_id);
+            if graph_id == 0 {
+                writeln!(out, "    subgraph cluster_main {{")?;
+                writeln!(out, "        label=\"main graph ({} Hz)\";", rate)?;
+            } else {
+                writeln!(out, "    subgraph cluster_{} {{", rate)?;
+                writeln!(out, "        label=\"sub-graph ({} Hz)\";", rate)?;
+            }
+
+            for &(id, info) in nodes.iter().filter(|(_, info)| info.graph_id == graph_id) {
+                let role = if self.sink_node == Some(id) {
+                    "\\noutput"
+                } else if info.terminal {
+                    "\\nsink"
+                } else {
+                    ""
+                };
+                write!(out, "        n{} [label=\"{}\\n", id.0, info.name)?;
+                match info.num_inputs {
+                    // Mixers take any number of inputs
+                    usize::MAX => write!(out, "any in")?,
+                    inputs => write!(out, "{} in", inputs)?,
+                }
+                write!(out, ", {} out{}\"", info.num_outputs, role)?;
+                if !role.is_empty() {
+                    write!(out, ", peripheries=2")?;
+                }
+                // Channel mixers were put in by the engine, like bridges
+                if self.remixers.values().any(|&mixer| mixer == id) {
+                    write!(out, ", style=dashed")?;
+                }
+                writeln!(out, "];")?;
+            }
+
+            // Bridge ends living in this graph
+            for &((_, to_graph), bridge) in &bridges {
+                if bridge.from_graph == graph_id {
+                    let (id, channels, to_rate) = (bridge.sink_node.0, bridge.channels, self.graph_rate(to_graph));
+                    writeln!(out, "        n{} [label=\"RtrbSink\\n{} ch to {} Hz\", style=dashed];", id, channels, to_rate)?;
+                }
+                if to_graph == graph_id {
+                    let (id, channels, from_rate) = (bridge.source_node.0, bridge.channels, self.graph_rate(bridge.from_graph));
+                    writeln!(out, "        n{} [label=\"ResamplingSource\\n{} ch from {} Hz\", style=dashed];", id, channels, from_rate)?;
+                }
+            }
+            writeln!(out, "    }}")?;
+        }
+
+        for &(from, to, edge) in &self.edges {
+            write_dot_edge(out, from, to, edge)?;
+        }
+        for &((from, to_graph), bridge) in &bridges {
+            write_dot_edge(out, from, bridge.sink_node, Edge::default())?;
+            let (from_rate, to_rate) = (self.graph_rate(bridge.from_graph), self.graph_rate(to_graph));
+            writeln!(
+                out,
+                "    n{} -> n{} [style=bold, label=\"{} -> {} Hz\"];",
+                bridge.sink_node.0, bridge.source_node.0, from_rate, to_rate
+            )?;
+            for &(to, ports) in &bridge.routes {
+                write_dot_edge(out, bridge.source_node, to, Edge { ports, feedback: false })?;
+            }
+        }
+
+        writeln!(out, "}}")
+    }
+
+    /// Start the transport from where it is.
+    ///
+    /// See [`Klingt::start_transport`](crate::Klingt::start_transport).
+    pub fn start_transport(&mut self) {
+        self.send_transport(TransportCommand::Start);
+    }
+
+    /// Stop the transport, keeping its position.
+    ///
+    /// See [`Klingt::stop_transport`](crate::Klingt::stop_transport).
+    pub fn stop_transport(&mut self) {
+        self.send_transport(TransportCommand::Stop);
+    }
+
+    /// Move the transport to `beat`.
+    ///
+    /// See [`Klingt::seek_transport`](crate::Klingt::seek_transport).
+    pub fn seek_transport(&mut self, beat: f64) {
+        self.send_transport(TransportCommand::Seek(beat));
+    }
+
+    /// Set a constant tempo in beats per minute.
+    ///
+    /// See [`Klingt::set_tempo`](crate::Klingt::set_tempo).
+    pub fn set_tempo(&mut self, bpm: f64) {
+        self.set_tempo_map(TempoMap::new(bpm));
+    }
+
+    /// Replace the tempo map.
+    ///
+    /// See [`Klingt::set_tempo_map`](crate::Klingt::set_tempo_map).
+    pub fn set_tempo_map(&mut self, tempo: TempoMap) {
+        self.send_transport(TransportCommand::SetTempo(tempo));
+    }
+
+    /// Set the time signature bars are counted in.
+    ///
+    /// See [`Klingt::set_time_signature`](crate::Klingt::set_time_signature).
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.send_transport(TransportCommand::SetTimeSignature(time_signature));
+    }
+
+    /// Loop the transport over a range of beats, or stop looping with `None`.
+    ///
+    /// See [`Klingt::set_loop`](crate::Klingt::set_loop).
+    pub fn set_loop(&mut self, beats: Option<Range<f64>>) {
+        if let Some(beats) = &beats {
+            assert!(beats.start < beats.end, "Loop must end after it starts, got {:?}", beats);
+        }
+        self.send_transport(TransportCommand::SetLoop(beats.map(|b| (b.start, b.end))));
+    }
+
+    fn send_transport(&mut self, command: TransportCommand) {
+        self.collect_garbage();
+        self.send(Command::Transport(command));
+    }
+
+    /// Drop whatever the processor handed back since the last call
+    pub(crate) fn collect_garbage(&mut self) {
+        while self.garbage.pop().is_ok() {}
+    }
+
+    /// Make sure the next `commands` commands fit in the queue
+    ///
+    /// Checked before an edit sends anything, so a full queue never leaves it half-applied.
+    fn reserve(&self, commands: usize) -> Result<(), KlingtError> {
+        if self.commands.slots() < commands {
+            return Err(KlingtError::CommandQueueFull);
+        }
+        Ok(())
+    }
+
+    fn node_info(&self, id: NodeId) -> Result<NodeInfo, KlingtError> {
+        self.nodes.get(&id).copied().ok_or(KlingtError::UnknownNode(id))
+    }
+
+    /// Sample rate of the graph with the given ID
+    fn graph_rate(&self, graph_id: GraphId) -> u32 {
+        if graph_id == 0 {
+            self.sample_rate
+        } else {
+            graph_id as u32
+        }
+    }
+
+    fn send(&mut self, command: Command) {
+        if self.commands.push(command).is_err() {
+            panic!("Command queue full - is the KlingtProcessor running?");
+        }
+    }
+
+    /// Whether an edge from `from` to `to` would close a loop, i.e. `to` already feeds `from`
+    fn creates_cycle(&self, from: NodeId, to: NodeId) -> bool {
+        let mut stack = alloc::vec![to];
+        let mut visited = Vec::new();
+        while let Some(node) = stack.pop() {
+            if node == from {
+                return true;
+            }
+            if !visited.contains(&node) {
+                visited.push(node);
+                let forward = self.edges.iter().filter(|&&(f, _, edge)| f == node && !edge.feedback);
+                stack.extend(forward.map(|&(_, t, _)| t));
+            }
+        }
+        false
+    }
+
+    fn next_id(&mut self) -> NodeId {
+        NodeId(NEXT_NODE_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Write one edge of [`KlingtController::to_dot`], labelled with its ports
+fn write_dot_edge(out: &mut String, from: NodeId, to: NodeId, edge: Edge) -> fmt::Result {
+    write!(out, "    n{} -> n{}", from.0, to.0)?;
+    match (edge.ports, edge.feedback) {
+        (None, false) => {}
+        (None, true) => write!(out, " [style=dashed, label=\"feedback\"]")?,
+        (Some(ports), false) => write!(out, " [label=\"{} -> {}\"]", ports.output, ports.input)?,
+        (Some(ports), true) => write!(out, " [style=dashed, label=\"feedback {} -> {}\"]", ports.output, ports.input)?,
+    }
+    writeln!(out, ";")
+}
+
+/// Name of `T` without its module path, e.g. `Sine` for `klingt::nodes::source::sine::Sine`
+fn short_type_name<T>() -> &'static str {
+    let full = core::any::type_name::<T>();
+    let path = full.find('<').map_or(full, |i| &full[..i]);
+    path.rfind("::").map_or(full, |i| &full[i + 2..])
+}
+
+/// Where a bridge's producer runs relative to its consumer
+struct BridgeTiming {
+    /// The producer runs [`LEAD_BLOCKS`] ahead of the output
+    producer_ahead: bool,
+    /// The producer is processed after the consumer, so it's a block behind
+    back_edge: bool,
+}
+
+impl BridgeTiming {
+    /// Frames of silence the resampler needs to start with so it never reads
+    /// further than its producer has written
+    ///
+    /// Within one [`KlingtProcessor::process`](crate::KlingtProcessor::process) call,
+    /// the consumer can get up to a block (plus the resampler's interpolation frames)
+    /// ahead of the producer. Producers running ahead can be up to the lead further
+    /// behind a consumer that also runs ahead, and producers on a back edge lag by
+    /// one main-graph block.
+    fn delay(&self, block_size: usize, main_rate: u32, from_rate: u32, to_rate: u32) -> usize {
+        let mut delay = block_size + 3 * (to_rate as usize).div_ceil(from_rate as usize) + 2;
+        if self.producer_ahead {
+            delay += LEAD_BLOCKS as usize * block_size;
+        }
+        if self.back_edge {
+            delay += (block_size as u64 * to_rate as u64).div_ceil(main_rate as u64) as usize;
+        }
+        delay
+    }
+}
@@ -0,0 +1,483 @@
+//! Declarative graph descriptions and the registry of node types they're built from
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+
+use hashbrown::HashMap;
+
+use crate::command::GraphId;
+use crate::controller::KlingtController;
+use crate::error::KlingtError;
+use crate::handle::NodeHandle;
+use crate::node::{AudioNode, NodeId};
+use crate::nodes::{Gain, Metronome, Mixer, SamplePlayer, Sine, SlewLimiter};
+
+/// A whole patch as data: which nodes to create, how to connect them and what
+/// goes to the output.
+///
+/// Build it in code or, with the `serde` feature, load it from any format serde
+/// supports. Turn it into nodes with [`Klingt::from_description`](crate::Klingt::from_description).
+///
+/// # Example
+///
+/// ```
+/// use klingt::{ConnectionDescription, GraphDescription, NodeDescription};
+///
+/// let patch = GraphDescription::new()
+///     .with_node(NodeDescription::new("osc", "Sine").with_param("frequency", 440.0))
+///     .with_node(NodeDescription::new("vol", "Gain").with_param("gain", 0.5))
+///     .with_connection(ConnectionDescription::new("osc", "vol"))
+///     .with_output("vol");
+/// ```
+///
+/// As JSON:
+///
+/// ```json
+/// {
+///   "nodes": [
+///     { "name": "osc", "type": "Sine", "params": { "frequency": 440.0 } },
+///     { "name": "vol", "type": "Gain", "params": { "gain": 0.5 } }
+///   ],
+///   "connections": [{ "from": "osc", "to": "vol" }],
+///   "output": ["vol"]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphDescription {
+    /// Nodes to create, each with a unique name
+    pub nodes: Vec<NodeDescription>,
+    /// Connections between the nodes, by name
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub connections: Vec<ConnectionDescription>,
+    /// Names of the nodes connected to the output
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub output: Vec<String>,
+}
+
+impl GraphDescription {
+    /// An empty description.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a node (builder pattern).
+    pub fn with_node(mut self, node: NodeDescription) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    /// Add a connection (builder pattern).
+    pub fn with_connection(mut self, connection: ConnectionDescription) -> Self {
+        self.connections.push(connection);
+        self
+    }
+
+    /// Connect the node named `name` to the output (builder pattern).
+    pub fn with_output(mut self, name: impl Into<String>) -> Self {
+        self.output.push(name.into());
+        self
+    }
+}
+
+/// One node of a [`GraphDescription`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeDescription {
+    /// Name the node is connected and looked up by
+    pub name: String,
+    /// Node type, as registered in the [`NodeRegistry`] (e.g. `"Sine"`)
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub kind: String,
+    /// Constructor parameters, passed to the type's factory
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+impl NodeDescription {
+    /// A node of type `kind` named `name`, without parameters.
+    pub fn new(name: impl Into<String>, kind: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind: kind.into(),
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Set a constructor parameter (builder pattern).
+    pub fn with_param(mut self, key: impl Into<String>, value: impl Into<ParamValue>) -> Self {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// One connection of a [`GraphDescription`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionDescription {
+    /// Name of the source node
+    pub from: String,
+    /// Name of the destination node
+    pub to: String,
+    /// Output channel of `from` and input port of `to`, like
+    /// [`Klingt::connect_ports`](crate::Klingt::connect_ports). `None` connects every channel.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ports: Option<(usize, usize)>,
+    /// Connect through a one-block delay, like [`Klingt::connect_feedback`](crate::Klingt::connect_feedback)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub feedback: bool,
+}
+
+impl ConnectionDescription {
+    /// Connect every channel of `from` to `to`.
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            ports: None,
+            feedback: false,
+        }
+    }
+
+    /// Connect only output channel `output` to input port `input` (builder pattern).
+    pub fn with_ports(mut self, output: usize, input: usize) -> Self {
+        self.ports = Some((output, input));
+        self
+    }
+
+    /// Make this a feedback connection (builder pattern).
+    pub fn with_feedback(mut self) -> Self {
+        self.feedback = true;
+        self
+    }
+}
+
+/// A constructor parameter value.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(untagged))]
+pub enum ParamValue {
+    /// A flag
+    Bool(bool),
+    /// Any number
+    Number(f64),
+    /// Text, e.g. a file path for a custom factory
+    Text(String),
+    /// A list of numbers, e.g. samples
+    Numbers(Vec<f32>),
+}
+
+impl From<bool> for ParamValue {
+    fn from(value: bool) -> Self {
+        ParamValue::Bool(value)
+    }
+}
+
+impl From<f64> for ParamValue {
+    fn from(value: f64) -> Self {
+        ParamValue::Number(value)
+    }
+}
+
+impl From<f32> for ParamValue {
+    fn from(value: f32) -> Self {
+        ParamValue::Number(value as f64)
+    }
+}
+
+impl From<u32> for ParamValue {
+    fn from(value: u32) -> Self {
+        ParamValue::Number(value as f64)
+    }
+}
+
+impl From<&str> for ParamValue {
+    fn from(value: &str) -> Self {
+        ParamValue::Text(value.into())
+    }
+}
+
+impl From<String> for ParamValue {
+    fn from(value: String) -> Self {
+        ParamValue::Text(value)
+    }
+}
+
+impl From<Vec<f32>> for ParamValue {
+    fn from(value: Vec<f32>) -> Self {
+        ParamValue::Numbers(value)
+    }
+}
+
+/// The parameters of one node, as handed to its factory.
+///
+/// The accessors check the type of each value and report mistakes as
+/// [`KlingtError::InvalidParam`] naming the node and parameter.
+pub struct Params<'a> {
+    node: &'a NodeDescription,
+}
+
+impl<'a> Params<'a> {
+    /// Name of the node being created
+    pub fn name(&self) -> &'a str {
+        &self.node.name
+    }
+
+    /// A required number.
+    pub fn number(&self, key: &str) -> Result<f64, KlingtError> {
+        self.number_opt(key)?.ok_or_else(|| self.error(key, "missing"))
+    }
+
+    /// A number, or `default` if it's not given.
+    pub fn number_or(&self, key: &str, default: f64) -> Result<f64, KlingtError> {
+        Ok(self.number_opt(key)?.unwrap_or(default))
+    }
+
+    /// A required whole, non-negative number, e.g. a channel count.
+    pub fn integer(&self, key: &str) -> Result<usize, KlingtError> {
+        self.integer_opt(key)?.ok_or_else(|| self.error(key, "missing"))
+    }
+
+    /// A whole, non-negative number, or `default` if it's not given.
+    pub fn integer_or(&self, key: &str, default: usize) -> Result<usize, KlingtError> {
+        Ok(self.integer_opt(key)?.unwrap_or(default))
+    }
+
+    /// A flag, or `default` if it's not given.
+    pub fn bool_or(&self, key: &str, default: bool) -> Result<bool, KlingtError> {
+        match self.node.params.get(key) {
+            None => Ok(default),
+            Some(ParamValue::Bool(value)) => Ok(*value),
+            Some(_) => Err(self.error(key, "expected true or false")),
+        }
+    }
+
+    /// Required text.
+    pub fn text(&self, key: &str) -> Result<&'a str, KlingtError> {
+        match self.node.params.get(key) {
+            None => Err(self.error(key, "missing")),
+            Some(ParamValue::Text(value)) => Ok(value),
+            Some(_) => Err(self.error(key, "expected text")),
+        }
+    }
+
+    /// A required list of numbers.
+    pub fn numbers(&self, key: &str) -> Result<&'a [f32], KlingtError> {
+        match self.node.params.get(key) {
+            None => Err(self.error(key, "missing")),
+            Some(ParamValue::Numbers(value)) => Ok(value),
+            Some(_) => Err(self.error(key, "expected a list of numbers")),
+        }
+    }
+
+    /// An error about parameter `key` of this node, for factories to report
+    /// problems the accessors can't catch.
+    pub fn error(&self, key: &str, reason: impl Into<String>) -> KlingtError {
+        KlingtError::InvalidParam {
+            node: self.node.name.clone(),
+            param: key.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    fn number_opt(&self, key: &str) -> Result<Option<f64>, KlingtError> {
+        match self.node.params.get(key) {
+            None => Ok(None),
+            Some(ParamValue::Number(value)) => Ok(Some(*value)),
+            Some(_) => Err(self.error(key, "expected a number")),
+        }
+    }
+
+    fn integer_opt(&self, key: &str) -> Result<Option<usize>, KlingtError> {
+        match self.number_opt(key)? {
+            Some(value) if value < 0.0 || value.fract() != 0.0 => Err(self.error(key, "expected a whole number")),
+            value => Ok(value.map(|v| v as usize)),
+        }
+    }
+}
+
+/// Creates a node from its parameters, ready to be added
+type Factory = Box<dyn Fn(&Params) -> Result<Box<dyn PendingNode>, KlingtError> + Send + Sync>;
+
+/// Maps node type names to factories, for building [`GraphDescription`]s.
+///
+/// [`new`](Self::new) knows the built-in nodes:
+///
+/// | Type           | Parameters |
+/// |----------------|------------|
+/// | `Sine`         | `frequency`, `amplitude` (0.25) |
+/// | `Gain`         | `gain` (1.0), `smoothing` (true), `channels` (2) |
+/// | `Mixer`        | `channels` (2) |
+/// | `SlewLimiter`  | `rate`, `channels` (2) |
+/// | `SamplePlayer` | `samples` (interleaved), `sample_rate`, `channels` (1), `looping` (false) |
+/// | `Metronome`    | `volume` (0.5) |
+///
+/// Parameters with a value in parentheses are optional.
+///
+/// # Example
+///
+/// ```
+/// use klingt::{NodeRegistry, nodes::Sine};
+///
+/// let mut registry = NodeRegistry::new();
+/// // A detuned sine, created from a description like any built-in node
+/// registry.register("DetunedSine", |params| {
+///     let cents = params.number_or("cents", 0.0)?;
+///     Ok(Sine::new((params.number("frequency")? * 2f64.powf(cents / 1200.0)) as f32))
+/// });
+/// ```
+pub struct NodeRegistry {
+    factories: HashMap<String, Factory>,
+}
+
+impl NodeRegistry {
+    /// A registry with the built-in nodes.
+    pub fn new() -> Self {
+        let mut registry = Self { factories: HashMap::new() };
+        registry.register("Sine", |p| {
+            Ok(Sine::new(p.number("frequency")? as f32).with_amplitude(p.number_or("amplitude", 0.25)? as f32))
+        });
+        registry.register("Gain", |p| {
+            let gain = Gain::new(p.number_or("gain", 1.0)? as f32).with_channels(p.integer_or("channels", 2)?);
+            Ok(if p.bool_or("smoothing", true)? { gain } else { gain.without_smoothing() })
+        });
+        registry.register("Mixer", |p| Ok(Mixer::new(p.integer_or("channels", 2)?)));
+        registry.register("SlewLimiter", |p| {
+            Ok(SlewLimiter::new(p.number("rate")? as f32).with_channels(p.integer_or("channels", 2)?))
+        });
+        registry.register("SamplePlayer", |p| {
+            let sample_rate = p.integer("sample_rate")?;
+            if sample_rate == 0 {
+                return Err(p.error("sample_rate", "must be positive"));
+            }
+            let mut player = SamplePlayer::new(p.numbers("samples")?.to_vec(), p.integer_or("channels", 1)?, sample_rate as u32);
+            player.set_looping(p.bool_or("looping", false)?);
+            Ok(player)
+        });
+        registry.register("Metronome", |p| Ok(Metronome::new().with_volume(p.number_or("volume", 0.5)? as f32)));
+        registry
+    }
+
+    /// Register a node type, replacing any type of the same name.
+    ///
+    /// `factory` creates the node from the parameters of its description.
+    pub fn register<N, F>(&mut self, kind: impl Into<String>, factory: F)
+    where
+        N: AudioNode,
+        F: Fn(&Params) -> Result<N, KlingtError> + Send + Sync + 'static,
+    {
+        let factory: Factory = Box::new(move |params| Ok(Box::new(factory(params)?) as Box<dyn PendingNode>));
+        self.factories.insert(kind.into(), factory);
+    }
+
+    /// Whether a node type of this name is registered
+    pub fn contains(&self, kind: &str) -> bool {
+        self.factories.contains_key(kind)
+    }
+
+    /// Create the node for a description, not yet added anywhere
+    pub(crate) fn create(&self, node: &NodeDescription) -> Result<Box<dyn PendingNode>, KlingtError> {
+        let factory = self.factories.get(&node.kind).ok_or_else(|| KlingtError::UnknownNodeType(node.kind.clone()))?;
+        factory(&Params { node })
+    }
+}
+
+impl Default for NodeRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A node created from a description, waiting to be added
+pub(crate) trait PendingNode {
+    fn add(self: Box<Self>, controller: &mut KlingtController) -> Result<NamedHandle, KlingtError>;
+}
+
+impl<N: AudioNode> PendingNode for N {
+    fn add(self: Box<Self>, controller: &mut KlingtController) -> Result<NamedHandle, KlingtError> {
+        let handle = controller.try_add(*self)?;
+        Ok(NamedHandle {
+            node_id: handle.node_id,
+            graph_id: handle.graph_id,
+            node_type: TypeId::of::<N>(),
+            handle: Box::new(handle),
+        })
+    }
+}
+
+/// A handle whose node type is only known at runtime
+pub(crate) struct NamedHandle {
+    pub node_id: NodeId,
+    pub graph_id: GraphId,
+    /// The node's own type. Nodes with the same message, event and state
+    /// types share a handle type, so the handle alone can't tell them apart
+    node_type: TypeId,
+    /// The [`NodeHandle`] of the node
+    handle: Box<dyn Any + Send>,
+}
+
+impl NamedHandle {
+    fn is<N: AudioNode>(&self) -> bool {
+        self.node_type == TypeId::of::<N>()
+    }
+}
+
+/// The handles of a graph built from a [`GraphDescription`], by node name.
+///
+/// Ask for a handle along with the type of its node; asking for the wrong type
+/// returns `None`.
+///
+/// # Example
+///
+/// ```
+/// # use klingt::{Klingt, GraphDescription, NodeDescription, NodeRegistry};
+/// use klingt::nodes::{Sine, SineMessage};
+///
+/// let mut klingt = Klingt::offline(48000);
+/// let patch = GraphDescription::new()
+///     .with_node(NodeDescription::new("osc", "Sine").with_param("frequency", 440.0))
+///     .with_output("osc");
+/// let mut handles = klingt.from_description(&patch, &NodeRegistry::new());
+///
+/// let osc = handles.get_mut::<Sine>("osc").unwrap();
+/// osc.send(SineMessage::SetFrequency(220.0)).ok();
+/// ```
+pub struct NamedHandles {
+    handles: HashMap<String, NamedHandle>,
+}
+
+impl NamedHandles {
+    pub(crate) fn new(handles: HashMap<String, NamedHandle>) -> Self {
+        Self { handles }
+    }
+
+    /// The handle of the node named `name`, e.g. to connect it to other nodes.
+    pub fn get<N: AudioNode>(&self, name: &str) -> Option<&NodeHandle<N>> {
+        let named = self.handles.get(name).filter(|named| named.is::<N>())?;
+        named.handle.downcast_ref()
+    }
+
+    /// The handle of the node named `name`, e.g. to send it messages.
+    pub fn get_mut<N: AudioNode>(&mut self, name: &str) -> Option<&mut NodeHandle<N>> {
+        let named = self.handles.get_mut(name).filter(|named| named.is::<N>())?;
+        named.handle.downcast_mut()
+    }
+
+    /// Take the handle of the node named `name` out, e.g. to remove the node.
+    ///
+    /// The handle stays here if `N` is the wrong type.
+    pub fn take<N: AudioNode>(&mut self, name: &str) -> Option<NodeHandle<N>> {
+        if !self.handles.get(name)?.is::<N>() {
+            return None;
+        }
+        let handle = self.handles.remove(name)?.handle;
+        handle.downcast().ok().map(|handle| *handle)
+    }
+
+    /// Names of the nodes whose handles are still here
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.handles.keys().map(|name| name.as_str())
+    }
+}
@@ -24,7 +24,11 @@ use alloc::string::String;
 use alloc::vec::Vec;
 
 #[cfg(feature = "cpal_sink")]
-use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+#[cfg(feature = "cpal_sink")]
+use cpal::{FromSample, SampleFormat, SizedSample};
+
+use crate::KlingtProcessor;
 
 /// A discovered audio output device.
 ///
@@ -147,4 +151,80 @@ impl CpalDevice {
     pub fn create_sink(&self) -> crate::nodes::CpalSink {
         crate::nodes::CpalSink::new(&self.device, &self.config)
     }
+
+    /// Start an output stream that pulls audio from a [`KlingtProcessor`].
+    ///
+    /// The processor is moved into the device's audio callback and renders exactly
+    /// as many samples as the device asks for, so there's no need for a processing
+    /// loop on another thread. The stream plays until the returned [`CpalStream`]
+    /// is dropped.
+    ///
+    /// The processor should come from [`Klingt::for_device`](crate::Klingt::for_device),
+    /// so that its sample rate and channel count match the device.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use klingt::{Klingt, CpalDevice, nodes::Sine};
+    /// let device = CpalDevice::default_output().unwrap();
+    /// let (mut controller, processor) = Klingt::for_device(&device).split();
+    /// let _stream = device.play(processor).expect("Failed to start audio stream");
+    ///
+    /// let sine = controller.add(Sine::new(440.0));
+    /// controller.output(&sine);
+    /// ```
+    #[cfg(feature = "cpal_sink")]
+    pub fn play(&self, processor: KlingtProcessor) -> Result<CpalStream, cpal::BuildStreamError> {
+        let config = self.config.config();
+
+        let stream = match self.config.sample_format() {
+            SampleFormat::F32 => build_processor_stream::<f32>(&self.device, &config, processor)?,
+            SampleFormat::I16 => build_processor_stream::<i16>(&self.device, &config, processor)?,
+            SampleFormat::U16 => build_processor_stream::<u16>(&self.device, &config, processor)?,
+            _ => return Err(cpal::BuildStreamError::StreamConfigNotSupported),
+        };
+
+        stream.play().map_err(|err| match err {
+            cpal::PlayStreamError::DeviceNotAvailable => cpal::BuildStreamError::DeviceNotAvailable,
+            cpal::PlayStreamError::BackendSpecific { err } => cpal::BuildStreamError::BackendSpecific { err },
+        })?;
+
+        Ok(CpalStream { _stream: stream })
+    }
+}
+
+/// An output stream driven by a [`KlingtProcessor`].
+///
+/// Returned by [`CpalDevice::play`]. Audio stops when this is dropped.
+#[cfg(feature = "cpal_sink")]
+pub struct CpalStream {
+    _stream: cpal::Stream,
+}
+
+#[cfg(feature = "cpal_sink")]
+fn build_processor_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut processor: KlingtProcessor,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    // Render scratch, allocated here so the callback doesn't have to
+    let mut scratch = alloc::vec![0.0f32; 8192];
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            for chunk in data.chunks_mut(scratch.len()) {
+                let rendered = &mut scratch[..chunk.len()];
+                processor.render_into(rendered);
+                for (out, &sample) in chunk.iter_mut().zip(rendered.iter()) {
+                    *out = T::from_sample(sample.clamp(-1.0, 1.0));
+                }
+            }
+        },
+        |err| eprintln!("CPAL stream error: {:?}", err),
+        None,
+    )
 }
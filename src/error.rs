@@ -0,0 +1,76 @@
+//! Errors reported by the fallible graph-building API
+
+use alloc::string::String;
+use core::fmt;
+
+use crate::node::NodeId;
+
+/// Why a graph edit couldn't be made.
+///
+/// Returned by the `try_*` methods of [`Klingt`](crate::Klingt) and
+/// [`KlingtController`](crate::KlingtController). Their panicking counterparts
+/// panic with this error's message instead. A failed edit leaves the graph untouched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KlingtError {
+    /// The handle refers to a node that isn't part of this engine.
+    UnknownNode(NodeId),
+    /// [`output`](crate::Klingt::output) was called without an output sink configured.
+    NoOutputSink,
+    /// The output port doesn't exist on the source node.
+    InvalidOutputPort { port: usize, num_outputs: usize },
+    /// The input port doesn't exist on the destination node.
+    InvalidInputPort { port: usize, num_inputs: usize },
+    /// Connecting these nodes would close a loop. Use
+    /// [`connect_feedback`](crate::Klingt::connect_feedback) for feedback loops.
+    Cycle { from: NodeId, to: NodeId },
+    /// The command queue to the processor is full. Nothing was sent; try again
+    /// once the processor has caught up.
+    CommandQueueFull,
+    /// A node was added at a sample rate of zero.
+    InvalidSampleRate,
+    /// A graph description uses a node type that isn't registered.
+    UnknownNodeType(String),
+    /// A graph description refers to a node name it doesn't define.
+    UnknownName(String),
+    /// A graph description defines two nodes with the same name.
+    DuplicateName(String),
+    /// A node's parameter in a graph description is missing or has the wrong type.
+    InvalidParam { node: String, param: String, reason: String },
+}
+
+impl fmt::Display for KlingtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KlingtError::UnknownNode(id) => write!(f, "Unknown node {:?} - was it added to a different engine?", id),
+            KlingtError::NoOutputSink => write!(f, "No output sink configured. Use default_output() or with_output()."),
+            KlingtError::InvalidOutputPort { port, num_outputs } => write!(
+                f,
+                "Output port {} out of range: node has {} outputs",
+                port, num_outputs
+            ),
+            KlingtError::InvalidInputPort { port, num_inputs } => write!(
+                f,
+                "Input port {} out of range: node has {} inputs",
+                port, num_inputs
+            ),
+            KlingtError::Cycle { from, to } => write!(
+                f,
+                "Connecting {:?} to {:?} would create a cycle - use connect_feedback() for feedback loops",
+                from, to
+            ),
+            KlingtError::CommandQueueFull => write!(f, "Command queue full - is the KlingtProcessor running?"),
+            KlingtError::InvalidSampleRate => write!(f, "Sample rate must be greater than zero"),
+            KlingtError::UnknownNodeType(kind) => write!(f, "No node type {:?} in the registry", kind),
+            KlingtError::UnknownName(name) => write!(f, "No node named {:?} in the description", name),
+            KlingtError::DuplicateName(name) => write!(f, "More than one node named {:?} in the description", name),
+            KlingtError::InvalidParam { node, param, reason } => write!(
+                f,
+                "Parameter {:?} of node {:?}: {}",
+                param, node, reason
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for KlingtError {}
@@ -1,161 +1,950 @@
-//! Audio graph - owns nodes and message queues
+//! Audio graph - owns nodes and their message queues
 
 use alloc::boxed::Box;
-use core::marker::PhantomData;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use dasp_graph::{Buffer, Input, NodeData, Processor};
 use hashbrown::HashMap;
-use petgraph::graph::NodeIndex;
+use petgraph::algo::{has_path_connecting, DfsSpace};
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::visit::{DfsPostOrder, EdgeFiltered, EdgeRef, Reversed};
+use petgraph::Direction;
 use rtrb::{Consumer, Producer, RingBuffer};
 
+use crate::buffer::{Buffer, Input};
+use crate::error::KlingtError;
 use crate::node::{AudioNode, NodeId, ProcessContext};
+#[cfg(feature = "std")]
+use crate::pool::{wait_until, WorkerPool};
+use crate::simd;
+use crate::snapshot::{snapshot, Publisher, Reader};
+use crate::transport::{Segments, Transport};
 
-/// Internal handle to send messages to a node in an AudioGraph
-pub(crate) struct NodeHandle<M: Send + 'static> {
-    pub(crate) id: NodeId,
-    pub(crate) sender: Producer<M>,
-    pub(crate) _marker: PhantomData<M>,
+/// Samples per block unless configured otherwise
+pub(crate) const DEFAULT_BLOCK_SIZE: usize = 64;
+/// Smallest supported block size
+pub(crate) const MIN_BLOCK_SIZE: usize = 16;
+/// Largest supported block size
+pub(crate) const MAX_BLOCK_SIZE: usize = 2048;
+
+/// A message along with the output frame it's due at
+pub(crate) struct Timed<M> {
+    pub frame: u64,
+    pub message: M,
 }
 
-impl<M: Send + 'static> NodeHandle<M> {
-    /// Send a message to the node (applied next process cycle)
-    /// 
-    /// Returns Err if the queue is full (message dropped)
-    #[allow(dead_code)]
-    pub fn send(&mut self, msg: M) -> Result<(), M> {
-        self.sender.push(msg).map_err(|rtrb::PushError::Full(v)| v)
+impl<M> Timed<M> {
+    /// A message that's due right away
+    pub fn now(message: M) -> Self {
+        Self { frame: 0, message }
     }
-    
-    pub fn id(&self) -> NodeId {
-        self.id
+}
+
+/// Where a graph's current block sits in time
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Timeline {
+    /// The graph's frame at the start of the block
+    pub frame: u64,
+    /// Output frames per frame of the graph
+    pub step: f64,
+}
+
+impl Timeline {
+    /// Offset into the block of the first sample at or after output frame `frame`
+    fn offset(&self, frame: u64) -> usize {
+        let ahead = frame as f64 / self.step - self.frame as f64;
+        if ahead <= 0.0 {
+            0
+        } else {
+            ahead.ceil() as usize
+        }
     }
 }
 
 // Type-erased wrapper so we can store heterogeneous nodes
 trait ErasedNode: Send {
-    fn process_erased(&mut self, ctx: &ProcessContext, inputs: &[Input], outputs: &mut [Buffer]);
+    /// Where the segment of the block starting at `start` ends: at the next
+    /// message due after it, or at `block_size`
+    fn segment_end(&mut self, timeline: &Timeline, start: usize, block_size: usize) -> usize;
+
+    /// Whether the node is idle and no message for it is due within the block
+    ///
+    /// Only looks at messages [`segment_end`](Self::segment_end) has taken off the queue.
+    fn sleeps(&self, timeline: &Timeline, block_size: usize) -> bool;
+
+    /// Process the segment starting at `start`, with the messages due by then
+    fn process_erased(
+        &mut self,
+        ctx: &ProcessContext,
+        timeline: &Timeline,
+        start: usize,
+        inputs: &[Input],
+        outputs: &mut [Buffer],
+    );
 }
 
 struct NodeWrapper<N: AudioNode> {
     node: N,
-    receiver: Consumer<N::Message>,
+    receiver: Consumer<Timed<N::Message>>,
+    /// Messages taken off the queue, ordered by when they're due. Never grows
+    /// past its initial capacity.
+    pending: VecDeque<Timed<N::Message>>,
+    events: Producer<N::Event>,
+    state: Option<Publisher<N::State>>,
 }
 
 impl<N: AudioNode> ErasedNode for NodeWrapper<N> {
-    fn process_erased(&mut self, ctx: &ProcessContext, inputs: &[Input], outputs: &mut [Buffer]) {
-        // Split borrow to avoid conflict between receiver and node
-        let receiver = &mut self.receiver;
+    fn segment_end(&mut self, timeline: &Timeline, start: usize, block_size: usize) -> usize {
+        while self.pending.len() < self.pending.capacity() {
+            let message = match self.receiver.pop() {
+                Ok(message) => message,
+                Err(_) => break,
+            };
+            // After everything due at the same time, so messages stay in order
+            let index = self.pending.iter().position(|m| m.frame > message.frame).unwrap_or(self.pending.len());
+            self.pending.insert(index, message);
+        }
+
+        self.pending
+            .iter()
+            .map(|m| timeline.offset(m.frame))
+            .find(|&offset| offset > start)
+            .map_or(block_size, |offset| offset.min(block_size))
+    }
+
+    fn sleeps(&self, timeline: &Timeline, block_size: usize) -> bool {
+        self.node.is_idle() && self.pending.iter().all(|m| timeline.offset(m.frame) >= block_size)
+    }
+
+    fn process_erased(
+        &mut self,
+        ctx: &ProcessContext,
+        timeline: &Timeline,
+        start: usize,
+        inputs: &[Input],
+        outputs: &mut [Buffer],
+    ) {
+        // Split borrow to avoid conflict between the pending messages and node
+        let pending = &mut self.pending;
         let node = &mut self.node;
-        
-        // Create a draining iterator directly from the consumer - no allocation!
-        let messages = core::iter::from_fn(|| receiver.pop().ok());
+
+        // Drain the messages that are due - no allocation!
+        let messages = core::iter::from_fn(|| match pending.front() {
+            Some(m) if timeline.offset(m.frame) <= start => pending.pop_front().map(|m| m.message),
+            _ => None,
+        });
         node.process(ctx, messages, inputs, outputs);
+
+        // Events stay with the node while the queue is full
+        while !self.events.is_full() {
+            match self.node.next_event() {
+                Some(event) => {
+                    let _ = self.events.push(event);
+                }
+                None => break,
+            }
+        }
+
+        if let Some(publisher) = &mut self.state {
+            if let Some(state) = self.node.state() {
+                publisher.publish(state);
+            }
+        }
     }
 }
 
-// Adapter for dasp_graph
-struct DaspAdapter {
+/// A node ready to be inserted into an [`AudioGraph`], along with its output buffers
+///
+/// Built on the controller side so that all allocation (boxing, buffers,
+/// message queue) happens off the audio thread.
+pub(crate) struct GraphNode {
+    /// Kept alongside the node so index swaps on removal can be fixed up
+    id: NodeId,
     node: Box<dyn ErasedNode>,
+    /// One buffer per output channel. Lent to the graph's slots while the node
+    /// is in a graph, and handed back when it's removed.
+    buffers: Vec<Buffer>,
+    /// How many buffers the node brought along
+    channels: usize,
+    /// Output of the previous block, read by feedback edges
+    previous: Vec<Buffer>,
+}
+
+/// Wrap a node for insertion into a graph running with `block_size`
+///
+/// Returns the node along with the controller's ends of its queues.
+pub(crate) fn prepare_node<N: AudioNode>(
+    id: NodeId,
+    node: N,
+    block_size: usize,
+    queue_size: usize,
+) -> (GraphNode, NodeChannels<N>) {
+    let (producer, consumer) = RingBuffer::new(queue_size);
+    let (event_producer, event_consumer) = RingBuffer::new(queue_size);
+    let (publisher, reader) = match node.state() {
+        Some(state) => {
+            let (publisher, reader) = snapshot(state);
+            (Some(publisher), Some(reader))
+        }
+        None => (None, None),
+    };
+
+    // 0 outputs = sink, but keep a buffer around for consistency
+    let num_buffers = node.num_outputs().max(1);
+    let wrapper = NodeWrapper {
+        node,
+        receiver: consumer,
+        pending: VecDeque::with_capacity(queue_size),
+        events: event_producer,
+        state: publisher,
+    };
+
+    let graph_node = GraphNode {
+        id,
+        node: Box::new(wrapper),
+        buffers: alloc::vec![Buffer::new(block_size); num_buffers],
+        channels: num_buffers,
+        previous: alloc::vec![Buffer::new(block_size); num_buffers],
+    };
+
+    (graph_node, NodeChannels { sender: producer, events: event_consumer, state: reader })
+}
+
+/// The controller's ends of a node's queues, from [`prepare_node`]
+pub(crate) struct NodeChannels<N: AudioNode> {
+    pub sender: Producer<Timed<N::Message>>,
+    pub events: Consumer<N::Event>,
+    /// Only for nodes that publish their state
+    pub state: Option<Reader<N::State>>,
+}
+
+/// Which output channel of the source feeds which input port of the destination
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Ports {
+    pub output: usize,
+    pub input: usize,
+}
+
+/// Edge weight
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub(crate) struct Edge {
+    /// `None` for plain connections that carry every output channel
+    pub ports: Option<Ports>,
+    /// Carries the source's output from the previous block, so it may close a loop
+    pub feedback: bool,
+}
+
+type InnerGraph = petgraph::graph::Graph<GraphNode, Edge>;
+
+type VisitMap = <InnerGraph as petgraph::visit::Visitable>::Map;
+
+/// Edges that are followed when ordering nodes
+fn is_forward<E: EdgeRef<Weight = Edge>>(edge: E) -> bool {
+    !edge.weight().feedback
+}
+
+/// Build the [`Input`] an edge delivers from the source's `buffers`
+fn edge_input(buffers: &[Buffer], ports: Option<Ports>) -> Input {
+    match ports {
+        None => Input::new(buffers, None),
+        Some(ports) => {
+            // Nodes may report more outputs than they have buffers for
+            let channel = ports.output.min(buffers.len().saturating_sub(1));
+            Input::new(&buffers[channel..(channel + 1).min(buffers.len())], Some(ports.input))
+        }
+    }
+}
+
+/// Marks nodes without a slot while compiling
+const NO_SLOT: usize = usize::MAX;
+
+/// Where an input of a step reads from
+#[derive(Clone, Copy, Debug)]
+enum Source {
+    /// The slot of a node processed earlier in the block
+    Slot(usize),
+    /// The previous block of a node, through a feedback edge
+    Previous(NodeIndex),
+}
+
+/// One node's turn in a block
+struct Step {
+    index: NodeIndex,
+    /// Stays put until the graph changes. Only used by worker threads, which
+    /// can't index the graph while others write to it.
+    node: *mut GraphNode,
+    /// Where the node writes its output
+    slot: usize,
+    /// The node's inputs, as a range of `Plan::inputs`
+    inputs: (usize, usize),
+    /// 0 for nodes without inputs, otherwise one more than the deepest input
+    level: usize,
+}
+
+// Safety: the pointer is only dereferenced while the graph it points into is processed
+unsafe impl Send for Step {}
+
+/// The order nodes are processed in and where their audio goes
+///
+/// Compiled whenever the graph changes, so processing a block doesn't have to
+/// look at the graph at all.
+///
+/// Nodes are ordered by dependency level, which puts every node after its
+/// inputs. Each node writes into a slot with one buffer per channel. Slots are
+/// reused: once the last node reading a slot's audio is on a lower level than
+/// the one writing to it next, it's free again. Every node lends the graph a
+/// slot of its own size when it's inserted, so there are always enough.
+///
+/// Compiling happens on the audio thread. It reuses the plan's storage, so it
+/// only allocates when the graph has more nodes or edges than ever before,
+/// past the room the graph starts out with.
+struct Plan {
+    steps: Vec<Step>,
+    /// Inputs of every step, back to back, pointing into slots and `previous` buffers
+    inputs: Vec<Input>,
+    /// Where each of `inputs` reads from
+    sources: Vec<Source>,
+    /// Nodes read by feedback edges, along with their slots
+    feedback: Vec<(NodeIndex, usize)>,
+    /// The first input of the terminal, which gets rendered
+    terminal_input: Option<Input>,
+
+    // Scratch space for compiling, kept to avoid reallocating. All by node index.
+    levels: Vec<usize>,
+    slots: Vec<usize>,
+    /// Highest level reading a node's output
+    last_use: Vec<usize>,
+    free: Vec<usize>,
+    /// Slots in use, along with the highest level that reads them
+    live: Vec<(usize, usize)>,
+}
+
+impl Plan {
+    /// An empty plan with room for `nodes` nodes and as many edges
+    fn with_capacity(nodes: usize) -> Self {
+        Self {
+            steps: Vec::with_capacity(nodes),
+            inputs: Vec::with_capacity(nodes),
+            sources: Vec::with_capacity(nodes),
+            feedback: Vec::with_capacity(nodes),
+            terminal_input: None,
+            levels: Vec::with_capacity(nodes),
+            slots: Vec::with_capacity(nodes),
+            last_use: Vec::with_capacity(nodes),
+            free: Vec::with_capacity(nodes),
+            live: Vec::with_capacity(nodes),
+        }
+    }
+}
+
+/// A step's place in a block processed by a [`WorkerPool`]
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+struct Task {
+    step: usize,
+    /// Whether the node is processed in segments
+    split: bool,
+    /// How many tasks before this one have to be done before it can start
+    after: usize,
+}
+
+/// A block being processed by several threads
+#[cfg(feature = "std")]
+struct Block<'a> {
+    graph: *mut AudioGraph,
+    /// The graph's slots
+    slots: *mut Vec<Buffer>,
+    tasks: &'a [Task],
     ctx: ProcessContext,
+    timeline: Timeline,
+    /// Next task to hand out
+    next: AtomicUsize,
+    /// Tasks finished so far
+    done: AtomicUsize,
 }
 
-impl dasp_graph::Node for DaspAdapter {
-    fn process(&mut self, inputs: &[Input], outputs: &mut [Buffer]) {
-        self.node.process_erased(&self.ctx, inputs, outputs);
+// Safety: threads only touch the nodes and slots of the tasks they took, see `run`
+#[cfg(feature = "std")]
+unsafe impl Sync for Block<'_> {}
+
+#[cfg(feature = "std")]
+impl Block<'_> {
+    /// Take tasks until there are none left
+    ///
+    /// Tasks are handed out in order, and each waits until the tasks before its
+    /// level are done. Since those are the only ones that may finish before
+    /// then, `done` reaching `after` means they're all done.
+    fn run(&self) {
+        loop {
+            let task = match self.tasks.get(self.next.fetch_add(1, Ordering::Relaxed)) {
+                Some(task) => task,
+                None => return,
+            };
+            wait_until(|| self.done.load(Ordering::Acquire) >= task.after);
+            // Counts the task even if the node panics, so nobody waits forever
+            let _done = Done(&self.done);
+
+            // Safety: the inputs of the task's node are on lower levels, so they're
+            // done and nothing writes to their slots. Nobody on the same level writes
+            // to a slot that's read on it either. Feedback edges read `previous`,
+            // which is only written between blocks. A split task waits for every
+            // task before it and every task after it waits for it, so it has the
+            // graph to itself.
+            unsafe {
+                if task.split {
+                    (*self.graph).process_step(task.step);
+                    continue;
+                }
+
+                let plan = &(*self.graph).plan;
+                let step = &plan.steps[task.step];
+                let node = &mut (*step.node).node;
+                let buffers = &mut *self.slots.add(step.slot);
+                if node.sleeps(&self.timeline, self.ctx.buffer_size) {
+                    buffers.iter_mut().for_each(Buffer::silence);
+                    continue;
+                }
+                node.process_erased(&self.ctx, &self.timeline, 0, &plan.inputs[step.inputs.0..step.inputs.1], buffers);
+            }
+        }
     }
 }
 
-type InnerGraph = petgraph::graph::Graph<NodeData<DaspAdapter>, ()>;
+/// Counts a task as done when dropped
+#[cfg(feature = "std")]
+struct Done<'a>(&'a AtomicUsize);
+
+#[cfg(feature = "std")]
+impl Drop for Done<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::Release);
+    }
+}
 
 /// An audio processing graph at a fixed sample rate
 pub(crate) struct AudioGraph {
     graph: InnerGraph,
-    processor: Processor<InnerGraph>,
     ctx: ProcessContext,
-    
+    /// Timing of the block being processed
+    timeline: Timeline,
+    /// Transport positions over the block being processed
+    segments: Segments,
+
+    /// Output buffers of the nodes, see [`Plan`]
+    slots: Vec<Vec<Buffer>>,
+    plan: Plan,
+    /// Whether the graph changed since the plan was compiled
+    dirty: bool,
+
+    // Traversal state for compiling, kept to avoid reallocating
+    dfs: DfsPostOrder<NodeIndex, VisitMap>,
+    // Same for checking new edges for cycles
+    cycle_check: DfsSpace<NodeIndex, VisitMap>,
+
     node_indices: HashMap<NodeId, NodeIndex>,
-    next_node_id: u32,
-    
+
+    /// The output, whose input gets rendered
     terminal: Option<NodeIndex>,
+    /// Further sinks processed every block, e.g. bridges into other graphs
+    terminals: Vec<NodeIndex>,
+
+    /// Steps of the block being spread over a worker pool, in the order they're handed out
+    #[cfg(feature = "std")]
+    tasks: Vec<Task>,
 }
 
 impl AudioGraph {
-    /// Create a new graph with the given sample rate
-    pub fn new(sample_rate: u32) -> Self {
+    /// Create a new graph with the given sample rate and block size
+    pub fn new(sample_rate: u32, block_size: usize) -> Self {
+        let graph = InnerGraph::with_capacity(64, 64);
+        let mut dfs = DfsPostOrder::empty(&graph);
+        dfs.stack = Vec::with_capacity(64);
+        let cycle_check = DfsSpace::new(&graph);
+
         Self {
-            graph: InnerGraph::with_capacity(64, 64),
-            processor: Processor::with_capacity(64),
+            graph,
             ctx: ProcessContext {
                 sample_rate,
-                buffer_size: 64, // dasp_graph default
+                buffer_size: block_size,
+                frame: 0,
+                transport: Default::default(),
             },
-            node_indices: HashMap::new(),
-            next_node_id: 0,
+            timeline: Timeline { frame: 0, step: 1.0 },
+            segments: Segments::new(),
+            slots: Vec::with_capacity(64),
+            plan: Plan::with_capacity(64),
+            dirty: true,
+            dfs,
+            cycle_check,
+            node_indices: HashMap::with_capacity(64),
             terminal: None,
+            terminals: Vec::with_capacity(8),
+            #[cfg(feature = "std")]
+            tasks: Vec::with_capacity(64),
         }
     }
-    
+
     #[allow(dead_code)]
     pub fn sample_rate(&self) -> u32 {
         self.ctx.sample_rate
     }
 
-    /// Add a node, returns a handle for sending messages
-    pub fn add<N: AudioNode>(&mut self, node: N) -> NodeHandle<N::Message> {
-        self.add_with_queue_size(node, 64)
-    }
-    
-    /// Add a node with a custom message queue size
-    pub fn add_with_queue_size<N: AudioNode>(&mut self, node: N, queue_size: usize) -> NodeHandle<N::Message> {
-        let id = NodeId(self.next_node_id);
-        self.next_node_id += 1;
-        
-        let (producer, consumer) = RingBuffer::new(queue_size);
-        
-        let num_outputs = node.num_outputs();
-        let wrapper = NodeWrapper { node, receiver: consumer };
-        let adapter = DaspAdapter {
-            node: Box::new(wrapper),
-            ctx: self.ctx,
+    #[allow(dead_code)]
+    pub fn block_size(&self) -> usize {
+        self.ctx.buffer_size
+    }
+
+    /// Change the block size, resizing every node's buffers
+    ///
+    /// Allocates, so this is only meant for setting up the engine.
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.ctx.buffer_size = block_size;
+        for slot in self.slots.iter_mut() {
+            slot.iter_mut().for_each(|b| b.resize(block_size));
+        }
+        for node in self.graph.node_weights_mut() {
+            node.previous.iter_mut().for_each(|b| b.resize(block_size));
+        }
+        self.dirty = true;
+    }
+
+    /// Insert a node built with [`prepare_node`]
+    pub fn insert(&mut self, mut node: GraphNode) {
+        self.slots.push(core::mem::take(&mut node.buffers));
+        let id = node.id;
+        let idx = self.graph.add_node(node);
+        self.node_indices.insert(id, idx);
+        self.dirty = true;
+    }
+
+    /// Connect output of `from` to input of `to`
+    ///
+    /// Fails if either node isn't in this graph, or if the edge isn't a feedback
+    /// edge and would close a loop.
+    pub fn connect(&mut self, from: NodeId, to: NodeId, edge: Edge) -> Result<(), KlingtError> {
+        let from_idx = *self.node_indices.get(&from).ok_or(KlingtError::UnknownNode(from))?;
+        let to_idx = *self.node_indices.get(&to).ok_or(KlingtError::UnknownNode(to))?;
+
+        if !edge.feedback {
+            let forward = EdgeFiltered::from_fn(&self.graph, is_forward);
+            if from_idx == to_idx || has_path_connecting(&forward, to_idx, from_idx, Some(&mut self.cycle_check)) {
+                return Err(KlingtError::Cycle { from, to });
+            }
+        }
+
+        self.graph.add_edge(from_idx, to_idx, edge);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Remove every edge from `from` to `to`
+    ///
+    /// Returns false if there was no such edge.
+    pub fn disconnect(&mut self, from: NodeId, to: NodeId) -> bool {
+        let (from_idx, to_idx) = match (self.node_indices.get(&from), self.node_indices.get(&to)) {
+            (Some(&f), Some(&t)) => (f, t),
+            _ => return false,
         };
-        
-        let node_data = match num_outputs {
-            1 => NodeData::new1(adapter),
-            2 => NodeData::new2(adapter),
-            // 0 outputs = sink, but dasp_graph still needs a buffer for inputs
-            _ => NodeData::new1(adapter),
+
+        let mut removed = false;
+        while let Some(edge) = self.graph.find_edge(from_idx, to_idx) {
+            self.graph.remove_edge(edge);
+            removed = true;
+        }
+        self.dirty |= removed;
+        removed
+    }
+
+    /// Remove all incoming and outgoing edges of a node
+    pub fn disconnect_all(&mut self, id: NodeId) {
+        let idx = match self.node_indices.get(&id) {
+            Some(&idx) => idx,
+            None => return,
         };
-        
-        let idx = self.graph.add_node(node_data);
-        self.node_indices.insert(id, idx);
-        
-        NodeHandle {
-            id,
-            sender: producer,
-            _marker: PhantomData,
+
+        // Edge indices get swapped on removal, so always take the first one
+        for dir in [Direction::Incoming, Direction::Outgoing] {
+            while let Some(edge) = self.graph.first_edge(idx, dir) {
+                self.graph.remove_edge(edge);
+            }
         }
+        self.dirty = true;
+    }
+
+    /// Remove a node and all of its edges.
+    ///
+    /// Returns the removed node so the caller decides where it gets dropped,
+    /// or `None` if the node isn't in this graph.
+    pub fn remove(&mut self, id: NodeId) -> Option<GraphNode> {
+        let idx = self.node_indices.remove(&id)?;
+
+        // petgraph fills the hole by moving the last node into it
+        let last = NodeIndex::new(self.graph.node_count() - 1);
+        let mut removed = self.graph.remove_node(idx)?;
+
+        if last != idx {
+            let moved_id = self.graph[idx].id;
+            self.node_indices.insert(moved_id, idx);
+        }
+
+        if self.terminal == Some(idx) {
+            self.terminal = None;
+        } else if self.terminal == Some(last) {
+            self.terminal = Some(idx);
+        }
+
+        self.terminals.retain(|&t| t != idx);
+        for t in self.terminals.iter_mut().filter(|t| **t == last) {
+            *t = idx;
+        }
+
+        // Take a slot of the same size back, so its buffers get dropped with the node
+        if let Some(slot) = self.slots.iter().position(|slot| slot.len() == removed.channels) {
+            removed.buffers = self.slots.swap_remove(slot);
+        }
+        self.dirty = true;
+
+        Some(removed)
     }
 
-    /// Connect output of `from` to input of `to`
-    pub fn connect<M1, M2>(&mut self, from: &NodeHandle<M1>, to: &NodeHandle<M2>) 
-    where
-        M1: Send + 'static,
-        M2: Send + 'static,
-    {
-        let from_idx = self.node_indices[&from.id];
-        let to_idx = self.node_indices[&to.id];
-        self.graph.add_edge(from_idx, to_idx, ());
-    }
-    
     /// Set which node to process to (typically a sink)
-    pub fn set_terminal<M: Send + 'static>(&mut self, handle: &NodeHandle<M>) {
-        self.terminal = Some(self.node_indices[&handle.id]);
+    pub fn set_terminal(&mut self, id: NodeId) {
+        self.terminal = self.node_indices.get(&id).copied();
+        self.dirty = true;
+    }
+
+    /// Also process to `id` every block, without rendering its input
+    ///
+    /// Doesn't allocate as long as there are at most 8 extra terminals.
+    pub fn add_terminal(&mut self, id: NodeId) {
+        if let Some(&idx) = self.node_indices.get(&id) {
+            if !self.terminals.contains(&idx) {
+                self.terminals.push(idx);
+                self.dirty = true;
+            }
+        }
     }
-    
+
     /// Process one block of audio through the graph
-    pub fn process(&mut self) {
+    ///
+    /// Runs the steps of the [`Plan`], compiling it first if the graph changed.
+    /// Only nodes feeding the terminals are processed, each once, after all of its
+    /// inputs. Feedback edges read the previous block, so they don't count as
+    /// inputs for the order; their sources remember their output at the end of
+    /// the block.
+    ///
+    /// Nodes with messages due within the block are processed in several
+    /// segments, split where the messages are due. Every node is split where the
+    /// transport changes tempo or wraps around its loop. Idle nodes without
+    /// messages due are skipped, and only have their outputs silenced.
+    pub fn process(&mut self, timeline: Timeline, transport: &Transport) {
+        self.begin_block(timeline, transport);
+        for i in 0..self.plan.steps.len() {
+            self.process_step(i);
+        }
+        self.end_block();
+    }
+
+    /// Like [`process`](Self::process), but spread over the threads of `pool`
+    ///
+    /// Nodes on the same level of the plan don't depend on each other, so the
+    /// threads take them off a shared counter in any order, and only move on to
+    /// the next level once all of them are done.
+    ///
+    /// A node processed in segments narrows the buffers of its inputs while it
+    /// runs, which other nodes may be reading. So it gets a level of its own,
+    /// after the others on its level. Nodes see the same inputs, messages and
+    /// transport as with `process`, so the output is the same too.
+    #[cfg(feature = "std")]
+    pub fn process_parallel(&mut self, timeline: Timeline, transport: &Transport, pool: &WorkerPool) {
+        self.begin_block(timeline, transport);
+
+        let block_size = self.ctx.buffer_size;
+        let transport_split = self.segments.next_after(0).is_some();
+        self.tasks.clear();
+        let mut start = 0;
+        while start < self.plan.steps.len() {
+            let level = self.plan.steps[start].level;
+            let first = self.tasks.len();
+            for (i, step) in self.plan.steps[start..].iter().enumerate().take_while(|(_, step)| step.level == level) {
+                let split = transport_split || self.graph[step.index].node.segment_end(&self.timeline, 0, block_size) < block_size;
+                self.tasks.push(Task { step: start + i, split, after: first });
+            }
+            start += self.tasks.len() - first;
+
+            self.tasks[first..].sort_unstable_by_key(|task| task.split);
+            for i in first..self.tasks.len() {
+                if self.tasks[i].split {
+                    self.tasks[i].after = i;
+                }
+            }
+        }
+
+        // Taken out, so split tasks can borrow the whole graph without aliasing them
+        let tasks = core::mem::take(&mut self.tasks);
+        let block = Block {
+            slots: self.slots.as_mut_ptr(),
+            ctx: ProcessContext { transport: self.segments.info_at(0), ..self.ctx },
+            timeline: self.timeline,
+            graph: self,
+            tasks: &tasks,
+            next: AtomicUsize::new(0),
+            done: AtomicUsize::new(0),
+        };
+        // Not worth waking the workers if every level holds a single node
+        if tasks.windows(2).any(|pair| pair[0].after == pair[1].after) {
+            pool.run(&|_| block.run());
+        } else {
+            block.run();
+        }
+
+        self.tasks = tasks;
+        self.end_block();
+    }
+
+    /// Set up the timing of the block about to be processed
+    fn begin_block(&mut self, timeline: Timeline, transport: &Transport) {
+        if self.dirty {
+            self.compile();
+        }
+        self.timeline = timeline;
+        self.ctx.frame = timeline.frame;
+        let start = timeline.frame as f64 * timeline.step;
+        transport.segments(start, timeline.step, self.ctx.buffer_size, &mut self.segments);
+    }
+
+    /// Let the sources of feedback edges remember the block for the next one
+    fn end_block(&mut self) {
+        for &(node, slot) in &self.plan.feedback {
+            for (previous, buffer) in self.graph[node].previous.iter_mut().zip(&self.slots[slot]) {
+                previous.copy_from(buffer);
+            }
+        }
+    }
+
+    /// Work out the [`Plan`] for the graph as it is now
+    ///
+    /// Visits the nodes feeding the terminals and the sources of feedback edges
+    /// in depth-first post order over the reversed forward edges, so every node's
+    /// inputs have their level before it does.
+    ///
+    /// Runs on the audio thread, allocating only if the graph outgrew the plan.
+    fn compile(&mut self) {
+        let plan = &mut self.plan;
+        plan.steps.clear();
+        plan.inputs.clear();
+        plan.sources.clear();
+        plan.feedback.clear();
+        plan.terminal_input = None;
+
+        let count = self.graph.node_count();
+        plan.levels.clear();
+        plan.levels.resize(count, 0);
+        plan.slots.clear();
+        plan.slots.resize(count, NO_SLOT);
+        plan.last_use.clear();
+        plan.last_use.resize(count, 0);
+
+        // Order
+        self.dfs.reset(Reversed(&self.graph));
         if let Some(terminal) = self.terminal {
-            self.processor.process(&mut self.graph, terminal);
+            self.visit_to(terminal);
+        }
+        for i in 0..self.terminals.len() {
+            self.visit_to(self.terminals[i]);
+        }
+        for i in 0..self.graph.edge_count() {
+            let edge = EdgeIndex::new(i);
+            if self.graph[edge].feedback {
+                let (source, _) = self.graph.edge_endpoints(edge).unwrap();
+                self.visit_to(source);
+                if !self.plan.feedback.iter().any(|&(node, _)| node == source) {
+                    self.plan.feedback.push((source, NO_SLOT));
+                }
+            }
+        }
+        let (plan, graph, slots) = (&mut self.plan, &self.graph, &self.slots);
+        plan.steps.sort_unstable_by_key(|step| step.level);
+
+        // How long outputs are needed. What's rendered or fed back until the end of the block.
+        for step in &plan.steps {
+            plan.last_use[step.index.index()] = plan.last_use[step.index.index()].max(step.level);
+            for edge in graph.edges_directed(step.index, Direction::Incoming).filter(|&edge| is_forward(edge)) {
+                let last_use = &mut plan.last_use[edge.source().index()];
+                *last_use = (*last_use).max(step.level);
+            }
+        }
+        for &(node, _) in &plan.feedback {
+            plan.last_use[node.index()] = usize::MAX;
+        }
+        // Incoming edges come newest first, so this is what a sink gets as its first input
+        let terminal_edge = self.terminal.and_then(|terminal| {
+            graph.edges_directed(terminal, Direction::Incoming).find(|edge| edge.source() != terminal)
+        });
+        if let Some(edge) = terminal_edge.filter(|&edge| is_forward(edge)) {
+            plan.last_use[edge.source().index()] = usize::MAX;
+        }
+
+        // Slots
+        plan.free.clear();
+        plan.free.extend(0..slots.len());
+        plan.live.clear();
+        for step in plan.steps.iter_mut() {
+            let (free, level) = (&mut plan.free, step.level);
+            plan.live.retain(|&(last_use, slot)| {
+                if last_use < level {
+                    free.push(slot);
+                }
+                last_use >= level
+            });
+
+            let channels = graph[step.index].channels;
+            let free_slot = free.iter().position(|&slot| slots[slot].len() == channels);
+            step.slot = free.swap_remove(free_slot.expect("Every node brings a slot of its size"));
+            plan.slots[step.index.index()] = step.slot;
+            plan.live.push((plan.last_use[step.index.index()], step.slot));
+        }
+        for (node, slot) in plan.feedback.iter_mut() {
+            *slot = plan.slots[node.index()];
+        }
+
+        // Inputs
+        for step in plan.steps.iter_mut() {
+            let start = plan.inputs.len();
+            for edge in graph.edges_directed(step.index, Direction::Incoming) {
+                let source = if edge.weight().feedback {
+                    Source::Previous(edge.source())
+                } else {
+                    Source::Slot(plan.slots[edge.source().index()])
+                };
+                let buffers = match source {
+                    Source::Slot(slot) => &slots[slot],
+                    Source::Previous(node) => &graph[node].previous,
+                };
+                plan.inputs.push(edge_input(buffers, edge.weight().ports));
+                plan.sources.push(source);
+            }
+            step.inputs = (start, plan.inputs.len());
+        }
+        plan.terminal_input = terminal_edge.map(|edge| {
+            let buffers = if edge.weight().feedback {
+                &graph[edge.source()].previous
+            } else {
+                &slots[plan.slots[edge.source().index()]]
+            };
+            edge_input(buffers, edge.weight().ports)
+        });
+
+        for step in plan.steps.iter_mut() {
+            step.node = &mut self.graph[step.index];
+        }
+        self.dirty = false;
+    }
+
+    /// Add a step for everything feeding `terminal` that doesn't have one yet
+    fn visit_to(&mut self, terminal: NodeIndex) {
+        // Keeps the visited set, so shared upstream nodes are skipped
+        self.dfs.move_to(terminal);
+
+        loop {
+            let forward = EdgeFiltered::from_fn(Reversed(&self.graph), is_forward);
+            let n = match self.dfs.next(&forward) {
+                Some(n) => n,
+                None => break,
+            };
+
+            let levels = &mut self.plan.levels;
+            let level = self
+                .graph
+                .edges_directed(n, Direction::Incoming)
+                .filter(|&edge| is_forward(edge))
+                .map(|edge| levels[edge.source().index()] + 1)
+                .max()
+                .unwrap_or(0);
+            levels[n.index()] = level;
+            self.plan.steps.push(Step {
+                index: n,
+                node: core::ptr::null_mut(),
+                slot: NO_SLOT,
+                inputs: (0, 0),
+                level,
+            });
         }
     }
+
+    /// Process one step of the plan, in segments if messages are due within the block
+    fn process_step(&mut self, i: usize) {
+        let block_size = self.ctx.buffer_size;
+        let step = &self.plan.steps[i];
+        let (n, slot, inputs) = (step.index, step.slot, step.inputs.0..step.inputs.1);
+        let mut start = 0;
+        let mut split = false;
+
+        let end = self.graph[n].node.segment_end(&self.timeline, 0, block_size);
+        if self.graph[n].node.sleeps(&self.timeline, block_size) {
+            self.slots[slot].iter_mut().for_each(Buffer::silence);
+            return;
+        }
+
+        while start < block_size {
+            let end = if start == 0 { end } else { self.graph[n].node.segment_end(&self.timeline, start, block_size) };
+            let end = self.segments.next_after(start).map_or(end, |next| next.min(end));
+            if start > 0 || end < block_size {
+                self.set_windows(i, start, end);
+                split = true;
+            }
+            let ctx = ProcessContext {
+                buffer_size: end - start,
+                frame: self.ctx.frame + start as u64,
+                transport: self.segments.info_at(start),
+                ..self.ctx
+            };
+
+            // The inputs point into other slots, or into `previous` buffers
+            let inputs = &self.plan.inputs[inputs.clone()];
+            self.graph[n].node.process_erased(&ctx, &self.timeline, start, inputs, &mut self.slots[slot]);
+            start = end;
+        }
+
+        // Let everyone else see whole blocks again
+        if split {
+            self.set_windows(i, 0, block_size);
+        }
+    }
+
+    /// Narrow the buffers step `i` writes and reads to `start..end`
+    fn set_windows(&mut self, i: usize, start: usize, end: usize) {
+        let step = &self.plan.steps[i];
+        self.slots[step.slot].iter_mut().for_each(|b| b.set_window(start, end));
+
+        for source in &self.plan.sources[step.inputs.0..step.inputs.1] {
+            let buffers = match *source {
+                Source::Slot(slot) => &mut self.slots[slot],
+                Source::Previous(node) => &mut self.graph[node].previous,
+            };
+            buffers.iter_mut().for_each(|b| b.set_window(start, end));
+        }
+    }
+
+    /// Interleave what the terminal received during the last block into `out`
+    ///
+    /// Mirrors what sinks see: the first input, which is the one connected last,
+    /// with missing channels silent.
+    /// Writes silence if nothing reaches the terminal.
+    pub fn read_terminal_input(&self, channels: usize, out: &mut [f32]) {
+        // Points into slots that may have been handed back since the graph changed
+        let input = if self.dirty { None } else { self.plan.terminal_input.as_ref() };
+
+        let buffers = match input {
+            Some(input) if !input.buffers().is_empty() => input.buffers(),
+            _ => {
+                out.iter_mut().for_each(|s| *s = 0.0);
+                return;
+            }
+        };
+
+        simd::interleave(buffers, channels, 0, out);
+    }
 }
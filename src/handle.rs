@@ -0,0 +1,171 @@
+//! Handles to nodes living in the audio graph
+
+use core::marker::PhantomData;
+
+use crate::command::GraphId;
+use crate::graph::Timed;
+use crate::node::{AudioNode, NodeId};
+use crate::snapshot::Reader;
+
+/// The [`Handle`] for a node of type `N`, as returned by [`Klingt::add`](crate::Klingt::add).
+pub type NodeHandle<N> = Handle<<N as AudioNode>::Message, <N as AudioNode>::Event, <N as AudioNode>::State>;
+
+/// A handle for sending messages to a node in the audio graph.
+///
+/// Handles are returned when you add a node to [`Klingt`](crate::Klingt) (or a
+/// [`KlingtController`](crate::KlingtController)) and provide four capabilities:
+/// 1. **Connections** - Pass handles to [`Klingt::connect`](crate::Klingt::connect)
+///    or [`Klingt::output`](crate::Klingt::output)
+/// 2. **Messages** - Send parameter updates via [`Handle::send`]
+/// 3. **Events** - Receive what the node reports back via [`Handle::poll_events`]
+/// 4. **State** - Read the node's current state via [`Handle::state`]
+///
+/// `M` is the node's [`Message`](crate::AudioNode::Message) type, `E` its
+/// [`Event`](crate::AudioNode::Event) type and `S` its [`State`](crate::AudioNode::State) type.
+///
+/// # Example
+///
+/// ```no_run
+/// # use klingt::{Klingt, nodes::{Sine, SineMessage}};
+/// # let mut klingt = Klingt::default_output().unwrap();
+/// let mut sine = klingt.add(Sine::new(440.0));
+///
+/// // Change frequency (processed next audio block)
+/// sine.send(SineMessage::SetFrequency(880.0)).ok();
+/// ```
+///
+/// # Message Delivery
+///
+/// Messages are buffered in a lock-free ring buffer and processed at the start
+/// of each audio block, or at an exact frame with [`Handle::send_at`]. If the
+/// buffer is full, [`Handle::send`] returns `Err(msg)` with the message that
+/// couldn't be sent.
+pub struct Handle<M: Send + 'static, E: Send + 'static = (), S: Send + 'static = ()> {
+    pub(crate) node_id: NodeId,
+    pub(crate) graph_id: GraphId,
+    pub(crate) sender: rtrb::Producer<Timed<M>>,
+    pub(crate) events: rtrb::Consumer<E>,
+    /// `None` for nodes that don't publish their state
+    pub(crate) state: Option<Reader<S>>,
+    pub(crate) _marker: PhantomData<M>,
+}
+
+impl<M: Send + 'static, E: Send + 'static, S: Send + 'static> Handle<M, E, S> {
+    /// Send a message to the node.
+    ///
+    /// The message will be processed at the start of the next audio block.
+    /// This is lock-free and safe to call from any thread.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the message was queued successfully
+    /// - `Err(msg)` if the queue is full (message dropped)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use klingt::{Klingt, nodes::{Sine, SineMessage}};
+    /// # let mut klingt = Klingt::default_output().unwrap();
+    /// let mut sine = klingt.add(Sine::new(440.0));
+    ///
+    /// // Fire-and-forget style (ignore if queue full)
+    /// sine.send(SineMessage::SetFrequency(880.0)).ok();
+    ///
+    /// // Or handle the error
+    /// if sine.send(SineMessage::SetAmplitude(0.5)).is_err() {
+    ///     eprintln!("Message queue full!");
+    /// }
+    /// ```
+    pub fn send(&mut self, msg: M) -> Result<(), M> {
+        self.push(Timed::now(msg))
+    }
+
+    /// Send a message that takes effect at an exact output frame.
+    ///
+    /// `frame` counts output frames since the engine started, the same frames
+    /// [`Klingt::render`](crate::Klingt::render) returns. The block containing
+    /// `frame` is split there: the node processes the samples before it, then
+    /// receives the message and processes the rest, so the change lands on the
+    /// exact sample. Frames that have already passed take effect at the start
+    /// of the next block, like [`send`](Self::send).
+    ///
+    /// Nodes running at a different sample rate than the output get the message
+    /// at the first of their own samples at or after `frame`, and their audio is
+    /// lined up with the output, so it lands on time there too. Their graphs run
+    /// ahead of the output though, by up to five of their own blocks: messages
+    /// for them sent later than that before `frame` take effect late. Audio that
+    /// goes from the output's graph into one at another rate and back arrives a
+    /// block or two late, to give the resampling on the way time to catch up.
+    ///
+    /// Messages are delivered in order of their frames; messages due at the same
+    /// frame in the order they were sent. Up to the queue size (64) of them can
+    /// wait for their frame at once.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::{Sine, SineMessage}};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let mut sine = klingt.add(Sine::new(440.0));
+    /// klingt.output(&sine);
+    ///
+    /// // Exactly half a second in, whatever the block size
+    /// sine.send_at(24000, SineMessage::SetFrequency(880.0)).ok();
+    /// let samples = klingt.render(48000);
+    /// ```
+    pub fn send_at(&mut self, frame: u64, msg: M) -> Result<(), M> {
+        self.push(Timed { frame, message: msg })
+    }
+
+    /// Take the events the node has reported since the last call.
+    ///
+    /// Events are queued lock-free as the node reports them (see
+    /// [`AudioNode::next_event`](crate::AudioNode::next_event)), oldest first.
+    /// Up to the queue size (64) of them wait to be picked up; while the queue
+    /// is full, the node holds on to further events.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::{SamplePlayer, PlayerEvent}};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let mut player = klingt.add(SamplePlayer::new(vec![0.5; 4800], 1, 48000));
+    /// klingt.output(&player);
+    ///
+    /// klingt.render(9600);
+    /// assert!(player.poll_events().any(|event| matches!(event, PlayerEvent::Finished)));
+    /// ```
+    pub fn poll_events(&mut self) -> impl Iterator<Item = E> + '_ {
+        let events = &mut self.events;
+        core::iter::from_fn(move || events.pop().ok())
+    }
+
+    /// Read the node's latest state, e.g. a player's position for a progress bar.
+    ///
+    /// Nodes publish their state after every block they process (see
+    /// [`AudioNode::state`](crate::AudioNode::state)); this returns the most recent
+    /// one without locking or waiting on the audio thread. Before the first block
+    /// it's the state the node was added with.
+    ///
+    /// Returns `None` for nodes that don't publish any state.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::SamplePlayer};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let mut player = klingt.add(SamplePlayer::new(vec![0.5; 48000], 1, 48000));
+    /// klingt.output(&player);
+    ///
+    /// klingt.render(24000);
+    /// let state = player.state().unwrap();
+    /// assert!(state.playing && state.position_secs >= 0.5);
+    /// ```
+    pub fn state(&mut self) -> Option<&S> {
+        self.state.as_mut().map(|reader| reader.read())
+    }
+
+    fn push(&mut self, timed: Timed<M>) -> Result<(), M> {
+        self.sender.push(timed).map_err(|rtrb::PushError::Full(m)| m.message)
+    }
+}
@@ -1,92 +1,26 @@
 //! High-level audio engine API
 
-use core::marker::PhantomData;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
 
-use hashbrown::HashMap;
+use delegate::delegate;
 use rtrb::RingBuffer;
 
-use crate::graph::AudioGraph;
-use crate::node::{AudioNode, NodeId};
-use crate::nodes::{ResamplingSource, RtrbSink};
+use crate::command::COMMAND_QUEUE_SIZE;
+use crate::controller::KlingtController;
+use crate::description::{GraphDescription, NamedHandles, NodeRegistry};
+use crate::error::KlingtError;
+use crate::graph::{MAX_BLOCK_SIZE, MIN_BLOCK_SIZE};
+use crate::handle::{Handle, NodeHandle};
+use crate::node::AudioNode;
+use crate::nodes::NullSink;
+use crate::processor::KlingtProcessor;
+use crate::transport::{TempoMap, TimeSignature};
 
 #[cfg(feature = "cpal_sink")]
 use crate::device::CpalDevice;
 
-/// A handle for sending messages to a node in the audio graph.
-///
-/// Handles are returned when you add a node to [`Klingt`] and provide two capabilities:
-/// 1. **Connections** - Pass handles to [`Klingt::connect`] or [`Klingt::output`]
-/// 2. **Messages** - Send parameter updates via [`Handle::send`]
-///
-/// # Example
-///
-/// ```no_run
-/// # use klingt::{Klingt, nodes::{Sine, SineMessage}};
-/// # let mut klingt = Klingt::default_output().unwrap();
-/// let mut sine = klingt.add(Sine::new(440.0));
-///
-/// // Change frequency (processed next audio block)
-/// sine.send(SineMessage::SetFrequency(880.0)).ok();
-/// ```
-///
-/// # Message Delivery
-///
-/// Messages are buffered in a lock-free ring buffer and processed at the start
-/// of each audio block. If the buffer is full, [`Handle::send`] returns `Err(msg)`
-/// with the message that couldn't be sent.
-pub struct Handle<M: Send + 'static> {
-    pub(crate) node_id: NodeId,
-    #[allow(dead_code)]
-    pub(crate) graph_id: usize,
-    pub(crate) sender: rtrb::Producer<M>,
-    pub(crate) _marker: PhantomData<M>,
-}
-
-impl<M: Send + 'static> Handle<M> {
-    /// Send a message to the node.
-    ///
-    /// The message will be processed at the start of the next audio block.
-    /// This is lock-free and safe to call from any thread.
-    ///
-    /// # Returns
-    ///
-    /// - `Ok(())` if the message was queued successfully
-    /// - `Err(msg)` if the queue is full (message dropped)
-    ///
-    /// # Example
-    ///
-    /// ```no_run
-    /// # use klingt::{Klingt, nodes::{Sine, SineMessage}};
-    /// # let mut klingt = Klingt::default_output().unwrap();
-    /// let mut sine = klingt.add(Sine::new(440.0));
-    ///
-    /// // Fire-and-forget style (ignore if queue full)
-    /// sine.send(SineMessage::SetFrequency(880.0)).ok();
-    ///
-    /// // Or handle the error
-    /// if sine.send(SineMessage::SetAmplitude(0.5)).is_err() {
-    ///     eprintln!("Message queue full!");
-    /// }
-    /// ```
-    pub fn send(&mut self, msg: M) -> Result<(), M> {
-        self.sender.push(msg).map_err(|rtrb::PushError::Full(m)| m)
-    }
-}
-
-/// Internal tracking for sub-graphs that need resampling
-struct SubGraph {
-    graph: AudioGraph,
-    /// Sample rate of this sub-graph
-    #[allow(dead_code)]
-    sample_rate: u32,
-    /// Node ID of the RtrbSink in this sub-graph (terminal that feeds main graph)
-    sink_node: NodeId,
-    /// Node ID of the ResamplingSource in the main graph
-    resampler_node: NodeId,
-    /// How many blocks we've processed
-    blocks_processed: u64,
-}
-
 /// The main audio engine - manages nodes, connections, and audio processing.
 ///
 /// `Klingt` provides a high-level API for building audio graphs. It handles:
@@ -134,10 +68,26 @@ struct SubGraph {
 /// klingt.output(&gain);
 /// ```
 ///
+/// # Running on the Audio Thread
+///
+/// `Klingt` keeps graph building and processing in one object. To let the audio
+/// thread own the graph, [`split`](Self::split) it into a [`KlingtController`] for
+/// editing and a [`KlingtProcessor`] that can be moved into an audio callback:
+///
+/// ```no_run
+/// # use klingt::{Klingt, CpalDevice, nodes::Sine};
+/// let device = CpalDevice::default_output().unwrap();
+/// let (mut controller, processor) = Klingt::for_device(&device).split();
+/// let _stream = device.play(processor).unwrap();
+///
+/// let sine = controller.add(Sine::new(440.0));
+/// controller.output(&sine);
+/// ```
+///
 /// # Processing Audio
 ///
-/// Call [`process`](Self::process) repeatedly to generate audio. This is typically done
-/// in a loop, paced to match real-time:
+/// Without splitting, call [`process`](Self::process) repeatedly to generate audio.
+/// This is typically done in a loop, paced to match real-time:
 ///
 /// ```no_run
 /// # use klingt::Klingt;
@@ -146,11 +96,12 @@ struct SubGraph {
 ///
 /// let start = Instant::now();
 /// let rate = klingt.sample_rate() as f64;
+/// let block = klingt.block_size() as f64;
 /// let mut blocks = 0u64;
 ///
 /// loop {
 ///     // Calculate how many blocks should have been processed by now
-///     let target = (start.elapsed().as_secs_f64() * rate / 64.0) as u64 + 4;
+///     let target = (start.elapsed().as_secs_f64() * rate / block) as u64 + 4;
 ///     
 ///     while blocks < target {
 ///         klingt.process();
@@ -161,22 +112,8 @@ struct SubGraph {
 /// }
 /// ```
 pub struct Klingt {
-    /// Main output graph at device/output sample rate
-    main_graph: AudioGraph,
-    /// Output sample rate
-    sample_rate: u32,
-    /// Number of output channels
-    channels: usize,
-    
-    /// Sub-graphs for nodes at different sample rates
-    /// Key: the sample rate of the sub-graph
-    sub_graphs: HashMap<u32, SubGraph>,
-    
-    /// The output sink node in main graph (e.g., CpalSink)
-    sink_node: Option<NodeId>,
-    
-    /// Blocks processed on main graph (for scheduling)
-    main_blocks_processed: u64,
+    controller: KlingtController,
+    processor: KlingtProcessor,
 }
 
 impl Klingt {
@@ -194,16 +131,37 @@ impl Klingt {
     ///     .with_output(device.create_sink());
     /// ```
     pub fn new(sample_rate: u32) -> Self {
+        let (command_tx, command_rx) = RingBuffer::new(COMMAND_QUEUE_SIZE);
+        let (garbage_tx, garbage_rx) = RingBuffer::new(COMMAND_QUEUE_SIZE);
+
         Self {
-            main_graph: AudioGraph::new(sample_rate),
-            sample_rate,
-            channels: 2,
-            sub_graphs: HashMap::new(),
-            sink_node: None,
-            main_blocks_processed: 0,
+            controller: KlingtController::new(sample_rate, 2, command_tx, garbage_rx),
+            processor: KlingtProcessor::new(sample_rate, 2, command_rx, garbage_tx),
         }
     }
 
+    /// Create a Klingt instance that isn't attached to any audio device.
+    ///
+    /// The output is a [`NullSink`], so [`output`](Self::output) works as usual and
+    /// the result can be collected with [`render`](Self::render). Use this to bounce
+    /// audio to memory, generate assets, or test graphs on machines without a sound card.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::Sine};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let sine = klingt.add(Sine::new(440.0));
+    /// klingt.output(&sine);
+    ///
+    /// // One second of interleaved stereo audio
+    /// let samples = klingt.render(48000);
+    /// assert_eq!(samples.len(), 48000 * 2);
+    /// ```
+    pub fn offline(sample_rate: u32) -> Self {
+        Self::new(sample_rate).with_output(NullSink::new(2))
+    }
+
     /// Create Klingt with the system's default audio output device.
     ///
     /// This is the easiest way to get started. Returns `None` if no audio device is available.
@@ -217,32 +175,115 @@ impl Klingt {
     #[cfg(feature = "cpal_sink")]
     pub fn default_output() -> Option<Self> {
         let device = CpalDevice::default_output()?;
-        let sample_rate = device.sample_rate();
-        let channels = device.channels() as usize;
-        
-        let mut klingt = Self {
-            main_graph: AudioGraph::new(sample_rate),
-            sample_rate,
-            channels,
-            sub_graphs: HashMap::new(),
-            sink_node: None,
-            main_blocks_processed: 0,
-        };
-        
-        // Add the CPAL sink as the output
         let sink = device.create_sink();
-        let handle = klingt.main_graph.add(sink);
-        klingt.sink_node = Some(handle.id());
-        klingt.main_graph.set_terminal(&handle);
-        
-        Some(klingt)
+
+        Some(Self::new(device.sample_rate())
+            .with_channels(device.channels() as usize)
+            .with_output(sink))
+    }
+
+    /// Create Klingt for a device that will drive it from its own audio callback.
+    ///
+    /// Matches the device's sample rate and channel count, and uses a [`NullSink`] as
+    /// output: the audio is pulled by [`CpalDevice::play`] rather than pushed to a
+    /// [`CpalSink`](crate::nodes::CpalSink). [`split`](Self::split) the result and
+    /// hand the processor to the device.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use klingt::{Klingt, CpalDevice};
+    /// let device = CpalDevice::default_output().unwrap();
+    /// let (mut controller, processor) = Klingt::for_device(&device).split();
+    /// let stream = device.play(processor).unwrap();
+    /// ```
+    #[cfg(feature = "cpal_sink")]
+    pub fn for_device(device: &CpalDevice) -> Self {
+        let channels = device.channels() as usize;
+        Self::new(device.sample_rate())
+            .with_channels(channels)
+            .with_output(NullSink::new(channels))
     }
 
     /// Set the number of output channels (builder pattern).
     ///
     /// Default is 2 (stereo). This affects sub-graph creation for sample rate conversion.
     pub fn with_channels(mut self, channels: usize) -> Self {
-        self.channels = channels;
+        self.controller.set_channels(channels);
+        self.processor.set_channels(channels);
+        self
+    }
+
+    /// Set the number of samples processed per block (builder pattern).
+    ///
+    /// Default is 64. Smaller blocks react to messages and graph edits sooner and
+    /// lower the latency of a device callback; larger blocks spend less time on
+    /// per-block overhead, which suits offline rendering. Every node in the engine,
+    /// including sub-graphs, runs at this size and sees it as
+    /// [`ProcessContext::buffer_size`](crate::ProcessContext::buffer_size).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::Klingt;
+    /// let klingt = Klingt::offline(48000).with_block_size(256);
+    /// assert_eq!(klingt.block_size(), 256);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is outside `16..=2048`.
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        assert!(
+            (MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size),
+            "Block size must be between {} and {} samples, got {}",
+            MIN_BLOCK_SIZE,
+            MAX_BLOCK_SIZE,
+            block_size
+        );
+
+        self.controller.set_block_size(block_size);
+        self.processor.set_block_size(block_size);
+        self
+    }
+
+    /// Spread processing over `threads` threads (builder pattern).
+    ///
+    /// Default is 1: every node is processed on the thread calling
+    /// [`process`](Self::process). With more, worker threads are started here,
+    /// and nodes that don't depend on each other - like voices feeding a
+    /// [`Mixer`](crate::nodes::Mixer) - are processed side by side. The thread
+    /// calling `process` counts as one of them. The output is the same as with
+    /// a single thread.
+    ///
+    /// Parallel processing pays off for graphs with many independent, busy
+    /// branches. Small graphs are usually faster on one thread.
+    ///
+    /// Only available with the `std` feature, which isn't enabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use klingt::{Klingt, nodes::{Mixer, Sine}};
+    ///
+    /// let mut klingt = Klingt::offline(48000).with_threads(4);
+    /// let mixer = klingt.add(Mixer::stereo());
+    /// for i in 0..16 {
+    ///     let voice = klingt.add(Sine::new(110.0 * (i + 1) as f32));
+    ///     klingt.connect(&voice, &mixer);
+    /// }
+    /// klingt.output(&mixer);
+    ///
+    /// let samples = klingt.render(48000);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is 0.
+    #[cfg(feature = "std")]
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "Need at least one thread to process on");
+        self.processor.set_threads(threads);
         self
     }
 
@@ -263,15 +304,53 @@ impl Klingt {
     ///     .with_output(device.create_sink());
     /// ```
     pub fn with_output<S: AudioNode<Message = ()>>(mut self, sink: S) -> Self {
-        let handle = self.main_graph.add(sink);
-        self.sink_node = Some(handle.id());
-        self.main_graph.set_terminal(&handle);
+        self.controller.set_output(sink);
+        self.sync();
         self
     }
 
-    /// Get the output sample rate in Hz.
-    pub fn sample_rate(&self) -> u32 {
-        self.sample_rate
+    /// Split into a controller for editing the graph and a processor for running it.
+    ///
+    /// The [`KlingtProcessor`] can be moved to the audio thread (or into an audio
+    /// callback), while the [`KlingtController`] stays wherever the graph is edited.
+    /// Structural changes made through the controller are applied by the processor
+    /// at its next block boundary, without locks.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use klingt::{Klingt, nodes::Sine};
+    ///
+    /// let (mut controller, mut processor) = Klingt::offline(48000).split();
+    ///
+    /// let sine = controller.add(Sine::new(440.0));
+    /// controller.output(&sine);
+    ///
+    /// // Changes show up once the processor runs
+    /// let samples = processor.render(480);
+    /// assert!(samples.iter().any(|&s| s != 0.0));
+    /// ```
+    pub fn split(self) -> (KlingtController, KlingtProcessor) {
+        (self.controller, self.processor)
+    }
+
+    delegate! {
+        to self.controller {
+            /// Get the output sample rate in Hz.
+            pub fn sample_rate(&self) -> u32;
+
+            /// Get the number of output channels.
+            pub fn channels(&self) -> usize;
+
+            /// Get the number of samples processed per block.
+            pub fn block_size(&self) -> usize;
+        }
+    }
+
+    /// Get the number of threads processing the graph, see [`with_threads`](Self::with_threads).
+    #[cfg(feature = "std")]
+    pub fn threads(&self) -> usize {
+        self.processor.threads()
     }
 
     /// Add a node to the audio graph.
@@ -296,76 +375,118 @@ impl Klingt {
     /// let sine = klingt.add(Sine::new(440.0));
     /// klingt.output(&sine);
     /// ```
-    pub fn add<N: AudioNode>(&mut self, node: N) -> Handle<N::Message> {
-        let node_rate = node.native_sample_rate();
-        
-        if let Some(rate) = node_rate {
-            if rate != self.sample_rate {
-                // Node needs its own sub-graph with resampling
-                return self.add_to_subgraph(node, rate);
-            }
-        }
-        
-        // Node matches output rate (or has no preference) - add to main graph
-        let handle = self.main_graph.add(node);
-        let node_id = handle.id();
-        
-        Handle {
-            node_id,
-            graph_id: 0,
-            sender: handle.sender,
-            _marker: PhantomData,
-        }
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_add`](Self::try_add) would return an error.
+    pub fn add<N: AudioNode>(&mut self, node: N) -> NodeHandle<N> {
+        self.try_add(node).unwrap_or_else(|e| panic!("{}", e))
     }
 
-    /// Add a node to a sub-graph at a specific sample rate
-    fn add_to_subgraph<N: AudioNode>(&mut self, node: N, rate: u32) -> Handle<N::Message> {
-        let channels = node.num_outputs().max(self.channels);
-        
-        // Get or create sub-graph for this sample rate
-        if !self.sub_graphs.contains_key(&rate) {
-            self.create_subgraph(rate, channels);
-        }
-        
-        let sub = self.sub_graphs.get_mut(&rate).unwrap();
-        let handle = sub.graph.add(node);
-        let node_id = handle.id();
-        
-        Handle {
-            node_id,
-            graph_id: rate as usize,
-            sender: handle.sender,
-            _marker: PhantomData,
-        }
+    /// Add a node to the audio graph, reporting failures instead of panicking.
+    ///
+    /// Like [`add`](Self::add), but returns [`KlingtError::CommandQueueFull`] if the
+    /// processor isn't keeping up.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::Sine};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let sine = klingt.try_add(Sine::new(440.0))?;
+    /// klingt.try_output(&sine)?;
+    /// # Ok::<(), klingt::KlingtError>(())
+    /// ```
+    pub fn try_add<N: AudioNode>(&mut self, node: N) -> Result<NodeHandle<N>, KlingtError> {
+        let handle = self.controller.try_add(node);
+        self.sync();
+        handle
+    }
+
+    /// Add a node to the graph running at `sample_rate`, regardless of its
+    /// [`native_sample_rate`](AudioNode::native_sample_rate).
+    ///
+    /// Use this to run effects at a different rate than the output, e.g. a chain
+    /// tuned for 48 kHz inside a 44.1 kHz engine. Audio is resampled on its way
+    /// into and out of the sub-graph, wherever it's connected to nodes at other rates.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::{Sine, Gain}};
+    /// let mut klingt = Klingt::offline(44100);
+    /// let sine = klingt.add(Sine::new(440.0));
+    /// let gain = klingt.add_at_rate(Gain::new(0.5), 48000);
+    ///
+    /// // 44.1 kHz -> 48 kHz -> 44.1 kHz
+    /// klingt.connect(&sine, &gain);
+    /// klingt.output(&gain);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_add_at_rate`](Self::try_add_at_rate) would return an error.
+    pub fn add_at_rate<N: AudioNode>(&mut self, node: N, sample_rate: u32) -> NodeHandle<N> {
+        self.try_add_at_rate(node, sample_rate).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Add a node to the graph running at `sample_rate`, reporting failures instead
+    /// of panicking.
+    ///
+    /// See [`add_at_rate`](Self::add_at_rate) and [`try_add`](Self::try_add).
+    pub fn try_add_at_rate<N: AudioNode>(
+        &mut self,
+        node: N,
+        sample_rate: u32,
+    ) -> Result<NodeHandle<N>, KlingtError> {
+        let handle = self.controller.try_add_at_rate(node, sample_rate);
+        self.sync();
+        handle
     }
 
-    /// Create a new sub-graph with resampling bridge to main graph
-    fn create_subgraph(&mut self, rate: u32, channels: usize) {
-        // Ring buffer between sub-graph and main graph
-        let buffer_size = ((rate as f32 * 0.1) as usize * channels).next_power_of_two().max(8192);
-        let (producer, consumer) = RingBuffer::<f32>::new(buffer_size);
-        
-        // Create sub-graph
-        let mut sub_graph = AudioGraph::new(rate);
-        
-        // Add RtrbSink to sub-graph (this is the terminal that feeds main graph)
-        let sink = RtrbSink::new(producer, channels);
-        let sink_handle = sub_graph.add(sink);
-        let sink_node = sink_handle.id();
-        sub_graph.set_terminal(&sink_handle);
-        
-        // Add resampling source to main graph
-        let resampler = ResamplingSource::new(consumer, channels, rate);
-        let resampler_handle = self.main_graph.add(resampler);
-        let resampler_node = resampler_handle.id();
-        
-        self.sub_graphs.insert(rate, SubGraph {
-            graph: sub_graph,
-            sample_rate: rate,
-            sink_node,
-            resampler_node,
-            blocks_processed: 0,
-        });
+    /// Add a sink that's processed every block, alongside the output.
+    ///
+    /// The output only pulls audio from what's connected to it. Further sinks,
+    /// like a recorder or a monitor output, get their own terminal in the graph:
+    /// connect nodes to them as usual and they're processed every block too.
+    /// Nodes feeding several sinks are still processed only once per block.
+    /// Remove the sink with [`remove`](Self::remove).
+    ///
+    /// Like [`add`](Self::add), sinks with a
+    /// [`native_sample_rate`](AudioNode::native_sample_rate) run in a sub-graph.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::{RtrbSink, Sine}};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let sine = klingt.add(Sine::new(440.0));
+    ///
+    /// // Record everything that's played
+    /// let (producer, mut recording) = rtrb::RingBuffer::new(48000 * 2);
+    /// let recorder = klingt.add_sink(RtrbSink::stereo(producer));
+    /// klingt.connect(&sine, &recorder);
+    /// klingt.output(&sine);
+    ///
+    /// let played = klingt.render(4800);
+    /// let recorded: Vec<f32> = std::iter::from_fn(|| recording.pop().ok()).collect();
+    /// assert_eq!(played, recorded);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_add_sink`](Self::try_add_sink) would return an error.
+    pub fn add_sink<S: AudioNode>(&mut self, sink: S) -> NodeHandle<S> {
+        self.try_add_sink(sink).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Add a sink that's processed every block, reporting failures instead of panicking.
+    ///
+    /// Like [`add_sink`](Self::add_sink), with the errors of [`try_add`](Self::try_add).
+    pub fn try_add_sink<S: AudioNode>(&mut self, sink: S) -> Result<NodeHandle<S>, KlingtError> {
+        let handle = self.controller.try_add_sink(sink);
+        self.sync();
+        handle
     }
 
     /// Connect two nodes together.
@@ -376,9 +497,20 @@ impl Klingt {
     ///
     /// # Cross-Sample-Rate Connections
     ///
-    /// If nodes are in different sub-graphs (different sample rates), the connection
-    /// automatically routes through the resampling bridge. However, connecting
-    /// *into* a sub-graph from the main graph is not supported.
+    /// If nodes are in different graphs (different sample rates), the connection
+    /// automatically routes through a resampling bridge, between any two graphs.
+    /// Graphs are processed in dependency order, so each one runs after the graphs
+    /// feeding it. Audio fed into a graph that the main graph also feeds arrives a
+    /// few blocks late, since that graph can only process it once the main graph
+    /// has produced it.
+    ///
+    /// # Channel Layouts
+    ///
+    /// If `to` expects its input in another [`ChannelLayout`](crate::ChannelLayout)
+    /// than `from` puts out (see [`AudioNode::input_layout`]), a
+    /// [`ChannelMixer`](crate::nodes::ChannelMixer) is put in between to up- or
+    /// down-mix. It goes away again with the connection. The output expects the
+    /// engine's [`channels`](Self::channels) unless its sink says otherwise.
     ///
     /// # Example
     ///
@@ -394,49 +526,191 @@ impl Klingt {
     ///
     /// # Panics
     ///
-    /// Panics if attempting to connect across sub-graphs in an unsupported direction.
-    pub fn connect<M1, M2>(&mut self, from: &Handle<M1>, to: &Handle<M2>)
+    /// Panics if [`try_connect`](Self::try_connect) would return an error.
+    pub fn connect<M1, E1, S1, M2, E2, S2>(&mut self, from: &Handle<M1, E1, S1>, to: &Handle<M2, E2, S2>)
     where
         M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
         M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
     {
-        // graph_id: 0 = main graph, otherwise it's the sample rate of a sub-graph
-        let from_graph_id = from.graph_id;
-        let to_graph_id = to.graph_id;
-        
-        // Create internal dasp_graph handles
-        let from_h = Self::make_handle::<M1>(from.node_id);
-        let to_h = Self::make_handle::<M2>(to.node_id);
-        
-        match (from_graph_id, to_graph_id) {
-            // Both in main graph
-            (0, 0) => {
-                self.main_graph.connect(&from_h, &to_h);
-            }
-            // Both in same sub-graph
-            (r1, r2) if r1 == r2 && r1 != 0 => {
-                let rate = r1 as u32;
-                let sub = self.sub_graphs.get_mut(&rate).unwrap();
-                sub.graph.connect(&from_h, &to_h);
-            }
-            // From sub-graph to main graph - connect through resampler bridge
-            (rate_usize, 0) if rate_usize != 0 => {
-                let rate = rate_usize as u32;
-                let sub = self.sub_graphs.get_mut(&rate).unwrap();
-                
-                // Connect source node to the RtrbSink in sub-graph
-                let sink_handle = Self::make_handle::<()>(sub.sink_node);
-                sub.graph.connect(&from_h, &sink_handle);
-                
-                // Connect ResamplingSource to destination in main graph
-                let resampler_handle = Self::make_handle::<()>(sub.resampler_node);
-                self.main_graph.connect(&resampler_handle, &to_h);
-            }
-            // Other cases not yet supported
-            _ => {
-                panic!("Cannot connect nodes across different sub-graphs directly (from graph {} to graph {})", from_graph_id, to_graph_id);
-            }
-        }
+        self.try_connect(from, to).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Connect two nodes together, reporting failures instead of panicking.
+    ///
+    /// Like [`connect`](Self::connect), but returns [`KlingtError::UnknownNode`]
+    /// for handles that belong to a different engine, [`KlingtError::Cycle`] if
+    /// the connection would close a loop, and [`KlingtError::CommandQueueFull`]
+    /// if the processor isn't keeping up.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, KlingtError, nodes::{Gain, Sine}};
+    /// let mut other = Klingt::offline(48000);
+    /// let sine = other.add(Sine::new(440.0));
+    ///
+    /// let mut klingt = Klingt::offline(48000);
+    /// let gain = klingt.add(Gain::new(0.5));
+    ///
+    /// // The sine lives in a different engine
+    /// assert!(matches!(klingt.try_connect(&sine, &gain), Err(KlingtError::UnknownNode(_))));
+    /// ```
+    pub fn try_connect<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        to: &Handle<M2, E2, S2>,
+    ) -> Result<(), KlingtError>
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        let result = self.controller.try_connect(from, to);
+        self.sync();
+        result
+    }
+
+    /// Connect two nodes through a one-block delay, allowing feedback loops.
+    ///
+    /// [`connect`](Self::connect) refuses edges that would close a loop, since
+    /// every node needs its inputs before it can run. A feedback edge instead
+    /// hands `to` what `from` produced during the previous block, so delay lines,
+    /// Karplus-Strong strings or FDN reverbs can be built from separate nodes.
+    /// The loop is always at least one block long; `from` may also be `to`.
+    ///
+    /// Between graphs at different sample rates, the resampling bridge already
+    /// delays the audio, so this is the same as [`connect`](Self::connect).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::{Gain, Mixer, Sine}};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let sine = klingt.add(Sine::new(440.0));
+    /// let mixer = klingt.add(Mixer::stereo());
+    /// let decay = klingt.add(Gain::new(0.5));
+    ///
+    /// klingt.connect(&sine, &mixer);
+    /// klingt.connect(&mixer, &decay);
+    /// // Would close a loop with `connect`
+    /// klingt.connect_feedback(&decay, &mixer);
+    /// klingt.output(&mixer);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_connect_feedback`](Self::try_connect_feedback) would return an error.
+    pub fn connect_feedback<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        to: &Handle<M2, E2, S2>,
+    )
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.try_connect_feedback(from, to).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Connect two nodes through a one-block delay, reporting failures instead of panicking.
+    ///
+    /// Like [`connect_feedback`](Self::connect_feedback), with the errors of
+    /// [`try_connect`](Self::try_connect) apart from [`KlingtError::Cycle`].
+    pub fn try_connect_feedback<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        to: &Handle<M2, E2, S2>,
+    ) -> Result<(), KlingtError>
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        let result = self.controller.try_connect_feedback(from, to);
+        self.sync();
+        result
+    }
+
+    /// Connect one output channel of `from` to one input port of `to`.
+    ///
+    /// Unlike [`connect`](Self::connect), which hands `to` every output channel of
+    /// `from`, the destination receives only channel `out_port`, as an
+    /// [`Input`](crate::Input) whose [`port`](crate::Input::port) is `in_port`.
+    /// Nodes with several inputs use the port to tell their signals apart.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::{SamplePlayer, Mixer}};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let player = klingt.add(SamplePlayer::new(vec![0.0; 4800 * 2], 2, 48000));
+    /// let mixer = klingt.add(Mixer::mono());
+    ///
+    /// // Only the right channel of the player reaches the mixer
+    /// klingt.connect_ports(&player, 1, &mixer, 0);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_connect_ports`](Self::try_connect_ports) would return an error.
+    pub fn connect_ports<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        out_port: usize,
+        to: &Handle<M2, E2, S2>,
+        in_port: usize,
+    )
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.try_connect_ports(from, out_port, to, in_port).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Connect one output channel of `from` to one input port of `to`, reporting
+    /// failures instead of panicking.
+    ///
+    /// Like [`connect_ports`](Self::connect_ports), but returns
+    /// [`KlingtError::InvalidOutputPort`] if `out_port` is not below `from`'s
+    /// [`num_outputs`](AudioNode::num_outputs) and [`KlingtError::InvalidInputPort`]
+    /// if `in_port` is not below `to`'s [`num_inputs`](AudioNode::num_inputs), along
+    /// with the errors of [`try_connect`](Self::try_connect).
+    pub fn try_connect_ports<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        out_port: usize,
+        to: &Handle<M2, E2, S2>,
+        in_port: usize,
+    ) -> Result<(), KlingtError>
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        let result = self.controller.try_connect_ports(from, out_port, to, in_port);
+        self.sync();
+        result
     }
 
     /// Connect a node directly to the audio output.
@@ -448,6 +722,11 @@ impl Klingt {
     /// If the node is in a sub-graph (different sample rate), it will be
     /// automatically routed through the resampler.
     ///
+    /// The output plays one node: the one connected last. Calling this again
+    /// with another node keeps processing the earlier ones, but they're no longer
+    /// heard. To play several nodes, connect them to a
+    /// [`Mixer`](crate::nodes::Mixer) and output that.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -459,91 +738,433 @@ impl Klingt {
     ///
     /// # Panics
     ///
-    /// Panics if no output sink is configured.
-    pub fn output<M: Send + 'static>(&mut self, handle: &Handle<M>) {
-        let sink_id = self.sink_node.expect("No output sink configured. Use default_output() or with_output().");
-        
-        if handle.graph_id == 0 {
-            // Node is in main graph - connect directly to sink
-            let from_h = Self::make_handle::<M>(handle.node_id);
-            let to_h = Self::make_handle::<()>(sink_id);
-            self.main_graph.connect(&from_h, &to_h);
-        } else {
-            // Node is in a sub-graph - connect through resampler bridge
-            let rate = handle.graph_id as u32;
-            let sub = self.sub_graphs.get_mut(&rate)
-                .expect("Sub-graph not found for handle's graph_id");
-            
-            // Connect node to RtrbSink in sub-graph
-            let from_h = Self::make_handle::<M>(handle.node_id);
-            let sink_handle = Self::make_handle::<()>(sub.sink_node);
-            sub.graph.connect(&from_h, &sink_handle);
-            
-            // Connect ResamplingSource to output sink in main graph
-            let resampler_handle = Self::make_handle::<()>(sub.resampler_node);
-            let to_h = Self::make_handle::<()>(sink_id);
-            self.main_graph.connect(&resampler_handle, &to_h);
-        }
+    /// Panics if [`try_output`](Self::try_output) would return an error, e.g. if
+    /// no output sink is configured.
+    pub fn output<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: &Handle<M, E, S>,
+    ) {
+        self.try_output(handle).unwrap_or_else(|e| panic!("{}", e))
     }
 
-    /// Process one block of audio (64 samples).
+    /// Connect a node directly to the audio output, reporting failures instead of panicking.
+    ///
+    /// Like [`output`](Self::output), but returns [`KlingtError::NoOutputSink`] if no
+    /// output sink is configured.
+    ///
+    /// # Example
     ///
-    /// Call this repeatedly in your main loop to generate audio. The method:
-    /// 1. Processes any sub-graphs to keep resamplers fed
-    /// 2. Processes the main graph to generate output
+    /// ```
+    /// # use klingt::{Klingt, KlingtError, nodes::Sine};
+    /// let mut klingt = Klingt::new(48000); // no sink yet
+    /// let sine = klingt.add(Sine::new(440.0));
+    /// assert_eq!(klingt.try_output(&sine), Err(KlingtError::NoOutputSink));
+    /// ```
+    pub fn try_output<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: &Handle<M, E, S>,
+    ) -> Result<(), KlingtError> {
+        let result = self.controller.try_output(handle);
+        self.sync();
+        result
+    }
+
+    /// Disconnect `from` from `to`, undoing [`connect`](Self::connect).
     ///
-    /// # Timing
+    /// Both nodes stay in the graph, so they can be reconnected elsewhere while
+    /// audio keeps playing. Connections that run through a resampling bridge are
+    /// handled transparently. Disconnecting nodes that aren't connected does nothing.
     ///
-    /// You're responsible for calling this at the right rate. A typical pattern:
+    /// # Example
     ///
     /// ```no_run
-    /// # use klingt::Klingt;
+    /// # use klingt::{Klingt, nodes::{Sine, Mixer}};
     /// # let mut klingt = Klingt::default_output().unwrap();
-    /// use std::time::{Duration, Instant};
-    ///
-    /// let start = Instant::now();
-    /// let rate = klingt.sample_rate() as f64;
-    /// let mut blocks = 0u64;
-    ///
-    /// loop {
-    ///     // Stay a few blocks ahead to prevent underruns
-    ///     let target = (start.elapsed().as_secs_f64() * rate / 64.0) as u64 + 4;
-    ///     
-    ///     while blocks < target {
-    ///         klingt.process();
-    ///         blocks += 1;
-    ///     }
-    ///     
-    ///     std::thread::sleep(Duration::from_micros(500));
-    /// }
+    /// let sine = klingt.add(Sine::new(440.0));
+    /// let mixer_a = klingt.add(Mixer::stereo());
+    /// let mixer_b = klingt.add(Mixer::stereo());
+    /// klingt.connect(&sine, &mixer_a);
+    ///
+    /// // Reroute the sine from one mixer to the other
+    /// klingt.disconnect(&sine, &mixer_a);
+    /// klingt.connect(&sine, &mixer_b);
     /// ```
-    pub fn process(&mut self) {
-        // First, process sub-graphs enough to feed their resamplers
-        let main_rate = self.sample_rate as f64;
-        let main_blocks = self.main_blocks_processed + 1;
-        
-        for (rate, sub) in self.sub_graphs.iter_mut() {
-            let rate_ratio = *rate as f64 / main_rate;
-            // How many sub-graph blocks needed to feed main_blocks of output
-            let blocks_needed = ((main_blocks as f64) * rate_ratio).ceil() as u64 + 4;
-            
-            while sub.blocks_processed < blocks_needed {
-                sub.graph.process();
-                sub.blocks_processed += 1;
-            }
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_disconnect`](Self::try_disconnect) would return an error.
+    pub fn disconnect<M1, E1, S1, M2, E2, S2>(&mut self, from: &Handle<M1, E1, S1>, to: &Handle<M2, E2, S2>)
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        self.try_disconnect(from, to).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Disconnect `from` from `to`, reporting failures instead of panicking.
+    ///
+    /// Returns [`KlingtError::CommandQueueFull`] without changing anything if the
+    /// processor can't take the edit yet. See [`disconnect`](Self::disconnect).
+    pub fn try_disconnect<M1, E1, S1, M2, E2, S2>(
+        &mut self,
+        from: &Handle<M1, E1, S1>,
+        to: &Handle<M2, E2, S2>,
+    ) -> Result<(), KlingtError>
+    where
+        M1: Send + 'static,
+        E1: Send + 'static,
+        S1: Send + 'static,
+        M2: Send + 'static,
+        E2: Send + 'static,
+        S2: Send + 'static,
+    {
+        let result = self.controller.try_disconnect(from, to);
+        self.sync();
+        result
+    }
+
+    /// Disconnect a node from everything it's connected to.
+    ///
+    /// This removes both incoming and outgoing connections, including ones
+    /// that go through a resampling bridge. The node itself stays in the graph.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_disconnect_all`](Self::try_disconnect_all) would return an error.
+    pub fn disconnect_all<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: &Handle<M, E, S>,
+    ) {
+        self.try_disconnect_all(handle).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Disconnect a node from everything it's connected to, reporting failures
+    /// instead of panicking.
+    ///
+    /// Returns [`KlingtError::CommandQueueFull`] without changing anything if the
+    /// processor can't take the edit yet. See [`disconnect_all`](Self::disconnect_all).
+    pub fn try_disconnect_all<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: &Handle<M, E, S>,
+    ) -> Result<(), KlingtError> {
+        let result = self.controller.try_disconnect_all(handle);
+        self.sync();
+        result
+    }
+
+    /// Remove a node from the audio graph.
+    ///
+    /// The node is detached from everything it was connected to and dropped.
+    /// Consuming the [`Handle`] ensures no further messages can be sent to it.
+    ///
+    /// If the node lived in a sub-graph (different sample rate) and was the last
+    /// node there, the sub-graph and its resampling bridge are torn down as well.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use klingt::{Klingt, nodes::Sine};
+    /// # let mut klingt = Klingt::default_output().unwrap();
+    /// let sine = klingt.add(Sine::new(440.0));
+    /// klingt.output(&sine);
+    ///
+    /// // ...later, when the sound is no longer needed
+    /// klingt.remove(sine);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the command queue is full, see [`try_remove`](Self::try_remove).
+    pub fn remove<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: Handle<M, E, S>,
+    ) {
+        if self.try_remove(handle).is_err() {
+            panic!("{}", KlingtError::CommandQueueFull);
         }
-        
-        // Then process main graph
-        self.main_graph.process();
-        self.main_blocks_processed += 1;
-    }
-
-    // Helper to create internal handle (static - no borrow needed)
-    fn make_handle<M: Send + 'static>(node_id: NodeId) -> crate::graph::NodeHandle<M> {
-        crate::graph::NodeHandle {
-            id: node_id,
-            sender: rtrb::RingBuffer::new(1).0, // dummy, not used for connect
-            _marker: PhantomData,
+    }
+
+    /// Remove a node from the audio graph, handing the handle back if the
+    /// processor can't take the edit yet.
+    ///
+    /// Like [`Handle::send`], which hands back the message: nothing changes,
+    /// and the node can be removed with the handle later. See [`remove`](Self::remove).
+    pub fn try_remove<M: Send + 'static, E: Send + 'static, S: Send + 'static>(
+        &mut self,
+        handle: Handle<M, E, S>,
+    ) -> Result<(), Handle<M, E, S>> {
+        let result = self.controller.try_remove(handle);
+        self.sync();
+        result
+    }
+
+    /// Build the nodes and connections of a [`GraphDescription`], so patches can
+    /// live in data files instead of code.
+    ///
+    /// Every node is created by the factory `registry` has for its type, then
+    /// connected as described, and the nodes listed as output are connected to
+    /// the output. Returns the handles of the new nodes by name. Nodes are added
+    /// to the graph that's already there, so this can be called more than once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`try_from_description`](Self::try_from_description) would return an error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, GraphDescription, NodeRegistry};
+    /// # fn load() -> GraphDescription {
+    /// #     use klingt::{ConnectionDescription, NodeDescription};
+    /// #     GraphDescription::new()
+    /// #         .with_node(NodeDescription::new("osc", "Sine").with_param("frequency", 440.0))
+    /// #         .with_node(NodeDescription::new("vol", "Gain").with_param("gain", 0.5))
+    /// #         .with_connection(ConnectionDescription::new("osc", "vol"))
+    /// #         .with_output("vol")
+    /// # }
+    /// use klingt::nodes::{Gain, GainMessage};
+    ///
+    /// let mut klingt = Klingt::offline(48000);
+    /// // e.g. deserialized from a file with the `serde` feature
+    /// let patch: GraphDescription = load();
+    ///
+    /// let mut handles = klingt.from_description(&patch, &NodeRegistry::new());
+    /// let vol = handles.get_mut::<Gain>("vol").unwrap();
+    /// vol.send(GainMessage::SetGain(0.25)).ok();
+    /// ```
+    pub fn from_description(&mut self, description: &GraphDescription, registry: &NodeRegistry) -> NamedHandles {
+        self.try_from_description(description, registry).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Build the nodes and connections of a [`GraphDescription`], reporting
+    /// failures instead of panicking.
+    ///
+    /// Names, node types and parameters are all checked before anything is
+    /// added. If a connection fails after that, e.g. because it would close a
+    /// loop, the nodes added so far are removed again, so a failed description
+    /// leaves the graph as it was. Room for that is kept as the patch is built:
+    /// a step the queue can't hold along with the rollback of everything before
+    /// it fails with [`KlingtError::CommandQueueFull`], and the patch is rolled back.
+    ///
+    /// The whole patch goes through the command queue in one go, which holds 1024
+    /// commands. Each node takes one to add, three if it starts a sub-graph, and up
+    /// to four to remove again. A connection within a graph takes one; connections
+    /// between sample rates or channel layouts take up to 18, for the bridges and
+    /// channel mixers they need.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, GraphDescription, NodeDescription, NodeRegistry, KlingtError};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let patch = GraphDescription::new().with_node(NodeDescription::new("osc", "Saw"));
+    ///
+    /// let result = klingt.try_from_description(&patch, &NodeRegistry::new());
+    /// assert_eq!(result.err(), Some(KlingtError::UnknownNodeType("Saw".into())));
+    /// ```
+    pub fn try_from_description(
+        &mut self,
+        description: &GraphDescription,
+        registry: &NodeRegistry,
+    ) -> Result<NamedHandles, KlingtError> {
+        let result = self.controller.try_from_description(description, registry);
+        self.sync();
+        result
+    }
+
+    /// Describe the live graph structure in Graphviz DOT format, for debugging routing.
+    ///
+    /// The main graph and every sub-graph are drawn as clusters labelled with
+    /// their sample rate. Nodes show their type and channel counts; the output
+    /// and extra sinks are outlined twice. Connections between graphs show the
+    /// bridge Klingt created for them: an `RtrbSink` in the source graph feeding a
+    /// `ResamplingSource` in the destination graph. Port connections are labelled
+    /// `output -> input`, feedback connections are dashed.
+    ///
+    /// Render it with e.g. `dot -Tsvg graph.dot -o graph.svg`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, nodes::{SamplePlayer, Gain}};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let player = klingt.add(SamplePlayer::new(vec![0.0; 44100], 1, 44100));
+    /// let gain = klingt.add(Gain::new(0.5));
+    /// klingt.connect(&player, &gain);
+    /// klingt.output(&gain);
+    ///
+    /// let dot = klingt.to_dot();
+    /// assert!(dot.contains("sub-graph (44100 Hz)"));
+    /// assert!(dot.contains("ResamplingSource"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        self.controller.to_dot()
+    }
+
+    /// Start the transport from where it is.
+    ///
+    /// The transport is the engine's musical clock: a position in beats that
+    /// advances at the current tempo while it plays. Nodes read it from
+    /// [`ProcessContext::transport`](crate::ProcessContext::transport), e.g. the
+    /// [`Metronome`](crate::nodes::Metronome). It starts out stopped at beat 0, at
+    /// 120 BPM in 4/4.
+    ///
+    /// Like graph edits, transport changes take effect at the next block boundary.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, TimeSignature, nodes::Metronome};
+    /// let mut klingt = Klingt::offline(48000);
+    /// let click = klingt.add(Metronome::new());
+    /// klingt.output(&click);
+    ///
+    /// klingt.set_tempo(90.0);
+    /// klingt.set_time_signature(TimeSignature::new(3, 4));
+    /// klingt.set_loop(Some(0.0..12.0)); // Four bars, over and over
+    /// klingt.start_transport();
+    ///
+    /// let samples = klingt.render(48000);
+    /// ```
+    pub fn start_transport(&mut self) {
+        self.controller.start_transport();
+        self.sync();
+    }
+
+    /// Stop the transport, keeping its position.
+    ///
+    /// [`start_transport`](Self::start_transport) continues from there.
+    pub fn stop_transport(&mut self) {
+        self.controller.stop_transport();
+        self.sync();
+    }
+
+    /// Move the transport to `beat` (in quarter notes), whether or not it's playing.
+    pub fn seek_transport(&mut self, beat: f64) {
+        self.controller.seek_transport(beat);
+        self.sync();
+    }
+
+    /// Set a constant tempo in beats (quarter notes) per minute.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bpm` isn't positive.
+    pub fn set_tempo(&mut self, bpm: f64) {
+        self.controller.set_tempo(bpm);
+        self.sync();
+    }
+
+    /// Replace the tempo map, for tempo changes at given beats.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use klingt::{Klingt, TempoMap};
+    /// # let mut klingt = Klingt::offline(48000);
+    /// // Double time from beat 16
+    /// klingt.set_tempo_map(TempoMap::new(100.0).with_change(16.0, 200.0));
+    /// ```
+    pub fn set_tempo_map(&mut self, tempo: TempoMap) {
+        self.controller.set_tempo_map(tempo);
+        self.sync();
+    }
+
+    /// Set the time signature bars are counted in.
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.controller.set_time_signature(time_signature);
+        self.sync();
+    }
+
+    /// Loop the transport over a range of beats, or stop looping with `None`.
+    ///
+    /// When the transport reaches the end of the range, it jumps back to its
+    /// start, on the exact sample. A transport that's already past the end
+    /// plays on without looping.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty.
+    pub fn set_loop(&mut self, beats: Option<Range<f64>>) {
+        self.controller.set_loop(beats);
+        self.sync();
+    }
+
+    delegate! {
+        to self.processor {
+            /// Process one block of audio ([`block_size`](Self::block_size) samples).
+            ///
+            /// Call this repeatedly in your main loop to generate audio. The method:
+            /// 1. Processes any sub-graphs to keep resamplers fed
+            /// 2. Processes the main graph to generate output
+            ///
+            /// # Timing
+            ///
+            /// You're responsible for calling this at the right rate. A typical pattern:
+            ///
+            /// ```no_run
+            /// # use klingt::Klingt;
+            /// # let mut klingt = Klingt::default_output().unwrap();
+            /// use std::time::{Duration, Instant};
+            ///
+            /// let start = Instant::now();
+            /// let rate = klingt.sample_rate() as f64;
+            /// let block = klingt.block_size() as f64;
+            /// let mut blocks = 0u64;
+            ///
+            /// loop {
+            ///     // Stay a few blocks ahead to prevent underruns
+            ///     let target = (start.elapsed().as_secs_f64() * rate / block) as u64 + 4;
+            ///     
+            ///     while blocks < target {
+            ///         klingt.process();
+            ///         blocks += 1;
+            ///     }
+            ///     
+            ///     std::thread::sleep(Duration::from_micros(500));
+            /// }
+            /// ```
+            pub fn process(&mut self);
+
+            /// Render `frames` frames of output into memory, faster than real time.
+            ///
+            /// Returns interleaved samples with [`channels`](Self::channels) channels - whatever
+            /// the output sink receives, including audio resampled from sub-graphs. The graph
+            /// advances exactly as with [`process`](Self::process), so this is meant for engines
+            /// created with [`offline`](Self::offline); with a device sink the audio is also
+            /// pushed to the device.
+            ///
+            /// Like the sink, this only has the node last connected with
+            /// [`output`](Self::output), see there.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// # use klingt::{Klingt, nodes::Sine};
+            /// let mut klingt = Klingt::offline(44100);
+            /// let sine = klingt.add(Sine::new(440.0));
+            /// klingt.output(&sine);
+            ///
+            /// let samples = klingt.render(100);
+            /// assert_eq!(samples.len(), 200);
+            /// ```
+            pub fn render(&mut self, frames: usize) -> Vec<f32>;
+
+            /// Render into an existing buffer of interleaved samples.
+            ///
+            /// Fills all of `out`, processing as many blocks as needed. `out` doesn't have to
+            /// be a multiple of the block size; left-over samples are returned by the next call.
+            pub fn render_into(&mut self, out: &mut [f32]);
         }
     }
+
+    /// Apply the controller's commands right away, since we own the processor too
+    fn sync(&mut self) {
+        self.processor.apply_commands();
+        self.controller.collect_garbage();
+    }
 }
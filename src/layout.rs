@@ -0,0 +1,290 @@
+//! Channel layouts and the matrices mixing between them
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64::consts::FRAC_1_SQRT_2;
+
+use crate::buffer::Sample;
+
+/// How the channels of a node's inputs or outputs are arranged.
+///
+/// Nodes declare the layout of their outputs with
+/// [`AudioNode::output_layout`](crate::AudioNode::output_layout), and the layout
+/// they expect at their inputs with [`AudioNode::input_layout`](crate::AudioNode::input_layout).
+/// Connecting nodes whose layouts differ puts a [`ChannelMixer`](crate::nodes::ChannelMixer)
+/// in between, which up- or down-mixes with [`mix_matrix`](Self::mix_matrix).
+///
+/// Speaker channels come in WAV order:
+///
+/// | Layout       | Channels                |
+/// |--------------|-------------------------|
+/// | `Mono`       | C                       |
+/// | `Stereo`     | L R                     |
+/// | `Quad`       | L R SL SR               |
+/// | `Surround51` | L R C LFE SL SR         |
+/// | `Surround71` | L R C LFE BL BR SL SR   |
+///
+/// # Mixing
+///
+/// - Between mono, stereo, quad and 5.1, the speaker mixing rules of the Web
+///   Audio API: up-mixing copies the speakers both layouts have (mono goes to
+///   the center, or to both front speakers if there's none), down-mixing folds
+///   the missing speakers into their neighbours.
+/// - 7.1 is mixed through 5.1, folding the back speakers into the sides at -3 dB.
+/// - Ambisonics between orders keep the components both have. Speakers are
+///   encoded as plane waves from their direction; decoding points a virtual
+///   cardioid at each speaker (leaving the LFE silent), which only uses the
+///   first order.
+/// - [`Discrete`](Self::Discrete) channels, on either side, are copied by index
+///   and extra outputs stay silent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelLayout {
+    /// One channel
+    Mono,
+    /// Left and right
+    Stereo,
+    /// Front and surround pairs
+    Quad,
+    /// Front pair, center, LFE and surround pair
+    Surround51,
+    /// 5.1 with an extra back pair
+    Surround71,
+    /// Ambisonics of the given order, with `(order + 1)²` channels in ACN order
+    /// and SN3D normalization (AmbiX)
+    Ambisonic(u32),
+    /// Channels without a speaker arrangement
+    Discrete(usize),
+}
+
+/// Speaker positions of the speaker layouts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Speaker {
+    Left,
+    Right,
+    Center,
+    Lfe,
+    SideLeft,
+    SideRight,
+    BackLeft,
+    BackRight,
+}
+
+use Speaker::*;
+
+impl ChannelLayout {
+    /// Number of channels in this layout
+    pub fn channels(self) -> usize {
+        match self {
+            ChannelLayout::Mono => 1,
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Quad => 4,
+            ChannelLayout::Surround51 => 6,
+            ChannelLayout::Surround71 => 8,
+            ChannelLayout::Ambisonic(order) => (order as usize + 1).pow(2),
+            ChannelLayout::Discrete(channels) => channels,
+        }
+    }
+
+    /// The usual layout for a channel count: mono, stereo, quad, 5.1 or 7.1 for
+    /// 1, 2, 4, 6 or 8 channels, [`Discrete`](Self::Discrete) for anything else
+    pub fn from_channels(channels: usize) -> Self {
+        match channels {
+            1 => ChannelLayout::Mono,
+            2 => ChannelLayout::Stereo,
+            4 => ChannelLayout::Quad,
+            6 => ChannelLayout::Surround51,
+            8 => ChannelLayout::Surround71,
+            channels => ChannelLayout::Discrete(channels),
+        }
+    }
+
+    /// Gains mixing this layout into `to`.
+    ///
+    /// One row per channel of `to`, each with a gain per channel of `self`:
+    /// output channel `o` is the sum of input channel `i` times
+    /// `matrix[o * self.channels() + i]`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use klingt::ChannelLayout;
+    ///
+    /// // Stereo to mono averages the two sides
+    /// assert_eq!(ChannelLayout::Stereo.mix_matrix(ChannelLayout::Mono), vec![0.5, 0.5]);
+    /// ```
+    pub fn mix_matrix(self, to: ChannelLayout) -> Vec<Sample> {
+        self.matrix(to).into_iter().map(|gain| gain as Sample).collect()
+    }
+
+    fn matrix(self, to: ChannelLayout) -> Vec<f64> {
+        match (self.speakers(), to.speakers(), self, to) {
+            (Some(_), Some(_), _, _) => speaker_matrix(self, to),
+            (Some(speakers), None, _, ChannelLayout::Ambisonic(order)) => {
+                let encoded: Vec<Vec<f64>> = speakers
+                    .iter()
+                    .map(|&speaker| match speaker {
+                        Lfe => vec![0.0; to.channels()],
+                        speaker => encode(order, azimuth(self, speaker)),
+                    })
+                    .collect();
+                // One row per ambisonic component
+                (0..to.channels()).flat_map(|k| encoded.iter().map(move |gains| gains[k])).collect()
+            }
+            (None, Some(speakers), ChannelLayout::Ambisonic(order), _) => speakers
+                .iter()
+                .flat_map(|&speaker| decode(order, self.channels(), speaker, azimuth(to, speaker)))
+                .collect(),
+            _ => by_index(self.channels(), to.channels()),
+        }
+    }
+
+    fn speakers(self) -> Option<&'static [Speaker]> {
+        match self {
+            ChannelLayout::Mono => Some(&[Center]),
+            ChannelLayout::Stereo => Some(&[Left, Right]),
+            ChannelLayout::Quad => Some(&[Left, Right, SideLeft, SideRight]),
+            ChannelLayout::Surround51 => Some(&[Left, Right, Center, Lfe, SideLeft, SideRight]),
+            ChannelLayout::Surround71 => Some(&[Left, Right, Center, Lfe, BackLeft, BackRight, SideLeft, SideRight]),
+            ChannelLayout::Ambisonic(_) | ChannelLayout::Discrete(_) => None,
+        }
+    }
+}
+
+/// Mix between speaker layouts, going through 5.1 for 7.1
+fn speaker_matrix(from: ChannelLayout, to: ChannelLayout) -> Vec<f64> {
+    use ChannelLayout::{Surround51, Surround71};
+
+    match (from, to) {
+        (Surround71, Surround71) => by_speaker(from, to, same),
+        (Surround71, _) => {
+            let fold = by_speaker(Surround71, Surround51, |out, input| match (out, input) {
+                (SideLeft, BackLeft) | (SideRight, BackRight) => FRAC_1_SQRT_2,
+                (out, input) => same(out, input),
+            });
+            multiply(&speaker_matrix(Surround51, to), &fold, Surround51.channels(), Surround71.channels())
+        }
+        (_, Surround71) => {
+            let widen = by_speaker(Surround51, Surround71, same);
+            multiply(&widen, &speaker_matrix(from, Surround51), Surround51.channels(), from.channels())
+        }
+        _ => by_speaker(from, to, |out, input| web_audio_gain(from, to, out, input)),
+    }
+}
+
+/// Gain from speaker `input` of `from` to speaker `out` of `to`, as the Web Audio
+/// API mixes between mono, stereo, quad and 5.1
+fn web_audio_gain(from: ChannelLayout, to: ChannelLayout, out: Speaker, input: Speaker) -> f64 {
+    use ChannelLayout::{Mono, Quad, Stereo, Surround51};
+    const H: f64 = FRAC_1_SQRT_2;
+
+    match (from, to, out, input) {
+        // Mono has no center to go to
+        (Mono, Stereo, _, _) | (Mono, Quad, Left, _) | (Mono, Quad, Right, _) => 1.0,
+        (Mono, Quad, _, _) => 0.0,
+        // Up-mixing otherwise copies what both have
+        _ if to.channels() >= from.channels() => same(out, input),
+
+        (Stereo, Mono, _, _) => 0.5,
+        (Quad, Mono, _, _) => 0.25,
+        (Surround51, Mono, _, Left) | (Surround51, Mono, _, Right) => H,
+        (Surround51, Mono, _, Center) => 1.0,
+        (Surround51, Mono, _, SideLeft) | (Surround51, Mono, _, SideRight) => 0.5,
+
+        (Quad, Stereo, Left, Left) | (Quad, Stereo, Left, SideLeft) => 0.5,
+        (Quad, Stereo, Right, Right) | (Quad, Stereo, Right, SideRight) => 0.5,
+
+        (Surround51, Stereo, _, Center) => H,
+        (Surround51, Stereo, Left, SideLeft) | (Surround51, Stereo, Right, SideRight) => H,
+        (Surround51, Quad, Left, Center) | (Surround51, Quad, Right, Center) => H,
+        (Surround51, _, out, input) => same(out, input),
+
+        _ => 0.0,
+    }
+}
+
+fn same(out: Speaker, input: Speaker) -> f64 {
+    if out == input { 1.0 } else { 0.0 }
+}
+
+/// Matrix with a gain for every pair of speakers
+fn by_speaker(from: ChannelLayout, to: ChannelLayout, gain: impl Fn(Speaker, Speaker) -> f64) -> Vec<f64> {
+    let (inputs, outputs) = (from.speakers().unwrap_or(&[]), to.speakers().unwrap_or(&[]));
+    outputs.iter().flat_map(|&out| inputs.iter().map(move |&input| (out, input))).map(|(out, input)| gain(out, input)).collect()
+}
+
+/// Matrix copying channel `i` to channel `i`
+fn by_index(inputs: usize, outputs: usize) -> Vec<f64> {
+    (0..outputs).flat_map(|out| (0..inputs).map(move |input| if out == input { 1.0 } else { 0.0 })).collect()
+}
+
+/// Apply `first` (`mid` rows of `inputs` gains), then `second`
+fn multiply(second: &[f64], first: &[f64], mid: usize, inputs: usize) -> Vec<f64> {
+    second
+        .chunks(mid)
+        .flat_map(|row| (0..inputs).map(move |input| row.iter().enumerate().map(|(k, gain)| gain * first[k * inputs + input]).sum()))
+        .collect()
+}
+
+/// Direction of a speaker in radians, counterclockwise from the front
+fn azimuth(layout: ChannelLayout, speaker: Speaker) -> f64 {
+    let degrees: f64 = match (layout, speaker) {
+        (_, Center) | (_, Lfe) => 0.0,
+        (ChannelLayout::Quad, Left) => 45.0,
+        (_, Left) => 30.0,
+        (ChannelLayout::Quad, SideLeft) => 135.0,
+        (ChannelLayout::Surround71, SideLeft) => 90.0,
+        (_, SideLeft) => 110.0,
+        (_, BackLeft) => 150.0,
+        // Mirror images of the left side
+        (layout, Right) => -azimuth(layout, Left).to_degrees(),
+        (layout, SideRight) => -azimuth(layout, SideLeft).to_degrees(),
+        (layout, BackRight) => -azimuth(layout, BackLeft).to_degrees(),
+    };
+    degrees.to_radians()
+}
+
+/// Ambisonic gains (ACN, SN3D) of a plane wave from `azimuth` on the horizon
+fn encode(order: u32, azimuth: f64) -> Vec<f64> {
+    let order = order as i64;
+    (0..=order)
+        .flat_map(|n| (-n..=n).map(move |m| (n, m)))
+        .map(|(n, m)| {
+            let a = m.abs();
+            let norm = ((if m == 0 { 1.0 } else { 2.0 }) * factorial(n - a) / factorial(n + a)).sqrt();
+            let circular = if m >= 0 { (m as f64 * azimuth).cos() } else { (a as f64 * azimuth).sin() };
+            norm * legendre_on_horizon(n, a) * circular
+        })
+        .collect()
+}
+
+/// Gains of a virtual cardioid pointing at a speaker at `azimuth`, from first
+/// order ambisonics (W, Y, Z, X) of `channels` channels
+fn decode(order: u32, channels: usize, speaker: Speaker, azimuth: f64) -> Vec<f64> {
+    let mut gains = vec![0.0; channels];
+    if speaker == Lfe {
+        return gains;
+    }
+    gains[0] = 0.5;
+    if order >= 1 {
+        gains[1] = 0.5 * azimuth.sin();
+        gains[3] = 0.5 * azimuth.cos();
+    }
+    gains
+}
+
+/// Associated Legendre function P(n, m) at 0, without the Condon-Shortley phase
+fn legendre_on_horizon(n: i64, m: i64) -> f64 {
+    if (n + m) % 2 == 1 {
+        return 0.0;
+    }
+    let sign = if (n - m) / 2 % 2 == 0 { 1.0 } else { -1.0 };
+    sign * double_factorial(n + m - 1) / double_factorial(n - m)
+}
+
+fn factorial(n: i64) -> f64 {
+    (1..=n).map(|k| k as f64).product()
+}
+
+fn double_factorial(n: i64) -> f64 {
+    (1..=n).rev().step_by(2).map(|k| k as f64).product()
+}
@@ -29,8 +29,14 @@
 //!
 //! Audio processing is done by **nodes** that implement [`AudioNode`]. When you add a node
 //! to Klingt, you get back a [`Handle`] that lets you:
-//! - Connect nodes together with [`Klingt::connect`]
-//! - Send parameter updates with [`Handle::send`]
+//! - Connect nodes together with [`Klingt::connect`] (and reroute with [`Klingt::disconnect`])
+//! - Wire individual channels to specific inputs with [`Klingt::connect_ports`]
+//! - Build feedback loops with [`Klingt::connect_feedback`]
+//! - Record or monitor alongside the output with [`Klingt::add_sink`]
+//! - Send parameter updates with [`Handle::send`], or sample-accurately with [`Handle::send_at`]
+//! - Receive events the node reports back with [`Handle::poll_events`]
+//! - Read the node's current state with [`Handle::state`]
+//! - Remove the node again with [`Klingt::remove`]
 //!
 //! ```no_run
 //! # use klingt::{Klingt, nodes::{Sine, SineMessage, Gain}};
@@ -58,6 +64,82 @@
 //! klingt.output(&handle);          // Routed through resampler
 //! ```
 //!
+//! ### Running on the Audio Thread
+//!
+//! [`Klingt::split`] separates graph editing from processing. The [`KlingtController`]
+//! stays on your thread and sends structural changes over a lock-free queue; the
+//! [`KlingtProcessor`] owns the graph and can be moved into an audio callback, where
+//! changes are applied at block boundaries:
+//!
+//! ```no_run
+//! use klingt::{Klingt, CpalDevice, nodes::Sine};
+//!
+//! let device = CpalDevice::default_output().expect("No audio device");
+//! let (mut controller, processor) = Klingt::for_device(&device).split();
+//! let _stream = device.play(processor).expect("Failed to start audio stream");
+//!
+//! // Edit the graph while it plays
+//! let sine = controller.add(Sine::new(440.0));
+//! controller.output(&sine);
+//! ```
+//!
+//! ### Offline Rendering
+//!
+//! [`Klingt::offline`] creates an engine without an audio device. Use
+//! [`Klingt::render`] to pull interleaved output into memory as fast as the CPU allows:
+//!
+//! ```
+//! use klingt::{Klingt, nodes::Sine};
+//!
+//! let mut klingt = Klingt::offline(48000);
+//! let sine = klingt.add(Sine::new(440.0));
+//! klingt.output(&sine);
+//!
+//! let one_second = klingt.render(48000);
+//! ```
+//!
+//! With the `std` feature, [`Klingt::with_threads`] spreads independent branches
+//! of the graph over several threads.
+//!
+//! ### Patches as Data
+//!
+//! A [`GraphDescription`] lists nodes by type name and parameters, their
+//! connections and what goes to the output. [`Klingt::from_description`] builds
+//! it with the factories of a [`NodeRegistry`] and hands back the handles by name.
+//! With the `serde` feature, descriptions can be loaded from JSON, TOML and friends:
+//!
+//! ```
+//! use klingt::{Klingt, ConnectionDescription, GraphDescription, NodeDescription, NodeRegistry};
+//!
+//! let patch = GraphDescription::new()
+//!     .with_node(NodeDescription::new("osc", "Sine").with_param("frequency", 220.0))
+//!     .with_node(NodeDescription::new("vol", "Gain").with_param("gain", 0.5))
+//!     .with_connection(ConnectionDescription::new("osc", "vol"))
+//!     .with_output("vol");
+//!
+//! let mut klingt = Klingt::offline(48000);
+//! let handles = klingt.from_description(&patch, &NodeRegistry::new());
+//! ```
+//!
+//! ### Musical Transport
+//!
+//! The engine keeps a transport - tempo (with changes), time signature, play
+//! position and loop region - controlled with [`Klingt::start_transport`],
+//! [`Klingt::set_tempo_map`] and friends. Nodes read it from
+//! [`ProcessContext::transport`] to follow beats and bars:
+//!
+//! ```
+//! use klingt::{Klingt, TempoMap, nodes::Metronome};
+//!
+//! let mut klingt = Klingt::offline(48000);
+//! let click = klingt.add(Metronome::new());
+//! klingt.output(&click);
+//!
+//! klingt.set_tempo_map(TempoMap::new(120.0).with_change(8.0, 140.0));
+//! klingt.start_transport();
+//! let samples = klingt.render(48000 * 4);
+//! ```
+//!
 //! ### Message Passing (No Locks!)
 //!
 //! All parameter updates use lock-free ring buffers. The audio thread never
@@ -68,9 +150,9 @@
 //!
 //! See the [`nodes`] module for available nodes:
 //!
-//! - **Sources**: [`Sine`](nodes::Sine), [`SamplePlayer`](nodes::SamplePlayer)
-//! - **Effects**: [`Gain`](nodes::Gain), [`Mixer`](nodes::Mixer), [`SlewLimiter`](nodes::SlewLimiter)
-//! - **Sinks**: [`CpalSink`](nodes::CpalSink) (with `cpal_sink` feature)
+//! - **Sources**: [`Sine`](nodes::Sine), [`SamplePlayer`](nodes::SamplePlayer), [`Metronome`](nodes::Metronome)
+//! - **Effects**: [`Gain`](nodes::Gain), [`Mixer`](nodes::Mixer), [`SlewLimiter`](nodes::SlewLimiter), [`ChannelMixer`](nodes::ChannelMixer)
+//! - **Sinks**: [`CpalSink`](nodes::CpalSink) (with `cpal_sink` feature), [`NullSink`](nodes::NullSink)
 //!
 //! ## Custom Nodes
 //!
@@ -78,8 +160,7 @@
 //! of a square wave oscillator with message-based parameter control:
 //!
 //! ```
-//! use klingt::{AudioNode, ProcessContext};
-//! use dasp_graph::{Buffer, Input};
+//! use klingt::{AudioNode, Buffer, Input, ProcessContext, Sample};
 //!
 //! // Define messages for runtime parameter control
 //! #[derive(Clone, Copy, Debug)]
@@ -109,6 +190,8 @@
 //!
 //! impl AudioNode for Square {
 //!     type Message = SquareMessage;
+//!     type Event = ();
+//!     type State = ();
 //!
 //!     fn process(
 //!         &mut self,
@@ -128,13 +211,15 @@
 //!
 //!         // 2. Generate audio
 //!         let phase_inc = self.frequency / ctx.sample_rate as f32;
+//!         // Buffers hold `Sample`s: f32, or f64 with the `f64` feature
+//!         let amplitude = self.amplitude as Sample;
 //!         
 //!         for sample in outputs[0].iter_mut() {
 //!             // Square wave: high when phase < pulse_width, low otherwise
 //!             *sample = if self.phase < self.pulse_width {
-//!                 self.amplitude
+//!                 amplitude
 //!             } else {
-//!                 -self.amplitude
+//!                 -amplitude
 //!             };
 //!
 //!             // Advance and wrap phase
@@ -172,31 +257,66 @@
 //! Override [`num_inputs`](AudioNode::num_inputs) and [`num_outputs`](AudioNode::num_outputs)
 //! to define your node's channel configuration.
 //!
+//! Buffers hold [`Sample`]s, which are `f32` unless the `f64` feature is enabled.
+//!
+//! ### Channel Layouts
+//!
+//! Nodes declare how their channels are arranged with a [`ChannelLayout`]:
+//! [`output_layout`](AudioNode::output_layout) defaults to the usual layout for
+//! the node's output count, and [`input_layout`](AudioNode::input_layout) is
+//! `None` unless the node wants its inputs in a particular layout. Connecting
+//! layouts that differ puts a [`ChannelMixer`](nodes::ChannelMixer) in between.
+//!
 //! ## Feature Flags
 //!
-//! - `cpal_sink` - Enable CPAL audio output (adds [`CpalDevice`] and [`CpalSink`](nodes::CpalSink))
-//! - `std` - Enable standard library (enabled by default)
+//! - `cpal_sink` - Enable CPAL audio output (adds [`CpalDevice`], [`CpalStream`] and [`CpalSink`](nodes::CpalSink))
+//! - `f64` - Process in double precision: [`Sample`] becomes `f64`, and audio is
+//!   only converted to `f32` at the sinks and [`Klingt::render`]
+//! - `serde` - Serialize and deserialize [`GraphDescription`]s
+//! - `std` - Enable standard library (off by default), needed for [`Klingt::with_threads`]
 //!
 //! ## Design Principles
 //!
 //! - **Lock-free audio thread**: No allocations, no `Arc`/`Mutex` on the hot path
+//!   (graph edits can allocate when a graph grows, see [`KlingtProcessor`])
 //! - **Automatic resampling**: Nodes at different sample rates just work
-//! - **Fixed block size**: 64 samples per block (from dasp_graph)
+//! - **Configurable block size**: 64 samples per block by default, see [`Klingt::with_block_size`]
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
 extern crate alloc;
 
+mod buffer;
+mod simd;
+mod layout;
 mod node;
+mod transport;
 mod graph;
+mod command;
+mod error;
+mod handle;
+mod snapshot;
+#[cfg(feature = "std")]
+mod pool;
+mod description;
+mod controller;
+mod processor;
 mod klingt;
 pub mod nodes;
 
 #[cfg(feature = "cpal_sink")]
 mod device;
 
+pub use buffer::{Buffer, Input, OutputSample, Sample};
+pub use layout::ChannelLayout;
 pub use node::{AudioNode, ProcessContext, NodeId};
-pub use klingt::{Klingt, Handle};
+pub use handle::{Handle, NodeHandle};
+pub use transport::{TempoMap, TimeSignature, TransportInfo};
+pub use description::{ConnectionDescription, GraphDescription, NamedHandles, NodeDescription, NodeRegistry, ParamValue, Params};
+pub use error::KlingtError;
+pub use controller::KlingtController;
+pub use processor::KlingtProcessor;
+pub use klingt::Klingt;
 
 #[cfg(feature = "cpal_sink")]
-pub use device::CpalDevice;
+pub use device::{CpalDevice, CpalStream};
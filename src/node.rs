@@ -1,17 +1,39 @@
 //! Core node trait and context types.
 
-use dasp_graph::{Buffer, Input};
+use crate::buffer::{Buffer, Input};
+use crate::layout::ChannelLayout;
+use crate::transport::TransportInfo;
 
 /// Information available during audio processing.
 ///
-/// Passed to every [`AudioNode::process`] call. Contains the graph's sample rate
-/// and the buffer size (always 64 samples in the current implementation).
+/// Passed to every [`AudioNode::process`] call. Contains the graph's sample rate,
+/// the buffer size (64 samples unless changed with
+/// [`Klingt::with_block_size`](crate::Klingt::with_block_size)), the graph's
+/// running sample clock and the musical transport.
 #[derive(Clone, Copy, Debug)]
 pub struct ProcessContext {
     /// Sample rate of the graph in Hz (e.g., 44100, 48000)
     pub sample_rate: u32,
-    /// Number of samples per buffer (currently always 64)
+    /// Number of samples per buffer, usually the engine's block size. Less when
+    /// the block is split at a message sent with [`Handle::send_at`](crate::Handle::send_at).
     pub buffer_size: usize,
+    /// Frames the graph has processed before this buffer, counted at `sample_rate`
+    ///
+    /// Starts at 0 in the output graph. Sub-graphs at other rates start counting
+    /// from the output's time at which they were created, so [`seconds`](Self::seconds)
+    /// agrees between graphs: audio a sub-graph makes at a given time plays at
+    /// that time in the output. Except for audio that comes from the output's
+    /// graph and goes back to it, which arrives a block or two late.
+    pub frame: u64,
+    /// Tempo, time signature and play position of the engine's transport
+    pub transport: TransportInfo,
+}
+
+impl ProcessContext {
+    /// Time of the first sample in this buffer, in seconds since the engine started
+    pub fn seconds(&self) -> f64 {
+        self.frame as f64 / self.sample_rate as f64
+    }
 }
 
 /// Unique identifier for a node within a graph.
@@ -33,8 +55,7 @@ pub struct NodeId(pub(crate) u32);
 /// Define your message type and handle it at the start of `process()`:
 ///
 /// ```
-/// use klingt::{AudioNode, ProcessContext};
-/// use dasp_graph::{Buffer, Input};
+/// use klingt::{AudioNode, Buffer, Input, ProcessContext, Sample};
 ///
 /// enum MyMessage {
 ///     SetFrequency(f32),
@@ -49,6 +70,8 @@ pub struct NodeId(pub(crate) u32);
 ///
 /// impl AudioNode for MyOscillator {
 ///     type Message = MyMessage;
+///     type Event = ();
+///     type State = ();
 ///
 ///     fn process(
 ///         &mut self,
@@ -67,7 +90,7 @@ pub struct NodeId(pub(crate) u32);
 ///
 ///         // Generate audio
 ///         for sample in outputs[0].iter_mut() {
-///             *sample = (self.phase * std::f32::consts::TAU).sin() * self.volume;
+///             *sample = ((self.phase * std::f32::consts::TAU).sin() * self.volume) as Sample;
 ///             self.phase = (self.phase + self.frequency / ctx.sample_rate as f32) % 1.0;
 ///         }
 ///     }
@@ -78,15 +101,17 @@ pub struct NodeId(pub(crate) u32);
 ///
 /// # No Messages Needed?
 ///
-/// If your node doesn't need runtime parameter updates, use `()` as the message type:
+/// If your node doesn't need runtime parameter updates, use `()` as the message type.
+/// The same goes for the event and state types of nodes that don't report anything back:
 ///
 /// ```
-/// # use klingt::{AudioNode, ProcessContext};
-/// # use dasp_graph::{Buffer, Input};
+/// # use klingt::{AudioNode, Buffer, Input, ProcessContext};
 /// struct FixedTone { /* ... */ }
 ///
 /// impl AudioNode for FixedTone {
 ///     type Message = (); // No messages
+///     type Event = (); // No events
+///     type State = (); // No state
 ///     
 ///     fn process(
 ///         &mut self,
@@ -106,9 +131,34 @@ pub trait AudioNode: Send + 'static {
     /// Use a custom enum for nodes with parameters, or `()` for nodes without.
     type Message: Send + 'static;
 
+    /// Event type for reporting back to the controller.
+    ///
+    /// Use a custom enum for nodes that report something (reaching the end of a
+    /// sample, a level reading), or `()` for nodes that don't. Events are picked
+    /// up with [`Handle::poll_events`](crate::Handle::poll_events).
+    ///
+    /// Nodes written for klingt 0.3 and earlier don't have one yet: add
+    /// `type Event = ();` to them.
+    type Event: Send + 'static;
+
+    /// State type for reading the node's current state from the controller.
+    ///
+    /// Use a plain-data struct for nodes with state worth showing (a player's
+    /// position, a parameter's current value), or `()` for nodes without. Read
+    /// with [`Handle::state`](crate::Handle::state).
+    ///
+    /// It has to be `Copy`: publishing a snapshot overwrites an older one on the
+    /// audio thread, which mustn't free anything.
+    ///
+    /// Nodes written for klingt 0.3 and earlier don't have one yet: add
+    /// `type State = ();` to them.
+    type State: Copy + Send + 'static;
+
     /// Process one block of audio.
     ///
-    /// Called once per audio block (64 samples). Your implementation should:
+    /// Called once per audio block of [`ProcessContext::buffer_size`] samples, or
+    /// several times on consecutive parts of the block if timestamped messages are
+    /// due within it. Your implementation should:
     /// 1. Drain and handle all pending messages
     /// 2. Read from `inputs` (if any)
     /// 3. Write to `outputs`
@@ -133,6 +183,20 @@ pub trait AudioNode: Send + 'static {
     /// Number of audio output channels.
     fn num_outputs(&self) -> usize { 1 }
 
+    /// How the output channels are arranged.
+    ///
+    /// The default is the usual layout for [`num_outputs`](Self::num_outputs)
+    /// channels - see [`ChannelLayout::from_channels`].
+    fn output_layout(&self) -> ChannelLayout { ChannelLayout::from_channels(self.num_outputs()) }
+
+    /// How the node expects the channels of its inputs to be arranged, if it cares.
+    ///
+    /// Connections (not [`connect_ports`](crate::Klingt::connect_ports)) from a node
+    /// with another [`output_layout`](Self::output_layout) get a
+    /// [`ChannelMixer`](crate::nodes::ChannelMixer) put in between, which up- or
+    /// down-mixes to this layout. The default, `None`, takes whatever comes in.
+    fn input_layout(&self) -> Option<ChannelLayout> { None }
+
     /// Native sample rate of this node, if it has one.
     /// 
     /// Sources with fixed sample rates (e.g., sample players with pre-decoded
@@ -143,4 +207,28 @@ pub trait AudioNode: Send + 'static {
     /// [`Klingt`](crate::Klingt) will automatically create a sub-graph at the
     /// node's native rate with resampling to match the output.
     fn native_sample_rate(&self) -> Option<u32> { None }
+
+    /// Next event to report back to the controller, if any.
+    ///
+    /// Called after every [`process`](Self::process) call until it returns `None`,
+    /// so keep events that happen during processing until they're asked for.
+    /// Returning them is lock-free. The default reports nothing.
+    fn next_event(&mut self) -> Option<Self::Event> { None }
+
+    /// Snapshot of the node's current state, if it has one.
+    ///
+    /// Called when the node is added and after every [`process`](Self::process)
+    /// call; the latest snapshot is what [`Handle::state`](crate::Handle::state)
+    /// returns. Nodes returning `None` when added never publish a state.
+    /// Runs on the audio thread, so don't allocate. The default has no state.
+    fn state(&self) -> Option<Self::State> { None }
+
+    /// Whether the node has nothing to do until it gets a message.
+    ///
+    /// Checked before every block. An idle node isn't processed until a message
+    /// for it is due, and its outputs stay silent meanwhile - it doesn't see its
+    /// inputs either. Meant for nodes whose output only changes with messages,
+    /// like a paused player; not for oscillators or nodes following the
+    /// transport. The default is never idle.
+    fn is_idle(&self) -> bool { false }
 }
@@ -0,0 +1,77 @@
+//! Channel mixer effect - converts between channel layouts
+
+use alloc::vec::Vec;
+
+use crate::buffer::{Buffer, Input, Sample};
+use crate::layout::ChannelLayout;
+use crate::node::{AudioNode, ProcessContext};
+use crate::simd;
+
+/// Up- or down-mixes audio from one channel layout to another
+///
+/// Put between connected nodes whose layouts differ automatically (see
+/// [`AudioNode::input_layout`]), but it can be added by hand too, e.g. to fold a
+/// 5.1 mix down to stereo for a recording. The gains come from
+/// [`ChannelLayout::mix_matrix`].
+pub struct ChannelMixer {
+    from: ChannelLayout,
+    to: ChannelLayout,
+    /// A row of gains per output channel
+    matrix: Vec<Sample>,
+}
+
+impl ChannelMixer {
+    /// Create a mixer taking audio in the `from` layout and putting it out in `to`
+    pub fn new(from: ChannelLayout, to: ChannelLayout) -> Self {
+        Self { from, to, matrix: from.mix_matrix(to) }
+    }
+}
+
+impl AudioNode for ChannelMixer {
+    type Message = ();
+    type Event = ();
+    type State = ();
+
+    fn process(
+        &mut self,
+        _ctx: &ProcessContext,
+        _messages: impl Iterator<Item = Self::Message>,
+        inputs: &[Input],
+        outputs: &mut [Buffer],
+    ) {
+        let input = match inputs.first() {
+            Some(input) if !input.is_silent() => input,
+            _ => {
+                outputs.iter_mut().for_each(Buffer::silence);
+                return;
+            }
+        };
+
+        let rows = self.matrix.chunks(self.from.channels().max(1));
+        for (row, output) in rows.zip(outputs.iter_mut()) {
+            // Scale into the output with the first gain that counts, add the rest
+            let mut written = false;
+            for (&gain, buffer) in row.iter().zip(input.buffers()).filter(|&(&gain, _)| gain != 0.0) {
+                if written {
+                    simd::add_scaled(output, buffer, gain);
+                } else {
+                    simd::scale(output, buffer, gain);
+                    written = true;
+                }
+            }
+            if !written {
+                output.silence();
+            }
+        }
+    }
+
+    #[inline]
+    fn num_inputs(&self) -> usize { 1 }
+
+    #[inline]
+    fn num_outputs(&self) -> usize { self.to.channels() }
+
+    fn output_layout(&self) -> ChannelLayout { self.to }
+
+    fn input_layout(&self) -> Option<ChannelLayout> { Some(self.from) }
+}
@@ -1,7 +1,12 @@
 //! Gain/volume control effect
 
-use dasp_graph::{Buffer, Input};
+use crate::buffer::{Buffer, Input, OutputSample, Sample};
+use crate::layout::ChannelLayout;
 use crate::node::{AudioNode, ProcessContext};
+use crate::simd;
+
+/// Samples of smoothed gain worked out at a time, on the stack
+const RAMP_LEN: usize = 64;
 
 /// Messages to control gain
 #[derive(Clone, Copy, Debug)]
@@ -10,15 +15,28 @@ pub enum GainMessage {
     SetGain(f32),
 }
 
+/// Current level of a [`Gain`].
+///
+/// Read this via [`Handle::state`](crate::Handle::state).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GainState {
+    /// Target gain multiplier, as last set
+    pub gain: f32,
+    /// Gain currently applied, on its way to `gain` while smoothing
+    pub smoothed_gain: f32,
+}
+
 /// A gain (volume) control that passes audio through with amplitude scaling
 /// 
-/// Supports any number of channels - each input channel maps to corresponding output.
+/// Stereo by default, any number of channels with [`with_channels`](Self::with_channels).
+/// Inputs in another layout are mixed to it on the way in.
 pub struct Gain {
     gain: f32,
+    channels: usize,
     /// Smoothing to prevent clicks on rapid gain changes
-    smoothed_gain: f32,
+    smoothed_gain: Sample,
     /// Smoothing coefficient (0.0 = instant, 1.0 = no change)
-    smooth_coeff: f32,
+    smooth_coeff: Sample,
 }
 
 impl Gain {
@@ -26,7 +44,8 @@ impl Gain {
     pub fn new(gain: f32) -> Self {
         Self {
             gain,
-            smoothed_gain: gain,
+            channels: 2,
+            smoothed_gain: gain as Sample,
             smooth_coeff: 0.995, // ~7ms at 48kHz
         }
     }
@@ -34,11 +53,17 @@ impl Gain {
     /// Set the smoothing time in milliseconds
     pub fn with_smoothing_ms(mut self, ms: f32, sample_rate: u32) -> Self {
         // Time constant: after `ms` milliseconds, we've reached ~63% of target
-        let samples = (ms / 1000.0) * sample_rate as f32;
+        let samples = (ms as Sample / 1000.0) * sample_rate as Sample;
         self.smooth_coeff = (-1.0 / samples).exp();
         self
     }
 
+    /// Set the number of channels (builder pattern). At least one.
+    pub fn with_channels(mut self, channels: usize) -> Self {
+        self.channels = channels.max(1);
+        self
+    }
+
     /// Disable smoothing for instant gain changes
     pub fn without_smoothing(mut self) -> Self {
         self.smooth_coeff = 0.0;
@@ -53,6 +78,8 @@ impl Gain {
 
 impl AudioNode for Gain {
     type Message = GainMessage;
+    type Event = ();
+    type State = GainState;
 
     fn process(
         &mut self,
@@ -77,33 +104,52 @@ impl AudioNode for Gain {
 
         if in_buffers.is_empty() {
             // No input buffers - output silence
-            for buffer in outputs.iter_mut() {
-                buffer.iter_mut().for_each(|s| *s = 0.0);
-            }
+            outputs.iter_mut().for_each(Buffer::silence);
             return;
         }
 
         let smooth_coeff = self.smooth_coeff;
-        let target_gain = self.gain;
+        let target_gain = self.gain as Sample;
         let mut current_gain = self.smoothed_gain;
 
-        // Process each output channel
-        for (ch, out_buffer) in outputs.iter_mut().enumerate() {
-            // Get input for this channel, or last available channel
-            let in_buffer = in_buffers.get(ch).unwrap_or_else(|| in_buffers.last().unwrap());
+        // Silence in, silence out - the gain still moves toward its target
+        if input.is_silent() {
+            outputs.iter_mut().for_each(Buffer::silence);
+            if current_gain != target_gain {
+                for _ in 0..outputs[0].len() {
+                    current_gain = target_gain + smooth_coeff * (current_gain - target_gain);
+                }
+                self.smoothed_gain = current_gain;
+            }
+            return;
+        }
+
+        // Channels the input lacks, e.g. next to a single picked one, stay silent
+        let (outputs, missing) = outputs.split_at_mut(in_buffers.len().min(outputs.len()));
+        missing.iter_mut().for_each(Buffer::silence);
 
-            // Reset smoothed gain for each channel (they should track together)
-            let mut gain = current_gain;
+        // Settled: the same gain for every sample
+        if current_gain == target_gain {
+            for (out_buffer, in_buffer) in outputs.iter_mut().zip(in_buffers) {
+                simd::scale(out_buffer, in_buffer, target_gain);
+            }
+            return;
+        }
 
-            for (out_sample, &in_sample) in out_buffer.iter_mut().zip(in_buffer.iter()) {
+        // Otherwise work out the smoothed gain once for all channels, a slice at a time
+        let len = outputs[0].len();
+        let mut ramp = [0.0; RAMP_LEN];
+        for start in (0..len).step_by(RAMP_LEN) {
+            let end = (start + RAMP_LEN).min(len);
+            let ramp = &mut ramp[..end - start];
+            for gain in ramp.iter_mut() {
                 // Apply smoothing: gain moves toward target
-                gain = target_gain + smooth_coeff * (gain - target_gain);
-                *out_sample = in_sample * gain;
+                current_gain = target_gain + smooth_coeff * (current_gain - target_gain);
+                *gain = current_gain;
             }
 
-            // Only update the stored value once (from first channel)
-            if ch == 0 {
-                current_gain = gain;
+            for (out_buffer, in_buffer) in outputs.iter_mut().zip(in_buffers) {
+                simd::multiply(&mut out_buffer[start..end], &in_buffer[start..end], ramp);
             }
         }
 
@@ -114,5 +160,11 @@ impl AudioNode for Gain {
     fn num_inputs(&self) -> usize { 1 }
 
     #[inline]
-    fn num_outputs(&self) -> usize { 2 } // Stereo pass-through by default
+    fn num_outputs(&self) -> usize { self.channels }
+
+    fn input_layout(&self) -> Option<ChannelLayout> { Some(ChannelLayout::from_channels(self.channels)) }
+
+    fn state(&self) -> Option<GainState> {
+        Some(GainState { gain: self.gain, smoothed_gain: f32::from_sample(self.smoothed_gain) })
+    }
 }
@@ -1,21 +1,25 @@
 //! Mixer effect - sums multiple inputs together
 
-use dasp_graph::{Buffer, Input};
+use crate::buffer::{Buffer, Input};
+use crate::layout::ChannelLayout;
 use crate::node::{AudioNode, ProcessContext};
+use crate::simd;
 
 /// A mixer that sums multiple inputs together
 /// 
-/// Each input is summed with equal weight. The output has `channels` channels.
-/// If an input has fewer channels, it will be upmixed (mono→stereo copies to both).
-/// If an input has more channels, extra channels are ignored.
+/// Each input is summed with equal weight. The output has `channels` channels,
+/// in the usual layout for that many (see [`ChannelLayout::from_channels`]), and
+/// inputs in another layout are mixed to it on the way in. Channels are summed
+/// by index, so a single channel wired with
+/// [`connect_ports`](crate::Klingt::connect_ports) goes to the first output.
 pub struct Mixer {
     channels: usize,
 }
 
 impl Mixer {
-    /// Create a new mixer with the specified number of output channels
+    /// Create a new mixer with the specified number of output channels, at least one
     pub fn new(channels: usize) -> Self {
-        Self { channels }
+        Self { channels: channels.max(1) }
     }
     
     /// Create a stereo mixer
@@ -31,6 +35,8 @@ impl Mixer {
 
 impl AudioNode for Mixer {
     type Message = ();
+    type Event = ();
+    type State = ();
     
     fn process(
         &mut self,
@@ -40,26 +46,12 @@ impl AudioNode for Mixer {
         output: &mut [Buffer],
     ) {
         // Clear output buffers
-        for buf in output.iter_mut() {
-            buf.iter_mut().for_each(|s| *s = 0.0);
-        }
+        output.iter_mut().for_each(Buffer::silence);
         
-        // Sum all inputs
-        for input in inputs {
-            let input_channels = input.buffers().len();
-            
-            for (out_ch, out_buf) in output.iter_mut().enumerate() {
-                // Determine which input channel to read from
-                let in_ch = if input_channels == 1 {
-                    0 // Mono input: use channel 0 for all outputs
-                } else {
-                    out_ch.min(input_channels - 1)
-                };
-                
-                let in_buf = &input.buffers()[in_ch];
-                for (out_sample, in_sample) in out_buf.iter_mut().zip(in_buf.iter()) {
-                    *out_sample += *in_sample;
-                }
+        // Sum all inputs, skipping silent ones
+        for input in inputs.iter().filter(|input| !input.is_silent()) {
+            for (out_buf, in_buf) in output.iter_mut().zip(input.buffers()) {
+                simd::add(out_buf, in_buf);
             }
         }
     }
@@ -72,4 +64,8 @@ impl AudioNode for Mixer {
     fn num_outputs(&self) -> usize {
         self.channels
     }
+
+    fn input_layout(&self) -> Option<ChannelLayout> {
+        Some(ChannelLayout::from_channels(self.channels))
+    }
 }
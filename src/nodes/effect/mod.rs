@@ -1,9 +1,11 @@
 //! Audio effect nodes (processors with audio inputs and outputs)
 
+mod channel_mixer;
 mod gain;
 mod mixer;
 mod slew_limiter;
 
-pub use gain::{Gain, GainMessage};
+pub use channel_mixer::ChannelMixer;
+pub use gain::{Gain, GainMessage, GainState};
 pub use mixer::Mixer;
 pub use slew_limiter::{SlewLimiter, SlewLimiterMessage};
@@ -1,7 +1,10 @@
 //! Slew rate limiter effect
 
-use dasp_graph::{Buffer, Input};
+use alloc::vec::Vec;
+use crate::buffer::{Buffer, Input, Sample};
+use crate::layout::ChannelLayout;
 use crate::node::{AudioNode, ProcessContext};
+use crate::simd;
 
 /// Messages to control the slew limiter
 #[derive(Clone, Copy, Debug)]
@@ -18,11 +21,14 @@ pub enum SlewLimiterMessage {
 /// - Smoothing control signals
 /// - Creating portamento/glide effects
 /// - Reducing harsh transients
+///
+/// Stereo by default, any number of channels with [`with_channels`](Self::with_channels).
+/// Inputs in another layout are mixed to it on the way in.
 pub struct SlewLimiter {
     /// Maximum change per sample
     rate: f32,
-    /// Last output value per channel (up to 8 channels)
-    last: [f32; 8],
+    /// Last output value per channel
+    last: Vec<Sample>,
     /// Cached rate per second for recalculation
     rate_per_second: Option<f32>,
 }
@@ -35,7 +41,7 @@ impl SlewLimiter {
     pub fn new(rate: f32) -> Self {
         Self {
             rate: rate.abs(),
-            last: [0.0; 8],
+            last: alloc::vec![0.0; 2],
             rate_per_second: None,
         }
     }
@@ -47,11 +53,17 @@ impl SlewLimiter {
     pub fn from_rate_per_second(rate: f32) -> Self {
         Self {
             rate: 0.0, // Will be set on first process
-            last: [0.0; 8],
+            last: alloc::vec![0.0; 2],
             rate_per_second: Some(rate),
         }
     }
 
+    /// Set the number of channels (builder pattern). At least one.
+    pub fn with_channels(mut self, channels: usize) -> Self {
+        self.last = alloc::vec![0.0; channels.max(1)];
+        self
+    }
+
     #[inline]
     pub fn rate(&self) -> f32 {
         self.rate
@@ -60,6 +72,8 @@ impl SlewLimiter {
 
 impl AudioNode for SlewLimiter {
     type Message = SlewLimiterMessage;
+    type Event = ();
+    type State = ();
 
     fn process(
         &mut self,
@@ -95,17 +109,43 @@ impl AudioNode for SlewLimiter {
         let in_buffers = input.buffers();
 
         if in_buffers.is_empty() {
-            for buffer in outputs.iter_mut() {
-                buffer.iter_mut().for_each(|s| *s = 0.0);
-            }
+            outputs.iter_mut().for_each(Buffer::silence);
             return;
         }
 
-        let max_delta = self.rate;
+        let max_delta = self.rate as Sample;
+
+        let (outputs, missing) = outputs.split_at_mut(in_buffers.len().min(outputs.len()));
+        let split = outputs.len().min(self.last.len());
+        let (lasts, missing_lasts) = self.last.split_at_mut(split);
 
-        for (ch, out_buffer) in outputs.iter_mut().enumerate() {
-            let in_buffer = in_buffers.get(ch).unwrap_or_else(|| in_buffers.last().unwrap());
-            let mut last = self.last[ch.min(7)];
+        // Channels the input lacks, e.g. next to a single picked one, glide to zero
+        for (out_buffer, last_out) in missing.iter_mut().zip(missing_lasts.iter_mut()) {
+            if *last_out == 0.0 {
+                out_buffer.silence();
+                continue;
+            }
+            for out_sample in out_buffer.iter_mut() {
+                *last_out -= last_out.clamp(-max_delta, max_delta);
+                *out_sample = *last_out;
+            }
+        }
+
+        for ((out_buffer, last_out), in_buffer) in outputs.iter_mut().zip(lasts.iter_mut()).zip(in_buffers) {
+            let mut last = *last_out;
+
+            // Settled at zero with nothing coming in
+            if last == 0.0 && in_buffer.is_silent() {
+                out_buffer.silence();
+                continue;
+            }
+
+            // Never too fast: the output is the input. Off by rounding at most
+            if simd::max_step(last, in_buffer) <= max_delta {
+                out_buffer.copy_from_slice(in_buffer);
+                *last_out = in_buffer.last().copied().unwrap_or(last);
+                continue;
+            }
 
             for (out_sample, &in_sample) in out_buffer.iter_mut().zip(in_buffer.iter()) {
                 let delta = in_sample - last;
@@ -115,7 +155,7 @@ impl AudioNode for SlewLimiter {
                 *out_sample = last;
             }
 
-            self.last[ch.min(7)] = last;
+            *last_out = last;
         }
     }
 
@@ -123,5 +163,7 @@ impl AudioNode for SlewLimiter {
     fn num_inputs(&self) -> usize { 1 }
 
     #[inline]
-    fn num_outputs(&self) -> usize { 2 }
+    fn num_outputs(&self) -> usize { self.last.len() }
+
+    fn input_layout(&self) -> Option<ChannelLayout> { Some(ChannelLayout::from_channels(self.last.len())) }
 }
@@ -7,6 +7,7 @@
 //! Generate audio with no audio inputs:
 //! - [`Sine`] - Sine wave oscillator with frequency/amplitude control
 //! - [`SamplePlayer`] - Play pre-decoded audio samples
+//! - [`Metronome`] - Click track following the transport
 //! - [`ResamplingSource`] - Read from ring buffer with sample rate conversion (internal use)
 //!
 //! ## Effects ([`effect`])
@@ -15,31 +16,52 @@
 //! - [`Gain`] - Volume control with smoothing
 //! - [`Mixer`] - Sum multiple inputs together
 //! - [`SlewLimiter`] - Smooth rapid changes (for control signals)
+//! - [`ChannelMixer`] - Up- and down-mix between channel layouts
 //!
 //! ## Sinks ([`sink`])
 //!
 //! Consume audio with no audio outputs:
 //! - [`CpalSink`] - Output to system audio device (requires `cpal_sink` feature)
-//! - [`RtrbSink`] - Write to ring buffer (internal use for sub-graphs)
+//! - [`RtrbSink`] - Write to ring buffer (recording, bridges between sub-graphs)
+//! - [`NullSink`] - Discard audio (terminal for offline rendering)
 //!
 //! # Message Types
 //!
 //! Most nodes have associated message types for runtime parameter control:
 //! - [`SineMessage`] - Control [`Sine`] frequency and amplitude
 //! - [`PlayerMessage`] - Control [`SamplePlayer`] playback (play/pause/seek)
+//! - [`MetronomeMessage`] - Control [`Metronome`] volume
 //! - [`GainMessage`] - Control [`Gain`] level
 //! - [`SlewLimiterMessage`] - Control [`SlewLimiter`] rate
 //!
-//! Nodes without parameters (like [`Mixer`]) use `()` as their message type.
+//! Nodes without parameters (like [`Mixer`] and [`ChannelMixer`]) use `()` as their message type.
+//!
+//! # Event Types
+//!
+//! Some nodes report back to the controller, picked up with
+//! [`Handle::poll_events`](crate::Handle::poll_events):
+//! - [`PlayerEvent`] - [`SamplePlayer`] reached the end
+//!
+//! Nodes with nothing to report use `()` as their event type.
+//!
+//! # State Types
+//!
+//! Some nodes publish their current state, read with
+//! [`Handle::state`](crate::Handle::state):
+//! - [`SineState`] - [`Sine`] frequency and amplitude
+//! - [`PlayerState`] - [`SamplePlayer`] position and playback state
+//! - [`GainState`] - [`Gain`] level
+//!
+//! Nodes without state use `()` as their state type.
 
 pub mod source;
 pub mod effect;
 pub mod sink;
 
 // Re-export common types at the top level for convenience
-pub use source::{Sine, SineMessage, SamplePlayer, PlayerMessage, ResamplingSource, ResamplingSourceMessage};
-pub use effect::{Gain, GainMessage, Mixer, SlewLimiter, SlewLimiterMessage};
-pub use sink::RtrbSink;
+pub use source::{Sine, SineMessage, SineState, SamplePlayer, PlayerMessage, PlayerEvent, PlayerState, Metronome, MetronomeMessage, ResamplingSource, ResamplingSourceMessage};
+pub use effect::{ChannelMixer, Gain, GainMessage, GainState, Mixer, SlewLimiter, SlewLimiterMessage};
+pub use sink::{RtrbSink, NullSink};
 
 #[cfg(feature = "cpal_sink")]
 pub use sink::CpalSink;
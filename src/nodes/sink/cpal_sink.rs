@@ -2,12 +2,14 @@
 
 use cpal::traits::{DeviceTrait, StreamTrait};
 use cpal::{SampleFormat, SupportedStreamConfig};
-use dasp_graph::{Buffer, Input};
+use crate::buffer::{Buffer, Input};
 use rtrb::{Consumer, Producer, RingBuffer};
 use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
 
+use crate::layout::ChannelLayout;
 use crate::node::{AudioNode, ProcessContext};
+use crate::simd;
 
 /// A sink that outputs audio to a CPAL device
 ///
@@ -203,6 +205,8 @@ fn build_stream(
 
 impl AudioNode for CpalSink {
     type Message = (); // No control messages
+    type Event = ();
+    type State = ();
 
     fn process(
         &mut self,
@@ -222,24 +226,20 @@ impl AudioNode for CpalSink {
             return;
         }
 
-        let buffer_len = buffers[0].len();
-        let samples_needed = buffer_len * self.channels;
+        let samples_needed = buffers[0].len() * self.channels;
 
-        // Check for overrun (generating faster than consuming)
-        if self.buffer.slots() < samples_needed {
-            // Skip this block rather than partially write
-            return;
-        }
+        // Skip this block if it doesn't fit, rather than partially write
+        let mut chunk = match self.buffer.write_chunk(samples_needed) {
+            Ok(chunk) => chunk,
+            Err(_) => return,
+        };
 
-        // Interleave channels into ring buffer
-        for i in 0..buffer_len {
-            for ch in 0..self.channels {
-                // Map output channel to source (duplicate mono to stereo if needed)
-                let src_ch = ch.min(buffers.len() - 1);
-                // Safety: we verified slots above
-                let _ = self.buffer.push(buffers[src_ch][i]);
-            }
-        }
+        // Interleave channels, leaving any the input lacks silent
+        let (first, second) = chunk.as_mut_slices();
+        let first_len = first.len();
+        simd::interleave(buffers, self.channels, 0, first);
+        simd::interleave(buffers, self.channels, first_len, second);
+        chunk.commit_all();
     }
 
     #[inline]
@@ -247,4 +247,6 @@ impl AudioNode for CpalSink {
 
     #[inline]
     fn num_outputs(&self) -> usize { 0 }
+
+    fn input_layout(&self) -> Option<ChannelLayout> { Some(ChannelLayout::from_channels(self.channels)) }
 }
@@ -1,11 +1,13 @@
 //! Audio sink nodes (outputs with no audio outputs)
 
 mod rtrb_sink;
+mod null_sink;
 
 #[cfg(feature = "cpal_sink")]
 mod cpal_sink;
 
 pub use rtrb_sink::RtrbSink;
+pub use null_sink::NullSink;
 
 #[cfg(feature = "cpal_sink")]
 pub use cpal_sink::CpalSink;
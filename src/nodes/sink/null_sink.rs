@@ -0,0 +1,50 @@
+//! Sink that discards its input
+
+use crate::buffer::{Buffer, Input};
+
+use crate::node::{AudioNode, ProcessContext};
+
+/// A sink that accepts audio and throws it away
+///
+/// Used as the terminal of engines that aren't attached to a device, e.g.
+/// [`Klingt::offline`](crate::Klingt::offline). The audio reaching it can still
+/// be collected with [`Klingt::render`](crate::Klingt::render).
+pub struct NullSink {
+    channels: usize,
+}
+
+impl NullSink {
+    /// Create a sink for the given number of channels
+    pub fn new(channels: usize) -> Self {
+        Self {
+            channels: channels.max(1),
+        }
+    }
+
+    /// Number of channels this sink accepts
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+}
+
+impl AudioNode for NullSink {
+    type Message = (); // No control messages
+    type Event = ();
+    type State = ();
+
+    fn process(
+        &mut self,
+        _ctx: &ProcessContext,
+        _messages: impl Iterator<Item = ()>,
+        _inputs: &[Input],
+        _outputs: &mut [Buffer],
+    ) {
+    }
+
+    #[inline]
+    fn num_inputs(&self) -> usize { 1 }
+
+    #[inline]
+    fn num_outputs(&self) -> usize { 0 }
+}
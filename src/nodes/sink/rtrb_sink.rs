@@ -1,9 +1,14 @@
 //! Ring buffer sink for custom audio processing
 
-use dasp_graph::{Buffer, Input};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::buffer::{Buffer, Input, OutputSample};
 use rtrb::Producer;
 
+use crate::layout::ChannelLayout;
 use crate::node::{AudioNode, ProcessContext};
+use crate::simd;
 
 /// A sink that pushes audio into an rtrb ring buffer
 /// 
@@ -11,27 +16,39 @@ use crate::node::{AudioNode, ProcessContext};
 /// - Custom audio processing pipelines
 /// - Sending audio to another thread
 /// - Recording/analysis
-pub struct RtrbSink {
-    producer: Producer<f32>,
+///
+/// Samples are converted to the ring buffer's type: `f32` by default, or `f64`
+/// to keep the full precision of the `f64` feature.
+pub struct RtrbSink<T: OutputSample = f32> {
+    producer: Producer<T>,
     channels: usize,
+    /// Frame of the first sample written, plus one, for a resampler reading on
+    start_frame: Option<Arc<AtomicU64>>,
 }
 
-impl RtrbSink {
+impl<T: OutputSample> RtrbSink<T> {
     /// Create a sink that writes interleaved samples to the given producer
-    pub fn new(producer: Producer<f32>, channels: usize) -> Self {
+    pub fn new(producer: Producer<T>, channels: usize) -> Self {
         Self {
             producer,
             channels: channels.max(1),
+            start_frame: None,
         }
     }
 
+    /// Publish the frame of the first sample written, plus one, to `start_frame`
+    pub(crate) fn with_start_frame(mut self, start_frame: Arc<AtomicU64>) -> Self {
+        self.start_frame = Some(start_frame);
+        self
+    }
+
     /// Create a sink for mono audio
-    pub fn mono(producer: Producer<f32>) -> Self {
+    pub fn mono(producer: Producer<T>) -> Self {
         Self::new(producer, 1)
     }
 
     /// Create a sink for stereo audio
-    pub fn stereo(producer: Producer<f32>) -> Self {
+    pub fn stereo(producer: Producer<T>) -> Self {
         Self::new(producer, 2)
     }
 
@@ -42,12 +59,14 @@ impl RtrbSink {
     }
 }
 
-impl AudioNode for RtrbSink {
+impl<T: OutputSample> AudioNode for RtrbSink<T> {
     type Message = (); // No control messages
+    type Event = ();
+    type State = ();
 
     fn process(
         &mut self,
-        _ctx: &ProcessContext,
+        ctx: &ProcessContext,
         _messages: impl Iterator<Item = ()>,
         inputs: &[Input],
         _outputs: &mut [Buffer],
@@ -63,21 +82,26 @@ impl AudioNode for RtrbSink {
             return;
         }
 
-        let buffer_len = buffers[0].len();
-        let samples_needed = buffer_len * self.channels;
-
-        // Skip if buffer is full
-        if self.producer.slots() < samples_needed {
-            return;
-        }
-
-        // Interleave channels
-        for i in 0..buffer_len {
-            for ch in 0..self.channels {
-                let src_ch = ch.min(buffers.len() - 1);
-                let _ = self.producer.push(buffers[src_ch][i]);
+        let samples_needed = buffers[0].len() * self.channels;
+
+        // Skip this block if it doesn't fit, rather than partially write
+        let mut chunk = match self.producer.write_chunk(samples_needed) {
+            Ok(chunk) => chunk,
+            Err(_) => return,
+        };
+
+        // Interleave channels, leaving any the input lacks silent
+        let (first, second) = chunk.as_mut_slices();
+        let first_len = first.len();
+        simd::interleave(buffers, self.channels, 0, first);
+        simd::interleave(buffers, self.channels, first_len, second);
+        // Committing publishes it along with the samples
+        if let Some(start) = &self.start_frame {
+            if start.load(Ordering::Relaxed) == 0 {
+                start.store(ctx.frame + 1, Ordering::Relaxed);
             }
         }
+        chunk.commit_all();
     }
 
     #[inline]
@@ -85,4 +109,6 @@ impl AudioNode for RtrbSink {
 
     #[inline]
     fn num_outputs(&self) -> usize { 0 }
+
+    fn input_layout(&self) -> Option<ChannelLayout> { Some(ChannelLayout::from_channels(self.channels)) }
 }
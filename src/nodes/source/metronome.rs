@@ -0,0 +1,121 @@
+//! Click track driven by the transport.
+
+use crate::buffer::{Buffer, Input, Sample};
+use crate::node::{AudioNode, ProcessContext};
+
+/// Pitch of the click on the first beat of a bar
+const ACCENT_FREQUENCY: Sample = 1760.0;
+/// Pitch of the other clicks
+const BEAT_FREQUENCY: Sample = 880.0;
+/// How fast a click fades out
+const DECAY_SECONDS: Sample = 0.01;
+
+const TAU: Sample = core::f64::consts::TAU as Sample;
+
+/// Messages to control a [`Metronome`].
+#[derive(Clone, Copy, Debug)]
+pub enum MetronomeMessage {
+    /// Set the volume (0.0 to 1.0).
+    SetVolume(f32),
+}
+
+/// A click on every beat of the transport (mono source).
+///
+/// Follows the engine's transport (see [`Klingt::start_transport`](crate::Klingt::start_transport)):
+/// it clicks on every beat of the time signature while the transport plays,
+/// higher on the first beat of each bar, and stays silent while it's stopped.
+/// Clicks land on the sample nearest to the beat.
+///
+/// # Example
+///
+/// ```
+/// # use klingt::{Klingt, nodes::Metronome};
+/// let mut klingt = Klingt::offline(48000);
+/// let click = klingt.add(Metronome::new());
+/// klingt.output(&click);
+///
+/// klingt.set_tempo(120.0);
+/// klingt.start_transport();
+///
+/// // Two clicks per second
+/// let samples = klingt.render(48000);
+/// ```
+pub struct Metronome {
+    volume: f32,
+    frequency: Sample,
+    phase: Sample,
+    envelope: Sample,
+}
+
+impl Metronome {
+    /// Create a metronome at volume 0.5.
+    pub fn new() -> Self {
+        Self {
+            volume: 0.5,
+            frequency: BEAT_FREQUENCY,
+            phase: 0.0,
+            envelope: 0.0,
+        }
+    }
+
+    /// Set the initial volume (builder pattern).
+    ///
+    /// Volume is clamped to 0.0 - 1.0.
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume.clamp(0.0, 1.0);
+        self
+    }
+}
+
+impl Default for Metronome {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioNode for Metronome {
+    type Message = MetronomeMessage;
+    type Event = ();
+    type State = ();
+
+    fn process(
+        &mut self,
+        ctx: &ProcessContext,
+        messages: impl Iterator<Item = MetronomeMessage>,
+        _inputs: &[Input],
+        outputs: &mut [Buffer],
+    ) {
+        for msg in messages {
+            match msg {
+                MetronomeMessage::SetVolume(v) => self.volume = v.clamp(0.0, 1.0),
+            }
+        }
+
+        let transport = &ctx.transport;
+        // Count in beats of the time signature, not quarter notes
+        let units_per_beat = transport.time_signature.denominator as f64 / 4.0;
+        let units_per_frame = transport.beats_per_frame * units_per_beat;
+        let decay = (-1.0 / (DECAY_SECONDS * ctx.sample_rate as Sample)).exp();
+
+        let volume = self.volume as Sample;
+        for (i, sample) in outputs[0].iter_mut().enumerate() {
+            if transport.playing {
+                // Click on the sample nearest to each beat
+                let units = transport.beat_at(i) * units_per_beat;
+                let (early, late) = (units - units_per_frame / 2.0, units + units_per_frame / 2.0);
+                if late.floor() > early.floor() {
+                    let accent = late.floor().rem_euclid(transport.time_signature.numerator as f64) == 0.0;
+                    self.frequency = if accent { ACCENT_FREQUENCY } else { BEAT_FREQUENCY };
+                    self.phase = 0.0;
+                    self.envelope = 1.0;
+                }
+            }
+
+            *sample = (self.phase * TAU).cos() * self.envelope * volume;
+            self.phase = (self.phase + self.frequency / ctx.sample_rate as Sample) % 1.0;
+            self.envelope *= decay;
+        }
+    }
+
+    fn num_outputs(&self) -> usize { 1 }
+}
@@ -7,12 +7,15 @@
 //!
 //! - [`Sine`] - Sine wave oscillator
 //! - [`SamplePlayer`] - Play pre-decoded audio samples
+//! - [`Metronome`] - Click on every beat of the transport
 //! - [`ResamplingSource`] - Internal node for sample rate conversion
 
 mod sine;
 mod player;
+mod metronome;
 mod resampling_source;
 
-pub use sine::{Sine, SineMessage};
-pub use player::{SamplePlayer, PlayerMessage};
+pub use sine::{Sine, SineMessage, SineState};
+pub use player::{SamplePlayer, PlayerMessage, PlayerEvent, PlayerState};
+pub use metronome::{Metronome, MetronomeMessage};
 pub use resampling_source::{ResamplingSource, ResamplingSourceMessage};
@@ -1,7 +1,7 @@
 //! Audio sample player.
 
 use alloc::vec::Vec;
-use dasp_graph::{Buffer, Input};
+use crate::buffer::{Buffer, Input, Sample};
 use crate::node::{AudioNode, ProcessContext};
 
 /// Messages to control a [`SamplePlayer`].
@@ -23,6 +23,32 @@ pub enum PlayerMessage {
     SetLooping(bool),
 }
 
+/// Events reported by a [`SamplePlayer`].
+///
+/// Pick these up via [`Handle::poll_events`](crate::Handle::poll_events).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayerEvent {
+    /// Playback reached the end and stopped (never sent while looping).
+    Finished,
+}
+
+/// Current playback state of a [`SamplePlayer`].
+///
+/// Read this via [`Handle::state`](crate::Handle::state), e.g. for a progress bar.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayerState {
+    /// Playback position in seconds
+    pub position_secs: f64,
+    /// Total duration in seconds
+    pub duration_secs: f64,
+    /// Whether playback is active
+    pub playing: bool,
+    /// Playback volume (0.0 to 2.0)
+    pub volume: f32,
+    /// Whether playback restarts at the end
+    pub looping: bool,
+}
+
 /// Plays pre-decoded audio samples.
 ///
 /// This node plays audio that's already been decoded into memory as f32 samples.
@@ -61,6 +87,8 @@ pub struct SamplePlayer {
     playing: bool,
     volume: f32,
     looping: bool,
+    /// Reached the end since the last event was taken
+    finished: bool,
 }
 
 impl SamplePlayer {
@@ -82,6 +110,7 @@ impl SamplePlayer {
             playing: true,
             volume: 1.0,
             looping: false,
+            finished: false,
         }
     }
 
@@ -125,6 +154,8 @@ impl SamplePlayer {
 
 impl AudioNode for SamplePlayer {
     type Message = PlayerMessage;
+    type Event = PlayerEvent;
+    type State = PlayerState;
 
     fn process(
         &mut self,
@@ -160,13 +191,11 @@ impl AudioNode for SamplePlayer {
 
         // Fast path: not playing - output silence
         if !self.playing {
-            for buffer in outputs.iter_mut() {
-                buffer.iter_mut().for_each(|s| *s = 0.0);
-            }
+            outputs.iter_mut().for_each(Buffer::silence);
             return;
         }
 
-        let volume = self.volume;
+        let volume = self.volume as Sample;
         let src_channels = self.channels;
         let total_samples = self.samples.len();
 
@@ -183,6 +212,7 @@ impl AudioNode for SamplePlayer {
                         }
                     }
                     self.playing = false;
+                    self.finished = true;
                     return;
                 }
             }
@@ -195,7 +225,7 @@ impl AudioNode for SamplePlayer {
 
                 buffer[i] = if sample_idx < total_samples {
                     // Safety: we checked bounds above
-                    unsafe { *self.samples.get_unchecked(sample_idx) * volume }
+                    unsafe { *self.samples.get_unchecked(sample_idx) as Sample * volume }
                 } else {
                     0.0
                 };
@@ -218,4 +248,27 @@ impl AudioNode for SamplePlayer {
     fn native_sample_rate(&self) -> Option<u32> {
         Some(self.sample_rate)
     }
+
+    fn state(&self) -> Option<PlayerState> {
+        Some(PlayerState {
+            position_secs: self.position_secs(),
+            duration_secs: self.duration_secs(),
+            playing: self.playing,
+            volume: self.volume,
+            looping: self.looping,
+        })
+    }
+
+    /// Paused or stopped players sleep until they get a message
+    fn is_idle(&self) -> bool {
+        !self.playing
+    }
+
+    fn next_event(&mut self) -> Option<PlayerEvent> {
+        if core::mem::take(&mut self.finished) {
+            Some(PlayerEvent::Finished)
+        } else {
+            None
+        }
+    }
 }
@@ -3,7 +3,10 @@
 //! Consumes audio from a ring buffer at one sample rate and outputs
 //! at the graph's sample rate. Used to bridge graphs at different rates.
 
-use dasp_graph::{Buffer, Input};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::buffer::{Buffer, Input, Sample};
 use rtrb::Consumer;
 
 use crate::node::{AudioNode, ProcessContext};
@@ -13,6 +16,9 @@ use crate::node::{AudioNode, ProcessContext};
 pub enum ResamplingSourceMessage {
     /// Set the input sample rate (if it changes dynamically)
     SetInputRate(u32),
+    /// Output this many frames of silence before reading on, giving the
+    /// producer time to get ahead
+    Delay(usize),
 }
 
 /// A source that reads from a ring buffer and resamples to the graph's sample rate
@@ -20,20 +26,28 @@ pub enum ResamplingSourceMessage {
 /// Uses linear interpolation for simplicity. For higher quality, consider
 /// using rubato or a sinc interpolator.
 pub struct ResamplingSource {
-    consumer: Consumer<f32>,
+    consumer: Consumer<Sample>,
     channels: usize,
     input_sample_rate: u32,
     
     /// Fractional position in the input stream
     position: f64,
     
-    /// Buffer of recent input samples for interpolation (per channel)
-    /// We keep 2 samples per channel for linear interp
-    prev_samples: [f32; 16], // up to 8 channels * 2 samples
-    curr_samples: [f32; 16],
+    /// The two most recent input frames, one sample per channel, to interpolate between
+    prev_samples: Vec<Sample>,
+    curr_samples: Vec<Sample>,
     
     /// Whether we've received any samples yet
     primed: bool,
+
+    /// Frames of silence left to output before reading from the ring buffer
+    delay: usize,
+
+    /// The producer's frame of its first sample, plus one, once it's written
+    /// (see [`RtrbSink`](crate::nodes::RtrbSink))
+    producer_start: Option<Arc<AtomicU64>>,
+    /// Whether the first sample has been lined up with the producer's time
+    aligned: bool,
 }
 
 impl ResamplingSource {
@@ -42,40 +56,56 @@ impl ResamplingSource {
     /// - `consumer`: Ring buffer consumer with interleaved samples at `input_sample_rate`
     /// - `channels`: Number of audio channels
     /// - `input_sample_rate`: Sample rate of the incoming audio
-    pub fn new(consumer: Consumer<f32>, channels: usize, input_sample_rate: u32) -> Self {
+    pub fn new(consumer: Consumer<Sample>, channels: usize, input_sample_rate: u32) -> Self {
+        let channels = channels.max(1);
         Self {
             consumer,
-            channels: channels.min(8),
+            channels,
             input_sample_rate,
             position: 0.0,
-            prev_samples: [0.0; 16],
-            curr_samples: [0.0; 16],
+            prev_samples: alloc::vec![0.0; channels],
+            curr_samples: alloc::vec![0.0; channels],
             primed: false,
+            delay: 0,
+            producer_start: None,
+            aligned: false,
         }
     }
 
+    /// Play the producer's first sample at the same time it was made, reading
+    /// its frame from `producer_start`
+    ///
+    /// A producer running ahead starts writing at a later time than the one
+    /// reading is at. Without this, everything it makes would play early.
+    pub(crate) fn with_producer_start(mut self, producer_start: Arc<AtomicU64>) -> Self {
+        self.producer_start = Some(producer_start);
+        self
+    }
+
     /// Read one frame (all channels) from the ring buffer
-    /// Returns true if successful
+    /// Returns true if successful, and reads nothing on an underrun
     fn read_frame(&mut self) -> bool {
-        for ch in 0..self.channels {
-            match self.consumer.pop() {
-                Ok(sample) => self.curr_samples[ch] = sample,
-                Err(_) => return false, // underrun
-            }
-        }
+        let chunk = match self.consumer.read_chunk(self.channels) {
+            Ok(chunk) => chunk,
+            Err(_) => return false,
+        };
+        let (first, second) = chunk.as_slices();
+        self.curr_samples[..first.len()].copy_from_slice(first);
+        self.curr_samples[first.len()..].copy_from_slice(second);
+        chunk.commit_all();
         true
     }
 
     /// Advance to next frame, shifting current to previous
     fn advance_frame(&mut self) {
-        for ch in 0..self.channels {
-            self.prev_samples[ch] = self.curr_samples[ch];
-        }
+        self.prev_samples.copy_from_slice(&self.curr_samples);
     }
 }
 
 impl AudioNode for ResamplingSource {
     type Message = ResamplingSourceMessage;
+    type Event = ();
+    type State = ();
 
     fn process(
         &mut self,
@@ -90,6 +120,9 @@ impl AudioNode for ResamplingSource {
                 ResamplingSourceMessage::SetInputRate(rate) => {
                     self.input_sample_rate = rate;
                 }
+                ResamplingSourceMessage::Delay(frames) => {
+                    self.delay += frames;
+                }
             }
         }
 
@@ -97,11 +130,40 @@ impl AudioNode for ResamplingSource {
             return;
         }
 
+        let buffer_len = outputs[0].len();
+
+        // Hold the first sample back until it's due
+        if !self.aligned {
+            match self.producer_start.as_ref().map(|start| start.load(Ordering::Acquire)) {
+                // Nothing written yet
+                Some(0) => {
+                    self.delay = self.delay.saturating_sub(buffer_len);
+                    outputs.iter_mut().for_each(|b| b.silence());
+                    return;
+                }
+                Some(start) => {
+                    let due = ((start - 1) as f64 * ctx.sample_rate as f64 / self.input_sample_rate as f64).round() as u64;
+                    self.delay += due.saturating_sub(ctx.frame + self.delay as u64) as usize;
+                }
+                None => {}
+            }
+            self.aligned = true;
+        }
+
+        // Delay covers the whole block - nothing to read yet
+        if self.delay >= buffer_len {
+            self.delay -= buffer_len;
+            outputs.iter_mut().for_each(|b| b.silence());
+            return;
+        }
+        let start = core::mem::take(&mut self.delay);
+        for buffer in outputs.iter_mut() {
+            buffer[..start].iter_mut().for_each(|s| *s = 0.0);
+        }
+
         let output_rate = ctx.sample_rate as f64;
         let input_rate = self.input_sample_rate as f64;
         let rate_ratio = input_rate / output_rate; // e.g., 48000/44100 ≈ 1.088
-        
-        let buffer_len = outputs[0].len();
 
         // Prime the interpolator if needed
         if !self.primed && self.read_frame() {
@@ -111,7 +173,7 @@ impl AudioNode for ResamplingSource {
             }
         }
 
-        for i in 0..buffer_len {
+        for i in start..buffer_len {
             // Check if we need to advance to next input frame
             while self.position >= 1.0 {
                 self.position -= 1.0;
@@ -128,7 +190,7 @@ impl AudioNode for ResamplingSource {
             }
 
             // Linear interpolation between prev and curr samples
-            let t = self.position as f32;
+            let t = self.position as Sample;
             
             for (ch, buffer) in outputs.iter_mut().enumerate() {
                 let ch_idx = ch % self.channels;
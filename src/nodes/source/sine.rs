@@ -1,8 +1,10 @@
 //! Sine wave oscillator.
 
-use dasp_graph::{Buffer, Input};
+use crate::buffer::{Buffer, Input, Sample};
 use crate::node::{AudioNode, ProcessContext};
 
+const TAU: Sample = core::f64::consts::TAU as Sample;
+
 /// Messages to control a [`Sine`] oscillator.
 ///
 /// Send these via [`Handle::send`](crate::Handle::send) to change parameters at runtime.
@@ -14,6 +16,17 @@ pub enum SineMessage {
     SetAmplitude(f32),
 }
 
+/// Current parameters of a [`Sine`] oscillator.
+///
+/// Read these via [`Handle::state`](crate::Handle::state).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SineState {
+    /// Frequency in Hz
+    pub frequency: f32,
+    /// Amplitude (0.0 to 1.0)
+    pub amplitude: f32,
+}
+
 /// A sine wave oscillator (mono source).
 ///
 /// Generates a pure sine tone at the specified frequency. Default amplitude is 0.25
@@ -34,7 +47,7 @@ pub enum SineMessage {
 /// ```
 pub struct Sine {
     frequency: f32,
-    phase: f32,
+    phase: Sample,
     amplitude: f32,
 }
 
@@ -73,6 +86,8 @@ impl Sine {
 
 impl AudioNode for Sine {
     type Message = SineMessage;
+    type Event = ();
+    type State = SineState;
 
     fn process(
         &mut self,
@@ -93,20 +108,20 @@ impl AudioNode for Sine {
             return;
         }
 
-        let phase_inc = self.frequency / ctx.sample_rate as f32;
+        let phase_inc = self.frequency as Sample / ctx.sample_rate as Sample;
         let buffer_len = outputs[0].len();
-        let amplitude = self.amplitude;
+        let amplitude = self.amplitude as Sample;
 
         // Generate samples - write to first buffer, then copy to others
         let (first, rest) = outputs.split_first_mut().unwrap();
         
         for i in 0..buffer_len {
-            let sample = (self.phase * core::f32::consts::TAU).sin() * amplitude;
+            let sample = (self.phase * TAU).sin() * amplitude;
             first[i] = sample;
 
             self.phase += phase_inc;
             // Branchless phase wrap (phase is always positive)
-            self.phase -= (self.phase >= 1.0) as u32 as f32;
+            self.phase -= (self.phase >= 1.0) as u32 as Sample;
         }
 
         // Copy to remaining output channels (if any)
@@ -120,4 +135,8 @@ impl AudioNode for Sine {
     
     #[inline]
     fn num_outputs(&self) -> usize { 1 }
+
+    fn state(&self) -> Option<SineState> {
+        Some(SineState { frequency: self.frequency, amplitude: self.amplitude })
+    }
 }
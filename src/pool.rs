@@ -0,0 +1,178 @@
+//! Worker threads for processing independent parts of a graph in parallel
+//!
+//! The thread calling [`WorkerPool::run`] takes part in every job, so a pool of
+//! `n` threads only spawns `n - 1`. Idle workers spin and yield for a little
+//! while before parking, and handing out a job is an atomic increment plus an
+//! unpark per worker - no locks and no allocation. Only a panicking worker
+//! takes a lock, to hand its panic back.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+
+/// How often a waiting thread checks before it starts yielding its time slice
+const SPINS_BEFORE_YIELDING: usize = 64;
+/// How often an idle worker yields before it parks
+const YIELDS_BEFORE_PARKING: usize = 256;
+
+/// A job with its lifetime erased. Only dereferenced while [`WorkerPool::run`] waits for it.
+type Job = *const (dyn Fn(usize) + Sync);
+
+struct Shared {
+    /// Bumped for every job, which is how workers notice there's a new one
+    epoch: AtomicUsize,
+    /// Workers that haven't finished the current job yet
+    busy: AtomicUsize,
+    /// Only written while no worker is busy
+    job: UnsafeCell<Option<Job>>,
+    /// The first panic of a worker during the current job
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+    /// Set along with `panic`, so `run` only locks it when a worker panicked
+    panicked: AtomicBool,
+    stop: AtomicBool,
+}
+
+// The job is only shared while `run` keeps it alive, and it's `Sync`
+unsafe impl Send for Shared {}
+unsafe impl Sync for Shared {}
+
+/// Threads that run jobs together with the thread that hands them out
+pub(crate) struct WorkerPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    /// Spawn `threads - 1` workers
+    pub fn new(threads: usize) -> Self {
+        let shared = Arc::new(Shared {
+            epoch: AtomicUsize::new(0),
+            busy: AtomicUsize::new(0),
+            job: UnsafeCell::new(None),
+            panic: Mutex::new(None),
+            panicked: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+        });
+
+        let workers = (1..threads.max(1))
+            .map(|index| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("klingt-worker-{}", index))
+                    .spawn(move || work(&shared, index))
+                    .expect("Failed to spawn worker thread")
+            })
+            .collect();
+
+        Self { shared, workers }
+    }
+
+    /// Threads a job runs on, including the caller
+    pub fn threads(&self) -> usize {
+        self.workers.len() + 1
+    }
+
+    /// Run `job` on every thread at once, passing each its index (0 for the caller)
+    ///
+    /// Returns once every thread is done with it. A panic on a worker is raised
+    /// again here.
+    pub fn run(&self, job: &(dyn Fn(usize) + Sync)) {
+        // Safety: no worker is busy, and they're done with the job before we return
+        unsafe { *self.shared.job.get() = Some(core::mem::transmute::<_, Job>(job)) };
+        self.shared.busy.store(self.workers.len(), Ordering::Relaxed);
+        self.shared.epoch.fetch_add(1, Ordering::Release);
+        for worker in &self.workers {
+            worker.thread().unpark();
+        }
+
+        {
+            // Workers keep using the job even if it panics on this thread
+            let _wait = WaitForWorkers(&self.shared);
+            job(0);
+        }
+
+        if self.shared.panicked.swap(false, Ordering::Acquire) {
+            let panic = self.shared.panic.lock().unwrap_or_else(|e| e.into_inner()).take();
+            if let Some(panic) = panic {
+                panic::resume_unwind(panic);
+            }
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        for worker in self.workers.drain(..) {
+            worker.thread().unpark();
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Waits until every worker is done with the current job when dropped
+struct WaitForWorkers<'a>(&'a Shared);
+
+impl Drop for WaitForWorkers<'_> {
+    fn drop(&mut self) {
+        wait_until(|| self.0.busy.load(Ordering::Acquire) == 0);
+    }
+}
+
+/// Wait for another thread to make `done` true
+///
+/// Spins for a bit, then yields, so threads that outnumber the cores still
+/// get to finish what's being waited for.
+pub(crate) fn wait_until(done: impl Fn() -> bool) {
+    let mut spins = 0;
+    while !done() {
+        if spins < SPINS_BEFORE_YIELDING {
+            spins += 1;
+            core::hint::spin_loop();
+        } else {
+            thread::yield_now();
+        }
+    }
+}
+
+/// Main loop of a worker thread
+fn work(shared: &Shared, index: usize) {
+    let mut epoch = 0;
+
+    loop {
+        let mut waited = 0;
+        loop {
+            if shared.stop.load(Ordering::Acquire) {
+                return;
+            }
+            let current = shared.epoch.load(Ordering::Acquire);
+            if current != epoch {
+                epoch = current;
+                break;
+            }
+            if waited < SPINS_BEFORE_YIELDING {
+                core::hint::spin_loop();
+            } else if waited < SPINS_BEFORE_YIELDING + YIELDS_BEFORE_PARKING {
+                thread::yield_now();
+            } else {
+                thread::park();
+            }
+            waited += 1;
+        }
+
+        // Safety: set before the epoch was bumped, and alive until `busy` drops to 0
+        let job = unsafe { &*(*shared.job.get()).expect("Worker woke up without a job") };
+        if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| job(index))) {
+            shared.panic.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert(panic);
+            shared.panicked.store(true, Ordering::Release);
+        }
+        shared.busy.fetch_sub(1, Ordering::Release);
+    }
+}
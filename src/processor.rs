@@ -0,0 +1,338 @@
+//! Audio-processing half of the engine
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use hashbrown::HashMap;
+use rtrb::{Consumer, Producer};
+
+use crate::command::{Command, Garbage, Scheduled};
+use crate::graph::{AudioGraph, Timeline, DEFAULT_BLOCK_SIZE};
+#[cfg(feature = "std")]
+use crate::pool::WorkerPool;
+use crate::transport::Transport;
+
+/// How many blocks graphs that feed the output run ahead of it
+pub(crate) const LEAD_BLOCKS: u64 = 4;
+
+/// Threads to spread graphs over, if there's more than one
+#[cfg(feature = "std")]
+type Workers = Option<WorkerPool>;
+/// Without `std` everything runs on the thread calling `process`
+#[cfg(not(feature = "std"))]
+type Workers = ();
+
+/// Process one block of `graph`, spread over the worker threads if there are any
+fn process_graph(graph: &mut AudioGraph, timeline: Timeline, transport: &Transport, workers: &Workers) {
+    #[cfg(feature = "std")]
+    if let Some(pool) = workers {
+        return graph.process_parallel(timeline, transport, pool);
+    }
+    let _ = workers;
+    graph.process(timeline, transport);
+}
+
+/// Processor-side state of a sub-graph that needs resampling
+struct SubGraph {
+    /// Stays boxed as sent by the controller, so removal doesn't allocate
+    graph: Box<AudioGraph>,
+    /// How many blocks we've processed
+    blocks_processed: u64,
+    /// Main graph blocks processed before this sub-graph was added
+    start_block: u64,
+    /// The sub-graph's frame at the time it was added
+    start_frame: u64,
+}
+
+/// The audio-processing half of a split [`Klingt`](crate::Klingt).
+///
+/// Owns every node and generates the audio. It is `Send`, so it can be moved
+/// into an audio callback or a dedicated thread, while the matching
+/// [`KlingtController`](crate::KlingtController) edits the graph from elsewhere.
+/// Structural changes arrive over a lock-free queue and are applied at the
+/// start of the next block, so processing never waits on a lock.
+///
+/// # Allocation
+///
+/// Processing a block doesn't allocate, but applying a graph edit can. Nodes,
+/// their buffers and sub-graphs are allocated by the controller, and removed
+/// ones are handed back to be dropped there. Linking nodes into a graph
+/// happens here though, and each graph only has room for 64 nodes and edges
+/// and 8 extra sinks to start with: a block that grows a graph past what it
+/// has held so far allocates. Keep that in mind when editing big graphs while
+/// playing.
+///
+/// Get one from [`Klingt::split`](crate::Klingt::split). With the `cpal_sink`
+/// feature, [`CpalDevice::play`](crate::CpalDevice::play) drives a processor
+/// straight from the device's audio callback.
+pub struct KlingtProcessor {
+    /// Main output graph at device/output sample rate
+    main_graph: AudioGraph,
+    /// Output sample rate
+    sample_rate: u32,
+    /// Number of output channels
+    channels: usize,
+    /// Samples per block, for every graph
+    block_size: usize,
+
+    /// Sub-graphs for nodes at different sample rates
+    /// Key: the sample rate of the sub-graph
+    sub_graphs: HashMap<u32, SubGraph>,
+
+    /// Blocks processed on main graph (for scheduling)
+    main_blocks_processed: u64,
+    /// Order to process graphs in, so audio crossing between them is ready in time
+    schedule: Vec<Scheduled>,
+    /// Musical position, shared by every graph
+    transport: Transport,
+    workers: Workers,
+
+    commands: Consumer<Command>,
+    garbage: Producer<Garbage>,
+
+    /// Interleaved terminal input of the last rendered block
+    render_buf: Vec<f32>,
+    /// How much of `render_buf` has already been handed out
+    render_pos: usize,
+}
+
+impl KlingtProcessor {
+    pub(crate) fn new(
+        sample_rate: u32,
+        channels: usize,
+        commands: Consumer<Command>,
+        garbage: Producer<Garbage>,
+    ) -> Self {
+        let mut processor = Self {
+            main_graph: AudioGraph::new(sample_rate, DEFAULT_BLOCK_SIZE),
+            sample_rate,
+            channels,
+            block_size: DEFAULT_BLOCK_SIZE,
+            sub_graphs: HashMap::with_capacity(8),
+            main_blocks_processed: 0,
+            schedule: alloc::vec![Scheduled { graph_id: 0, ahead: false }],
+            transport: Transport::new(sample_rate),
+            workers: Default::default(),
+            commands,
+            garbage,
+            render_buf: Vec::new(),
+            render_pos: 0,
+        };
+        processor.set_channels(channels);
+        processor
+    }
+
+    /// Get the output sample rate in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Get the number of output channels.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Get the number of samples processed per block.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Get the number of threads processing the graph, including the one calling
+    /// [`process`](Self::process).
+    #[cfg(feature = "std")]
+    pub fn threads(&self) -> usize {
+        self.workers.as_ref().map_or(1, WorkerPool::threads)
+    }
+
+    /// Start the worker threads, replacing any running ones
+    #[cfg(feature = "std")]
+    pub(crate) fn set_threads(&mut self, threads: usize) {
+        self.workers = if threads > 1 { Some(WorkerPool::new(threads)) } else { None };
+    }
+
+    pub(crate) fn set_channels(&mut self, channels: usize) {
+        self.channels = channels;
+        self.alloc_render_buf();
+    }
+
+    /// Resize every graph for a new block size
+    ///
+    /// Sub-graphs created after this are sized by the controller.
+    pub(crate) fn set_block_size(&mut self, block_size: usize) {
+        self.block_size = block_size;
+        self.main_graph.set_block_size(block_size);
+        for sub in self.sub_graphs.values_mut() {
+            sub.graph.set_block_size(block_size);
+        }
+        self.alloc_render_buf();
+    }
+
+    fn alloc_render_buf(&mut self) {
+        // Allocate up front so rendering on the audio thread doesn't have to
+        self.render_buf = alloc::vec![0.0; self.block_size * self.channels];
+        self.render_pos = self.render_buf.len();
+    }
+
+    /// Apply all pending structural changes from the controller
+    ///
+    /// Allocates when a graph outgrows its storage, see [`KlingtProcessor`].
+    pub(crate) fn apply_commands(&mut self) {
+        while let Ok(command) = self.commands.pop() {
+            match command {
+                Command::AddNode { graph_id, node } => {
+                    if let Some(graph) = self.graph_mut(graph_id) {
+                        graph.insert(node);
+                    }
+                }
+                Command::RemoveNode { graph_id, node_id } => {
+                    if let Some(node) = self.graph_mut(graph_id).and_then(|g| g.remove(node_id)) {
+                        self.discard(Garbage::Node(node));
+                    }
+                }
+                Command::Connect { graph_id, from, to, edge } => {
+                    // Already validated by the controller
+                    if let Some(graph) = self.graph_mut(graph_id) {
+                        let _ = graph.connect(from, to, edge);
+                    }
+                }
+                Command::Disconnect { graph_id, from, to } => {
+                    if let Some(graph) = self.graph_mut(graph_id) {
+                        graph.disconnect(from, to);
+                    }
+                }
+                Command::DisconnectAll { graph_id, node_id } => {
+                    if let Some(graph) = self.graph_mut(graph_id) {
+                        graph.disconnect_all(node_id);
+                    }
+                }
+                Command::SetTerminal { graph_id, node_id } => {
+                    if let Some(graph) = self.graph_mut(graph_id) {
+                        graph.set_terminal(node_id);
+                    }
+                }
+                Command::AddTerminal { graph_id, node_id } => {
+                    if let Some(graph) = self.graph_mut(graph_id) {
+                        graph.add_terminal(node_id);
+                    }
+                }
+                Command::AddSubGraph { rate, graph } => {
+                    // Pick up the clock where the output is
+                    let elapsed = (self.main_blocks_processed * self.block_size as u64) as f64;
+                    self.sub_graphs.insert(rate, SubGraph {
+                        graph,
+                        blocks_processed: 0,
+                        start_block: self.main_blocks_processed,
+                        start_frame: (elapsed * rate as f64 / self.sample_rate as f64).round() as u64,
+                    });
+                }
+                Command::RemoveSubGraph { rate } => {
+                    if let Some(sub) = self.sub_graphs.remove(&rate) {
+                        self.discard(Garbage::Graph(sub.graph));
+                    }
+                }
+                Command::SetSchedule(schedule) => {
+                    let old = core::mem::replace(&mut self.schedule, schedule);
+                    self.discard(Garbage::Schedule(old));
+                }
+                Command::Transport(command) => {
+                    let frame = self.main_blocks_processed * self.block_size as u64;
+                    if let Some(old) = self.transport.apply(command, frame) {
+                        self.discard(Garbage::TempoMap(old));
+                    }
+                }
+            }
+        }
+    }
+
+    fn graph_mut(&mut self, graph_id: usize) -> Option<&mut AudioGraph> {
+        if graph_id == 0 {
+            Some(&mut self.main_graph)
+        } else {
+            self.sub_graphs.get_mut(&(graph_id as u32)).map(|sub| &mut *sub.graph)
+        }
+    }
+
+    /// Hand something back to the controller to be dropped there
+    fn discard(&mut self, garbage: Garbage) {
+        // If the controller isn't collecting, dropping here is the only option left
+        let _ = self.garbage.push(garbage);
+    }
+
+    /// Process one block of audio ([`block_size`](Self::block_size) samples).
+    ///
+    /// Applies pending structural changes from the controller, then processes
+    /// every graph in dependency order, upstream first:
+    /// 1. Sub-graphs feeding the output run a few blocks ahead to keep resamplers fed
+    /// 2. The main graph generates one block of output
+    /// 3. Sub-graphs fed by the main graph catch up to it
+    pub fn process(&mut self) {
+        self.apply_commands();
+
+        let main_rate = self.sample_rate as f64;
+        let main_blocks = self.main_blocks_processed + 1;
+        let block_size = self.block_size as u64;
+
+        for i in 0..self.schedule.len() {
+            let entry = self.schedule[i];
+            if entry.graph_id == 0 {
+                let frame = self.main_blocks_processed * block_size;
+                process_graph(&mut self.main_graph, Timeline { frame, step: 1.0 }, &self.transport, &self.workers);
+                continue;
+            }
+
+            let sub = match self.sub_graphs.get_mut(&(entry.graph_id as u32)) {
+                Some(sub) => sub,
+                None => continue,
+            };
+
+            // How many sub-graph blocks cover the same time as main_blocks of output
+            let rate_ratio = entry.graph_id as f64 / main_rate;
+            let elapsed = main_blocks - sub.start_block;
+            let mut blocks_needed = ((elapsed as f64) * rate_ratio).ceil() as u64;
+            if entry.ahead {
+                blocks_needed += LEAD_BLOCKS;
+            }
+
+            let step = 1.0 / rate_ratio;
+            while sub.blocks_processed < blocks_needed {
+                let frame = sub.start_frame + sub.blocks_processed * block_size;
+                process_graph(&mut sub.graph, Timeline { frame, step }, &self.transport, &self.workers);
+                sub.blocks_processed += 1;
+            }
+        }
+
+        self.main_blocks_processed += 1;
+    }
+
+    /// Render `frames` frames of output into memory.
+    ///
+    /// See [`Klingt::render`](crate::Klingt::render).
+    pub fn render(&mut self, frames: usize) -> Vec<f32> {
+        let mut out = alloc::vec![0.0; frames * self.channels];
+        self.render_into(&mut out);
+        out
+    }
+
+    /// Render into an existing buffer of interleaved samples.
+    ///
+    /// Fills all of `out`, processing as many blocks as needed. `out` doesn't have to
+    /// be a multiple of the block size; left-over samples are returned by the next call.
+    /// Doesn't allocate, so it's safe to call from an audio callback.
+    pub fn render_into(&mut self, out: &mut [f32]) {
+        let mut written = 0;
+
+        while written < out.len() {
+            if self.render_pos >= self.render_buf.len() {
+                self.process();
+                self.main_graph.read_terminal_input(self.channels, &mut self.render_buf);
+                self.render_pos = 0;
+            }
+
+            let n = (self.render_buf.len() - self.render_pos).min(out.len() - written);
+            out[written..written + n]
+                .copy_from_slice(&self.render_buf[self.render_pos..self.render_pos + n]);
+            self.render_pos += n;
+            written += n;
+        }
+    }
+}
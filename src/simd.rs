@@ -0,0 +1,150 @@
+//! Vectorized kernels for the hot loops of the built-in nodes
+//!
+//! Samples are handled a vector of [`LANES`] at a time, as fixed-size arrays,
+//! with a scalar loop for whatever doesn't fill a whole vector. Every lane does
+//! exactly what the scalar loop does, so the results are the same.
+//!
+//! There are no explicit SIMD types or intrinsics here. The fixed-length inner
+//! loops are written so LLVM auto-vectorizes them in optimized builds, using
+//! whatever vector width the target has (SSE2 on any x86_64, wider with
+//! `-C target-cpu`, NEON on aarch64). Debug builds and targets without vector
+//! units run them as plain loops.
+
+use core::convert::TryInto;
+
+use crate::buffer::{Buffer, OutputSample, Sample};
+
+/// Samples per vector - a few registers' worth for either sample type
+pub(crate) const LANES: usize = 8;
+
+/// Run `f` on every sample of `out` and the matching sample of `input`,
+/// a vector at a time
+#[inline(always)]
+fn zip_lanes(out: &mut [Sample], input: &[Sample], f: impl Fn(&mut Sample, Sample)) {
+    let len = out.len().min(input.len());
+    let mut out = out[..len].chunks_exact_mut(LANES);
+    let mut input = input[..len].chunks_exact(LANES);
+
+    for (o, i) in (&mut out).zip(&mut input) {
+        let o: &mut [Sample; LANES] = o.try_into().unwrap();
+        let i: &[Sample; LANES] = i.try_into().unwrap();
+        for lane in 0..LANES {
+            f(&mut o[lane], i[lane]);
+        }
+    }
+
+    for (o, &i) in out.into_remainder().iter_mut().zip(input.remainder()) {
+        f(o, i);
+    }
+}
+
+/// `out = input * gain`
+pub(crate) fn scale(out: &mut [Sample], input: &[Sample], gain: Sample) {
+    zip_lanes(out, input, |o, i| *o = i * gain);
+}
+
+/// `out = input * gains`, sample by sample
+pub(crate) fn multiply(out: &mut [Sample], input: &[Sample], gains: &[Sample]) {
+    let len = input.len().min(gains.len());
+    out[..len].copy_from_slice(&input[..len]);
+    zip_lanes(out, gains, |o, g| *o *= g);
+}
+
+/// `out += input`
+pub(crate) fn add(out: &mut [Sample], input: &[Sample]) {
+    zip_lanes(out, input, |o, i| *o += i);
+}
+
+/// `out += input * gain`
+pub(crate) fn add_scaled(out: &mut [Sample], input: &[Sample], gain: Sample) {
+    zip_lanes(out, input, |o, i| *o += i * gain);
+}
+
+/// The largest difference between neighbouring samples of `input`, starting
+/// from `last`, or NaN if there's one in the way
+pub(crate) fn max_step(last: Sample, input: &[Sample]) -> Sample {
+    let (first, previous) = match input.first() {
+        Some(&first) => (first, &input[..input.len() - 1]),
+        None => return 0.0,
+    };
+    let current = &input[1..];
+
+    let mut steps: [Sample; LANES] = [0.0; LANES];
+    for (c, p) in current.chunks_exact(LANES).zip(previous.chunks_exact(LANES)) {
+        let c: &[Sample; LANES] = c.try_into().unwrap();
+        let p: &[Sample; LANES] = p.try_into().unwrap();
+        for lane in 0..LANES {
+            // Written so a NaN sticks
+            let step = (c[lane] - p[lane]).abs();
+            steps[lane] = if step > steps[lane] || step.is_nan() { step } else { steps[lane] };
+        }
+    }
+
+    let mut max = (first - last).abs();
+    let rest = current.len() - current.len() % LANES;
+    let remainder = current[rest..].iter().zip(&previous[rest..]).map(|(c, p)| (c - p).abs());
+    for step in steps.iter().copied().chain(remainder) {
+        if step > max || step.is_nan() {
+            max = step;
+        }
+    }
+    max
+}
+
+/// Interleave `buffers` into `out`, which starts at interleaved sample `offset`
+///
+/// Output channels past the last buffer are silent.
+pub(crate) fn interleave<T: OutputSample>(buffers: &[Buffer], channels: usize, offset: usize, out: &mut [T]) {
+    let sample = |ch: usize, frame: usize| buffers.get(ch).map_or_else(T::default, |b| T::from_sample(b[frame]));
+    let (mut frame, mut ch) = (offset / channels, offset % channels);
+
+    // Finish the frame `offset` falls into
+    let mut i = 0;
+    while ch != 0 && i < out.len() {
+        out[i] = sample(ch, frame);
+        i += 1;
+        ch += 1;
+        if ch == channels {
+            ch = 0;
+            frame += 1;
+        }
+    }
+
+    // Whole frames
+    let out = &mut out[i..];
+    let frames = out.len() / channels;
+    let (whole, rest) = out.split_at_mut(frames * channels);
+    match (channels, buffers) {
+        (1, [mono, ..]) => {
+            for (o, &i) in whole.iter_mut().zip(&mono[frame..frame + frames]) {
+                *o = T::from_sample(i);
+            }
+        }
+        (2, [left, right, ..]) => {
+            let (left, right) = (&left[frame..frame + frames], &right[frame..frame + frames]);
+            for ((o, &l), &r) in whole.chunks_exact_mut(2).zip(left).zip(right) {
+                o[0] = T::from_sample(l);
+                o[1] = T::from_sample(r);
+            }
+        }
+        _ => {
+            for ch in 0..channels {
+                let outputs = whole.iter_mut().skip(ch).step_by(channels);
+                match buffers.get(ch) {
+                    Some(input) => {
+                        for (o, &i) in outputs.zip(&input[frame..frame + frames]) {
+                            *o = T::from_sample(i);
+                        }
+                    }
+                    None => outputs.for_each(|o| *o = T::default()),
+                }
+            }
+        }
+    }
+    frame += frames;
+
+    // Start of the frame the end of `out` falls into
+    for (ch, o) in rest.iter_mut().enumerate() {
+        *o = sample(ch, frame);
+    }
+}
@@ -0,0 +1,68 @@
+//! Lock-free latest-value channel for node state
+//!
+//! A triple buffer: the publisher always has a slot to write, the reader always
+//! has a slot to read, and the third is handed back and forth with one atomic
+//! swap. Neither side ever waits, and the reader only sees whole snapshots.
+
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Bits of the shared index that pick the slot
+const INDEX: u8 = 0b11;
+/// Set on the shared index when it holds a snapshot the reader hasn't seen
+const FRESH: u8 = 0b100;
+
+struct Slots<T> {
+    slots: [UnsafeCell<T>; 3],
+    /// The slot neither side is using, plus the `FRESH` flag
+    shared: AtomicU8,
+}
+
+// Each slot is only ever accessed by the side that currently owns its index
+unsafe impl<T: Send> Sync for Slots<T> {}
+
+/// Writing side, owned by the node on the audio thread
+pub(crate) struct Publisher<T> {
+    slots: Arc<Slots<T>>,
+    index: u8,
+}
+
+/// Reading side, owned by the [`Handle`](crate::Handle)
+pub(crate) struct Reader<T> {
+    slots: Arc<Slots<T>>,
+    index: u8,
+}
+
+/// Create a channel whose reader sees `initial` until something is published
+///
+/// `T` is `Copy`, so overwriting a slot on the audio thread never drops anything.
+pub(crate) fn snapshot<T: Copy>(initial: T) -> (Publisher<T>, Reader<T>) {
+    let slots = Arc::new(Slots {
+        slots: [UnsafeCell::new(initial), UnsafeCell::new(initial), UnsafeCell::new(initial)],
+        shared: AtomicU8::new(1),
+    });
+    (Publisher { slots: slots.clone(), index: 0 }, Reader { slots, index: 2 })
+}
+
+impl<T: Copy> Publisher<T> {
+    /// Replace the latest snapshot. Doesn't allocate or wait.
+    pub fn publish(&mut self, value: T) {
+        // Safety: nobody else touches the slot at our index
+        unsafe { *self.slots.slots[self.index as usize].get() = value };
+        let previous = self.slots.shared.swap(self.index | FRESH, Ordering::AcqRel);
+        self.index = previous & INDEX;
+    }
+}
+
+impl<T> Reader<T> {
+    /// The latest published snapshot
+    pub fn read(&mut self) -> &T {
+        if self.slots.shared.load(Ordering::Relaxed) & FRESH != 0 {
+            let previous = self.slots.shared.swap(self.index, Ordering::AcqRel);
+            self.index = previous & INDEX;
+        }
+        // Safety: nobody else touches the slot at our index
+        unsafe { &*self.slots.slots[self.index as usize].get() }
+    }
+}
@@ -0,0 +1,335 @@
+//! Musical transport: tempo, time signature, play position and looping
+
+use alloc::vec::Vec;
+
+/// Most parts a block is split into where the tempo changes or the loop wraps
+const MAX_SEGMENTS: usize = 8;
+
+/// Beats per bar and the note value of a beat, e.g. 3/4 or 6/8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeSignature {
+    /// Beats per bar
+    pub numerator: u32,
+    /// Note value of one beat (4 = quarter note, 8 = eighth note)
+    pub denominator: u32,
+}
+
+impl TimeSignature {
+    /// Create a time signature.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either part is 0.
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        assert!(numerator > 0 && denominator > 0, "Invalid time signature {}/{}", numerator, denominator);
+        Self { numerator, denominator }
+    }
+
+    /// Length of one bar in quarter notes
+    pub fn bar_length(&self) -> f64 {
+        self.numerator as f64 * 4.0 / self.denominator as f64
+    }
+}
+
+impl Default for TimeSignature {
+    /// 4/4
+    fn default() -> Self {
+        Self::new(4, 4)
+    }
+}
+
+/// Tempo over the course of a piece.
+///
+/// Positions are in beats (quarter notes) from the start, tempos in beats per
+/// minute. The tempo holds until the next change.
+///
+/// # Example
+///
+/// ```
+/// use klingt::TempoMap;
+///
+/// // 120 BPM, slowing down to 90 BPM from bar 9 (in 4/4)
+/// let tempo = TempoMap::new(120.0).with_change(32.0, 90.0);
+/// assert_eq!(tempo.tempo_at(40.0), 90.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TempoMap {
+    /// (beat, bpm), sorted by beat, starting at beat 0
+    changes: Vec<(f64, f64)>,
+}
+
+impl TempoMap {
+    /// A constant tempo.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bpm` isn't positive.
+    pub fn new(bpm: f64) -> Self {
+        assert!(bpm > 0.0, "Tempo must be positive, got {}", bpm);
+        Self { changes: alloc::vec![(0.0, bpm)] }
+    }
+
+    /// Change to `bpm` at `beat`, replacing any change already there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bpm` isn't positive or `beat` is negative.
+    pub fn with_change(mut self, beat: f64, bpm: f64) -> Self {
+        assert!(bpm > 0.0, "Tempo must be positive, got {}", bpm);
+        assert!(beat >= 0.0, "Tempo changes can't be before beat 0, got {}", beat);
+
+        match self.changes.iter().position(|&(b, _)| b >= beat) {
+            Some(i) if self.changes[i].0 == beat => self.changes[i].1 = bpm,
+            Some(i) => self.changes.insert(i, (beat, bpm)),
+            None => self.changes.push((beat, bpm)),
+        }
+        self
+    }
+
+    /// Tempo in BPM at `beat`.
+    pub fn tempo_at(&self, beat: f64) -> f64 {
+        self.changes.iter().rev().find(|&&(b, _)| b <= beat).unwrap_or(&self.changes[0]).1
+    }
+
+    /// First tempo change after `beat`
+    fn next_change(&self, beat: f64) -> Option<f64> {
+        self.changes.iter().map(|&(b, _)| b).find(|&b| b > beat)
+    }
+
+    /// Frames it takes to get from beat `from` to beat `to` (`from <= to`)
+    fn frames_between(&self, from: f64, to: f64, sample_rate: f64) -> f64 {
+        let mut frames = 0.0;
+        let mut beat = from;
+        while beat < to {
+            let end = self.next_change(beat).map_or(to, |next| next.min(to));
+            frames += (end - beat) * 60.0 * sample_rate / self.tempo_at(beat);
+            beat = end;
+        }
+        frames
+    }
+
+    /// Beat reached from `beat` after `frames` frames
+    fn advance(&self, mut beat: f64, mut frames: f64, sample_rate: f64) -> f64 {
+        loop {
+            let beats_per_frame = self.tempo_at(beat) / (60.0 * sample_rate);
+            match self.next_change(beat) {
+                Some(next) if (next - beat) / beats_per_frame <= frames => {
+                    frames -= (next - beat) / beats_per_frame;
+                    beat = next;
+                }
+                _ => return beat + frames * beats_per_frame,
+            }
+        }
+    }
+}
+
+impl Default for TempoMap {
+    /// 120 BPM
+    fn default() -> Self {
+        Self::new(120.0)
+    }
+}
+
+/// The transport as seen by a node, from [`ProcessContext::transport`](crate::ProcessContext::transport).
+///
+/// Describes the first sample of the buffer. Blocks are split where the tempo
+/// changes or the loop wraps, so within one buffer the position moves at a
+/// constant rate and [`beat_at`](Self::beat_at) is exact.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransportInfo {
+    /// Whether the transport is running
+    pub playing: bool,
+    /// Position in beats (quarter notes) at the start of the buffer
+    pub beat: f64,
+    /// Tempo in beats per minute
+    pub tempo: f64,
+    /// How far the position moves per frame of this graph; 0 while stopped
+    pub beats_per_frame: f64,
+    /// The time signature bars are counted in
+    pub time_signature: TimeSignature,
+}
+
+impl TransportInfo {
+    /// Position in beats at `offset` samples into the buffer.
+    pub fn beat_at(&self, offset: usize) -> f64 {
+        self.beat + offset as f64 * self.beats_per_frame
+    }
+
+    /// Position in bars at `offset` samples into the buffer, counting from bar 0.
+    pub fn bar_at(&self, offset: usize) -> f64 {
+        self.beat_at(offset) / self.time_signature.bar_length()
+    }
+
+    /// Position in ticks of `ppq` ticks per quarter note at `offset` samples into the buffer.
+    pub fn tick_at(&self, offset: usize, ppq: u32) -> u64 {
+        (self.beat_at(offset) * ppq as f64).max(0.0) as u64
+    }
+}
+
+impl Default for TransportInfo {
+    fn default() -> Self {
+        Self {
+            playing: false,
+            beat: 0.0,
+            tempo: 120.0,
+            beats_per_frame: 0.0,
+            time_signature: TimeSignature::default(),
+        }
+    }
+}
+
+/// A change to the transport, applied by the processor at the next block boundary
+pub(crate) enum TransportCommand {
+    Start,
+    Stop,
+    Seek(f64),
+    SetTempo(TempoMap),
+    SetTimeSignature(TimeSignature),
+    SetLoop(Option<(f64, f64)>),
+}
+
+/// Processor-side transport state
+///
+/// The position is a function of the output frame, anchored where it was last
+/// started, stopped or moved, so every graph can work out where it is - sub-graphs
+/// running ahead of the output included.
+pub(crate) struct Transport {
+    sample_rate: f64,
+    playing: bool,
+    /// Output frame at which the position was `anchor_beat`
+    anchor_frame: f64,
+    anchor_beat: f64,
+    tempo: TempoMap,
+    time_signature: TimeSignature,
+    /// Loop region in beats
+    looping: Option<(f64, f64)>,
+}
+
+impl Transport {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f64,
+            playing: false,
+            anchor_frame: 0.0,
+            anchor_beat: 0.0,
+            tempo: TempoMap::default(),
+            time_signature: TimeSignature::default(),
+            looping: None,
+        }
+    }
+
+    /// Apply a command at output frame `frame`
+    ///
+    /// Returns the replaced tempo map, if any, so it can be dropped elsewhere.
+    pub fn apply(&mut self, command: TransportCommand, frame: u64) -> Option<TempoMap> {
+        let frame = frame as f64;
+        // Re-anchor, so the change only affects what comes after `frame`
+        self.anchor_beat = self.beat_at(frame);
+        self.anchor_frame = frame;
+
+        match command {
+            TransportCommand::Start => self.playing = true,
+            TransportCommand::Stop => self.playing = false,
+            TransportCommand::Seek(beat) => self.anchor_beat = beat,
+            TransportCommand::SetTempo(tempo) => return Some(core::mem::replace(&mut self.tempo, tempo)),
+            TransportCommand::SetTimeSignature(signature) => self.time_signature = signature,
+            TransportCommand::SetLoop(looping) => self.looping = looping.filter(|&(start, end)| start < end),
+        }
+        None
+    }
+
+    /// Position at output frame `frame`
+    fn beat_at(&self, frame: f64) -> f64 {
+        if !self.playing || frame <= self.anchor_frame {
+            return self.anchor_beat;
+        }
+
+        let (beat, frames) = (self.anchor_beat, frame - self.anchor_frame);
+        match self.looping {
+            Some((start, end)) if beat < end => {
+                let to_end = self.tempo.frames_between(beat, end, self.sample_rate);
+                if frames < to_end {
+                    return self.tempo.advance(beat, frames, self.sample_rate);
+                }
+                let period = self.tempo.frames_between(start, end, self.sample_rate);
+                self.tempo.advance(start, (frames - to_end) % period, self.sample_rate)
+            }
+            _ => self.tempo.advance(beat, frames, self.sample_rate),
+        }
+    }
+
+    /// Split a block of `len` frames starting at output frame `start` into parts
+    /// with a steady position, for a graph with `step` output frames per frame
+    pub fn segments(&self, start: f64, step: f64, len: usize, out: &mut Segments) {
+        out.len = 0;
+        let mut offset = 0;
+
+        loop {
+            let frame = start + offset as f64 * step;
+            let beat = self.beat_at(frame);
+            let tempo = self.tempo.tempo_at(beat);
+            out.items[out.len] = (offset, TransportInfo {
+                playing: self.playing,
+                beat,
+                tempo,
+                beats_per_frame: if self.playing { tempo * step / (60.0 * self.sample_rate) } else { 0.0 },
+                time_signature: self.time_signature,
+            });
+            out.len += 1;
+
+            if !self.playing || out.len == MAX_SEGMENTS {
+                return;
+            }
+
+            // The next tempo change or the end of the loop, whichever comes first
+            let mut next = self.tempo.next_change(beat);
+            if let Some((_, end)) = self.looping.filter(|&(_, end)| beat < end) {
+                next = Some(next.map_or(end, |next| next.min(end)));
+            }
+            let next = match next {
+                Some(next) => next,
+                None => return,
+            };
+
+            let frames = self.tempo.frames_between(beat, next, self.sample_rate);
+            let next_offset = ((frame + frames - start) / step).ceil() as usize;
+            if next_offset >= len {
+                return;
+            }
+            offset = next_offset.max(offset + 1);
+        }
+    }
+}
+
+/// A block's worth of transport positions, from [`Transport::segments`]
+pub(crate) struct Segments {
+    len: usize,
+    /// Offset into the block where each part starts, and the position there
+    items: [(usize, TransportInfo); MAX_SEGMENTS],
+}
+
+impl Segments {
+    pub fn new() -> Self {
+        Self {
+            len: 1,
+            items: [(0, TransportInfo::default()); MAX_SEGMENTS],
+        }
+    }
+
+    /// Transport at `offset` into the block
+    pub fn info_at(&self, offset: usize) -> TransportInfo {
+        let (start, mut info) = self.items[..self.len]
+            .iter()
+            .rev()
+            .find(|&&(start, _)| start <= offset)
+            .copied()
+            .unwrap_or(self.items[0]);
+        info.beat = info.beat_at(offset - start);
+        info
+    }
+
+    /// Where the part after the one containing `offset` starts, if there is one
+    pub fn next_after(&self, offset: usize) -> Option<usize> {
+        self.items[..self.len].iter().map(|&(start, _)| start).find(|&start| start > offset)
+    }
+}
@@ -1,19 +1,26 @@
 //! Integration tests for klingt
 //!
-//! These tests require audio output and are meant to be run manually
-//! to verify audio functionality.
+//! The hardware tests require audio output and are meant to be run manually
+//! to verify audio functionality. Everything else renders offline.
 
+#[cfg(feature = "cpal_sink")]
 use std::thread::sleep;
+#[cfg(feature = "cpal_sink")]
 use std::time::{Duration, Instant};
 
-use klingt::nodes::effect::{Gain, Mixer, SlewLimiter};
-use klingt::nodes::source::Sine;
-use klingt::Klingt;
+use klingt::nodes::effect::{ChannelMixer, Gain, GainMessage, Mixer, SlewLimiter};
+use klingt::nodes::sink::RtrbSink;
+use klingt::nodes::source::{PlayerEvent, SamplePlayer, Sine, SineMessage};
+use klingt::{
+    AudioNode, Buffer, ChannelLayout, ConnectionDescription, GraphDescription, Input, Klingt, KlingtError, NodeDescription, NodeRegistry,
+    OutputSample, ProcessContext, Sample,
+};
 
 #[cfg(feature = "cpal_sink")]
 use klingt::CpalDevice;
 
 /// Helper to run the audio loop for a given duration
+#[cfg(feature = "cpal_sink")]
 fn run_for(klingt: &mut Klingt, seconds: f32) {
     let start = Instant::now();
     let duration = Duration::from_secs_f32(seconds);
@@ -21,7 +28,7 @@ fn run_for(klingt: &mut Klingt, seconds: f32) {
     let mut blocks = 0u64;
 
     while start.elapsed() < duration {
-        let target = (start.elapsed().as_secs_f64() * rate / 64.0) as u64 + 4;
+        let target = (start.elapsed().as_secs_f64() * rate / klingt.block_size() as f64) as u64 + 4;
         while blocks < target {
             klingt.process();
             blocks += 1;
@@ -97,7 +104,6 @@ fn slew_limiter_smoothing() {
 #[ignore] // Requires audio hardware
 #[cfg(feature = "cpal_sink")]
 fn runtime_frequency_change() {
-    use klingt::nodes::SineMessage;
     
     let mut klingt = Klingt::default_output().expect("No audio device");
     
@@ -173,3 +179,1960 @@ fn gain_creation() {
     let gain2 = Gain::new(1.5);
     assert_eq!(gain2.gain(), 1.5);
 }
+
+/// Build an engine that writes into a ring buffer instead of a device
+fn offline(sample_rate: u32) -> (Klingt, rtrb::Consumer<f32>) {
+    let (producer, consumer) = rtrb::RingBuffer::new(1 << 16);
+    let klingt = Klingt::new(sample_rate).with_output(RtrbSink::stereo(producer));
+    (klingt, consumer)
+}
+
+/// Pop everything currently in the ring buffer
+fn drain(consumer: &mut rtrb::Consumer<f32>) -> Vec<f32> {
+    std::iter::from_fn(|| consumer.pop().ok()).collect()
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0f32, |m, s| m.max(s.abs()))
+}
+
+#[test]
+fn remove_keeps_remaining_nodes_addressable() {
+    let (mut klingt, mut out) = offline(48000);
+
+    let a = klingt.add(Sine::new(440.0));
+    let _b = klingt.add(Sine::new(550.0));
+    let c = klingt.add(Sine::new(660.0));
+
+    // `c` gets moved into `a`'s slot internally
+    klingt.remove(a);
+    klingt.output(&c);
+
+    for _ in 0..4 {
+        klingt.process();
+    }
+    assert!(peak(&drain(&mut out)) > 0.1);
+
+    klingt.remove(c);
+    klingt.process();
+    assert_eq!(peak(&drain(&mut out)), 0.0);
+}
+
+#[test]
+fn remove_last_subgraph_node_tears_down_bridge() {
+    let (mut klingt, mut out) = offline(48000);
+
+    let samples = vec![0.5f32; 44100 * 2];
+    let player = klingt.add(SamplePlayer::new(samples, 2, 44100));
+    klingt.output(&player);
+
+    for _ in 0..8 {
+        klingt.process();
+    }
+    assert!(peak(&drain(&mut out)) > 0.4);
+
+    klingt.remove(player);
+    for _ in 0..8 {
+        klingt.process();
+    }
+    assert_eq!(peak(&drain(&mut out)), 0.0);
+
+    // A new node at that rate gets a fresh sub-graph
+    let player = klingt.add(SamplePlayer::new(vec![0.5f32; 44100 * 2], 2, 44100));
+    klingt.output(&player);
+    for _ in 0..8 {
+        klingt.process();
+    }
+    assert!(peak(&drain(&mut out)) > 0.4);
+}
+
+#[test]
+fn disconnect_and_reconnect() {
+    let (mut klingt, mut out) = offline(48000);
+
+    let sine = klingt.add(Sine::new(440.0));
+    let mixer_a = klingt.add(Mixer::stereo());
+    let mixer_b = klingt.add(Mixer::stereo());
+    let muted = klingt.add(Gain::new(0.0).without_smoothing());
+    let master = klingt.add(Mixer::stereo());
+
+    klingt.connect(&sine, &mixer_a);
+    klingt.connect(&mixer_b, &muted);
+    klingt.connect(&mixer_a, &master);
+    klingt.connect(&muted, &master);
+    klingt.output(&master);
+
+    klingt.process();
+    assert!(peak(&drain(&mut out)) > 0.1);
+
+    // Route the sine into the muted path instead
+    klingt.disconnect(&sine, &mixer_a);
+    klingt.connect(&sine, &mixer_b);
+    klingt.process();
+    assert_eq!(peak(&drain(&mut out)), 0.0);
+
+    // Bypass the muted gain entirely
+    klingt.disconnect_all(&muted);
+    klingt.connect(&mixer_b, &master);
+    klingt.process();
+    assert!(peak(&drain(&mut out)) > 0.1);
+}
+
+#[test]
+fn disconnect_through_resampling_bridge() {
+    let (mut klingt, mut out) = offline(48000);
+
+    let player = klingt.add(SamplePlayer::new(vec![0.5f32; 44100 * 2], 2, 44100));
+    let mixer = klingt.add(Mixer::stereo());
+    klingt.connect(&player, &mixer);
+    klingt.output(&mixer);
+
+    for _ in 0..8 {
+        klingt.process();
+    }
+    assert!(peak(&drain(&mut out)) > 0.4);
+
+    klingt.disconnect(&player, &mixer);
+    klingt.process();
+    assert_eq!(peak(&drain(&mut out)), 0.0);
+
+    klingt.connect(&player, &mixer);
+    for _ in 0..8 {
+        klingt.process();
+    }
+    assert!(peak(&drain(&mut out)) > 0.4);
+
+    klingt.disconnect_all(&player);
+    klingt.process();
+    assert_eq!(peak(&drain(&mut out)), 0.0);
+}
+
+#[test]
+fn render_sine_offline() {
+    let mut klingt = Klingt::offline(48000);
+    let sine = klingt.add(Sine::new(1000.0).with_amplitude(0.5));
+    klingt.output(&sine);
+
+    let samples = klingt.render(4800);
+    assert_eq!(samples.len(), 4800 * 2);
+
+    // Mono source is duplicated to both channels
+    assert!(samples.chunks(2).all(|f| f[0] == f[1]));
+    assert!((peak(&samples) - 0.5).abs() < 1e-3);
+
+    // 1 kHz over 100ms: 100 full cycles, i.e. ~200 zero crossings
+    let crossings = samples
+        .chunks(2)
+        .map(|f| f[0])
+        .collect::<Vec<_>>()
+        .windows(2)
+        .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+        .count();
+    assert!((198..=202).contains(&crossings), "{} crossings", crossings);
+}
+
+#[test]
+fn render_into_is_contiguous_across_calls() {
+    let render = |chunk: usize| {
+        let mut klingt = Klingt::offline(48000);
+        let sine = klingt.add(Sine::new(440.0));
+        klingt.output(&sine);
+
+        let mut out = vec![0.0; 1000 * 2];
+        for piece in out.chunks_mut(chunk * 2) {
+            klingt.render_into(piece);
+        }
+        out
+    };
+
+    assert_eq!(render(1000), render(37));
+}
+
+#[test]
+fn render_includes_resampled_subgraphs() {
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(SamplePlayer::new(vec![0.5f32; 22050 * 2], 2, 22050));
+    klingt.output(&player);
+
+    let samples = klingt.render(4800);
+    // Skip the resampler priming at the start
+    assert!(samples[200..].iter().all(|&s| (s - 0.5).abs() < 1e-6));
+}
+
+#[test]
+fn split_applies_edits_at_block_boundaries() {
+    let (mut controller, mut processor) = Klingt::offline(48000).split();
+
+    let sine = controller.add(Sine::new(440.0));
+    // Nothing happens until the processor runs
+    controller.output(&sine);
+
+    let audio = std::thread::spawn(move || {
+        let first = processor.render(64);
+        (processor, first)
+    });
+    let (mut processor, first) = audio.join().unwrap();
+    assert!(peak(&first) > 0.1);
+
+    controller.remove(sine);
+    assert!(peak(&processor.render(64)) == 0.0);
+}
+
+#[test]
+fn split_processor_handles_subgraphs() {
+    let (mut controller, mut processor) = Klingt::offline(48000).split();
+
+    let player = controller.add(SamplePlayer::new(vec![0.5f32; 44100 * 2], 2, 44100));
+    controller.output(&player);
+    assert!(peak(&processor.render(512)) > 0.4);
+
+    // Removing the last node tears down the sub-graph on the audio side too
+    controller.remove(player);
+    assert_eq!(peak(&processor.render(512)), 0.0);
+
+    let player = controller.add(SamplePlayer::new(vec![0.25f32; 44100 * 2], 2, 44100));
+    controller.output(&player);
+    let samples = processor.render(512);
+    assert!((peak(&samples) - 0.25).abs() < 1e-6);
+}
+
+#[test]
+fn block_size_does_not_change_output() {
+    let render = |block_size: usize| {
+        let mut klingt = Klingt::offline(48000).with_block_size(block_size);
+        let sine = klingt.add(Sine::new(440.0));
+        klingt.output(&sine);
+        klingt.render(4096)
+    };
+
+    let reference = render(64);
+    for block_size in [16, 256, 2048] {
+        assert_eq!(render(block_size), reference, "block size {}", block_size);
+    }
+}
+
+#[test]
+fn nodes_see_configured_block_size() {
+    /// Outputs the buffer size it's given, so the test can check what nodes see
+    struct ReportBlockSize;
+
+    impl AudioNode for ReportBlockSize {
+        type Message = ();
+        type Event = ();
+        type State = ();
+
+        fn process(
+            &mut self,
+            ctx: &ProcessContext,
+            _messages: impl Iterator<Item = ()>,
+            _inputs: &[Input],
+            outputs: &mut [Buffer],
+        ) {
+            assert_eq!(outputs[0].len(), ctx.buffer_size);
+            outputs[0].iter_mut().for_each(|s| *s = ctx.buffer_size as Sample);
+        }
+    }
+
+    let mut klingt = Klingt::offline(48000).with_block_size(128);
+    let node = klingt.add(ReportBlockSize);
+    klingt.output(&node);
+
+    assert!(klingt.render(256).iter().all(|&s| s == 128.0));
+}
+
+#[test]
+fn large_blocks_with_resampled_subgraphs() {
+    let mut klingt = Klingt::offline(48000).with_block_size(1024);
+    let player = klingt.add(SamplePlayer::new(vec![0.5f32; 22050 * 2], 2, 22050));
+    klingt.output(&player);
+
+    let samples = klingt.render(9600);
+    // Skip the resampler priming at the start
+    assert!(samples[200..].iter().all(|&s| (s - 0.5).abs() < 1e-6));
+}
+
+#[test]
+#[should_panic]
+fn block_size_out_of_range() {
+    let _ = Klingt::offline(48000).with_block_size(4096);
+}
+
+#[test]
+fn connect_ports_selects_output_channel() {
+    let (mut klingt, mut out) = offline(48000);
+    let frames: Vec<f32> = (0..4800).flat_map(|_| [0.25, 0.75]).collect();
+    let player = klingt.add(SamplePlayer::new(frames, 2, 48000));
+    let mixer = klingt.add(Mixer::mono());
+
+    klingt.connect_ports(&player, 1, &mixer, 0);
+    klingt.output(&mixer);
+    klingt.process();
+
+    // Only the right channel arrives, duplicated by the stereo sink
+    assert!(drain(&mut out).iter().all(|&s| s == 0.75));
+}
+
+#[test]
+fn connect_ports_tells_destination_the_port() {
+    /// Outputs the sum of `port * channel value` over its inputs
+    struct PortWeighted;
+
+    impl AudioNode for PortWeighted {
+        type Message = ();
+        type Event = ();
+        type State = ();
+
+        fn process(
+            &mut self,
+            _ctx: &ProcessContext,
+            _messages: impl Iterator<Item = ()>,
+            inputs: &[Input],
+            outputs: &mut [Buffer],
+        ) {
+            outputs[0].silence();
+            for input in inputs {
+                assert_eq!(input.buffers().len(), 1);
+                let weight = input.port().expect("port-addressed input") as Sample + 1.0;
+                for (o, i) in outputs[0].iter_mut().zip(input.buffers()[0].iter()) {
+                    *o += weight * i;
+                }
+            }
+        }
+
+        fn num_inputs(&self) -> usize { 2 }
+    }
+
+    let (mut klingt, mut out) = offline(48000);
+    let frames: Vec<f32> = (0..4800).flat_map(|_| [0.25, 0.5]).collect();
+    let player = klingt.add(SamplePlayer::new(frames, 2, 48000));
+    let node = klingt.add(PortWeighted);
+
+    // Cross the channels over: left into port 1, right into port 0
+    klingt.connect_ports(&player, 0, &node, 1);
+    klingt.connect_ports(&player, 1, &node, 0);
+    klingt.output(&node);
+    klingt.process();
+
+    assert!(drain(&mut out).iter().all(|&s| s == 2.0 * 0.25 + 0.5));
+}
+
+#[test]
+fn connect_ports_through_resampling_bridge() {
+    let mut klingt = Klingt::offline(48000);
+    let frames: Vec<f32> = (0..22050).flat_map(|_| [0.25, 0.75]).collect();
+    let player = klingt.add(SamplePlayer::new(frames, 2, 22050));
+    let mixer = klingt.add(Mixer::mono());
+
+    klingt.connect_ports(&player, 1, &mixer, 0);
+    klingt.output(&mixer);
+
+    let samples = klingt.render(4800);
+    // Skip the resampler priming at the start
+    assert!(samples[200..].iter().all(|&s| (s - 0.75).abs() < 1e-6));
+}
+
+#[test]
+#[should_panic(expected = "Output port 2 out of range")]
+fn connect_ports_rejects_invalid_output() {
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(SamplePlayer::new(vec![0.0; 96], 2, 48000));
+    let gain = klingt.add(Gain::new(1.0));
+    klingt.connect_ports(&player, 2, &gain, 0);
+}
+
+#[test]
+#[should_panic(expected = "Input port 1 out of range")]
+fn connect_ports_rejects_invalid_input() {
+    let mut klingt = Klingt::offline(48000);
+    let sine = klingt.add(Sine::new(440.0));
+    let gain = klingt.add(Gain::new(1.0));
+    klingt.connect_ports(&sine, 0, &gain, 1);
+}
+
+#[test]
+fn try_output_without_sink() {
+    let mut klingt = Klingt::new(48000);
+    let sine = klingt.add(Sine::new(440.0));
+    assert_eq!(klingt.try_output(&sine), Err(KlingtError::NoOutputSink));
+}
+
+#[test]
+fn try_connect_rejects_handles_from_other_engines() {
+    let mut other = Klingt::offline(48000);
+    let _ = other.add(Sine::new(440.0));
+    let foreign = other.add(Sine::new(440.0));
+
+    let mut klingt = Klingt::offline(48000);
+    let gain = klingt.add(Gain::new(1.0));
+
+    assert!(matches!(klingt.try_connect(&foreign, &gain), Err(KlingtError::UnknownNode(_))));
+    assert!(matches!(klingt.try_output(&foreign), Err(KlingtError::UnknownNode(_))));
+}
+
+#[test]
+fn try_connect_ports_reports_invalid_ports() {
+    let mut klingt = Klingt::offline(48000);
+    let sine = klingt.add(Sine::new(440.0));
+    let gain = klingt.add(Gain::new(1.0));
+
+    assert_eq!(
+        klingt.try_connect_ports(&sine, 1, &gain, 0),
+        Err(KlingtError::InvalidOutputPort { port: 1, num_outputs: 1 })
+    );
+    assert_eq!(
+        klingt.try_connect_ports(&sine, 0, &gain, 3),
+        Err(KlingtError::InvalidInputPort { port: 3, num_inputs: 1 })
+    );
+    assert_eq!(klingt.try_connect_ports(&sine, 0, &gain, 0), Ok(()));
+}
+
+#[test]
+fn render_plays_the_last_output() {
+    let mut klingt = Klingt::offline(48000);
+    let quiet = klingt.add(SamplePlayer::new(vec![0.25; 4800], 1, 48000));
+    let loud = klingt.add(SamplePlayer::new(vec![0.5; 4800], 1, 48000));
+    klingt.output(&quiet);
+    klingt.output(&loud);
+    assert!(klingt.render(64).iter().all(|&s| s == 0.5));
+}
+
+#[test]
+fn try_add_at_rate_rejects_zero_rate() {
+    let mut klingt = Klingt::offline(48000);
+    assert!(matches!(klingt.try_add_at_rate(Gain::new(1.0), 0), Err(KlingtError::InvalidSampleRate)));
+
+    // The rejected node left no trace behind
+    let sine = klingt.add(Sine::new(440.0));
+    klingt.output(&sine);
+    assert!(peak(&klingt.render(64)) > 0.0);
+}
+
+#[test]
+fn try_add_mixes_channel_counts_at_one_rate() {
+    let mut klingt = Klingt::offline(48000).with_channels(6);
+    let _stereo = klingt.add(SamplePlayer::new(vec![0.0; 4410 * 2], 2, 44100));
+
+    // Bridges are as wide as the node they carry, whatever else runs at that rate
+    let player = klingt.try_add(channel_player(6, 44100)).unwrap();
+    klingt.output(&player);
+
+    let output = klingt.render(4800);
+    for frame in output[output.len() / 2..].chunks(6) {
+        for (c, &s) in frame.iter().enumerate() {
+            assert!((s - (c + 1) as f32 / 32.0).abs() < 1e-6, "channel {}: {}", c, s);
+        }
+    }
+}
+
+#[test]
+fn try_add_reports_full_queue() {
+    let (mut controller, mut processor) = Klingt::offline(48000).split();
+
+    // Nobody is applying commands, so the queue eventually runs out
+    let mut handles = Vec::new();
+    let error = loop {
+        match controller.try_add(Sine::new(440.0)) {
+            Ok(handle) => handles.push(handle),
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(error, KlingtError::CommandQueueFull);
+
+    // Once the processor catches up, edits go through again
+    processor.process();
+    let sine = controller.try_add(Sine::new(440.0)).unwrap();
+    controller.try_output(&sine).unwrap();
+    assert!(peak(&processor.render(64)) > 0.0);
+}
+
+#[test]
+fn try_disconnect_reports_full_queue() {
+    let (mut controller, mut processor) = Klingt::offline(48000).split();
+    let sine = controller.add(Sine::new(440.0));
+    let gain = controller.add(Gain::new(1.0).with_channels(1));
+    controller.connect(&sine, &gain);
+    controller.output(&sine);
+
+    let mut handles = Vec::new();
+    while let Ok(handle) = controller.try_add(Sine::new(440.0)) {
+        handles.push(handle);
+    }
+    assert_eq!(controller.try_disconnect(&sine, &gain), Err(KlingtError::CommandQueueFull));
+    assert_eq!(controller.try_disconnect_all(&sine), Err(KlingtError::CommandQueueFull));
+
+    processor.process();
+    assert_eq!(controller.try_disconnect(&sine, &gain), Ok(()));
+    assert_eq!(controller.try_disconnect_all(&sine), Ok(()));
+    assert_eq!(peak(&processor.render(64)), 0.0);
+}
+
+#[test]
+fn try_remove_hands_back_the_handle_on_full_queue() {
+    let (mut controller, mut processor) = Klingt::offline(48000).split();
+    let sine = controller.add(Sine::new(440.0));
+    controller.output(&sine);
+
+    let mut handles = Vec::new();
+    while let Ok(handle) = controller.try_add(Sine::new(440.0)) {
+        handles.push(handle);
+    }
+    let sine = match controller.try_remove(sine) {
+        Err(sine) => sine,
+        Ok(()) => panic!("Removed with a full queue"),
+    };
+
+    processor.process();
+    assert!(controller.try_remove(sine).is_ok());
+    assert_eq!(peak(&processor.render(64)), 0.0);
+}
+
+#[test]
+fn description_rollback_fits_in_the_queue() {
+    let registry = NodeRegistry::new();
+    // Builds a sub-graph, bridges and channel mixers before failing on the cycle
+    let patch = GraphDescription::new()
+        .with_node(NodeDescription::new("player", "SamplePlayer")
+            .with_param("samples", vec![0.5f32; 4410])
+            .with_param("sample_rate", 44100u32))
+        .with_node(NodeDescription::new("a", "Gain"))
+        .with_node(NodeDescription::new("b", "Gain"))
+        .with_connection(ConnectionDescription::new("player", "a"))
+        .with_connection(ConnectionDescription::new("a", "b"))
+        .with_connection(ConnectionDescription::new("b", "a"))
+        .with_output("b");
+
+    let (mut controller, _processor) = Klingt::offline(48000).split();
+    let mut capacity = 0;
+    while controller.try_add(Sine::new(440.0)).is_ok() {
+        capacity += 1;
+    }
+
+    // However little room is left, the patch is either rolled back or never started
+    for room in 0..=150 {
+        let (mut controller, _processor) = Klingt::offline(48000).split();
+        for _ in 0..capacity - room {
+            controller.try_add(Sine::new(440.0)).unwrap();
+        }
+        let before = controller.to_dot();
+        let result = controller.try_from_description(&patch, &registry);
+        assert!(matches!(result, Err(KlingtError::CommandQueueFull) | Err(KlingtError::Cycle { .. })));
+        assert_eq!(controller.to_dot(), before);
+        if room == 150 {
+            assert!(matches!(result, Err(KlingtError::Cycle { .. })));
+        }
+    }
+}
+
+#[test]
+fn large_descriptions_fit_in_the_queue() {
+    // A hundred same-rate connections, fanned out and mixed back together
+    let mut patch = GraphDescription::new()
+        .with_node(NodeDescription::new("osc", "Sine").with_param("frequency", 440.0))
+        .with_node(NodeDescription::new("split", "Gain"))
+        .with_node(NodeDescription::new("mix", "Mixer"))
+        .with_connection(ConnectionDescription::new("osc", "split"))
+        .with_output("mix");
+    for i in 0..50 {
+        let name = format!("voice{}", i);
+        patch = patch
+            .with_node(NodeDescription::new(name.as_str(), "Gain").with_param("gain", 0.02).with_param("smoothing", false))
+            .with_connection(ConnectionDescription::new("split", name.as_str()))
+            .with_connection(ConnectionDescription::new(name.as_str(), "mix"));
+    }
+
+    let (mut controller, mut processor) = Klingt::offline(48000).split();
+    let handles = controller.try_from_description(&patch, &NodeRegistry::new()).unwrap();
+    assert_eq!(handles.names().count(), 53);
+    assert!(peak(&processor.render(4800)) > 0.0);
+}
+
+/// Render `frames` of a constant 0.5 played at `main_rate`, sent through a gain
+/// running at `effect_rate` and back to the output
+fn render_through_subgraph(main_rate: u32, effect_rate: u32, block_size: usize, frames: usize) -> Vec<f32> {
+    let mut klingt = Klingt::offline(main_rate).with_block_size(block_size);
+    let player = klingt.add(SamplePlayer::new(vec![0.5f32; frames * 2], 2, main_rate));
+    let gain = klingt.add_at_rate(Gain::new(1.0), effect_rate);
+
+    klingt.connect(&player, &gain);
+    klingt.output(&gain);
+    klingt.render(frames)
+}
+
+#[test]
+fn main_graph_feeds_subgraph_without_dropouts() {
+    for &(main_rate, effect_rate) in &[(44100, 48000), (48000, 22050), (48000, 96000), (96000, 8000), (22050, 192000), (8000, 44100)] {
+        for &block_size in &[16, 64, 512] {
+            let samples = render_through_subgraph(main_rate, effect_rate, block_size, main_rate as usize / 2);
+
+            // Silent while the bridges fill up (about a block of each graph), then a steady signal
+            let start = samples.iter().position(|&s| s != 0.0).expect("no audio came through");
+            let effect_block = block_size * main_rate as usize / effect_rate as usize;
+            assert!(
+                start / 2 < 2 * block_size + 2 * effect_block + 64,
+                "{} -> {} Hz, block {}: {} samples of latency",
+                main_rate, effect_rate, block_size, start
+            );
+            // Skip the resamplers ramping in from silence
+            let ramp = samples[start..].iter().position(|&s| s == 0.5).unwrap();
+            let steady = &samples[start + ramp..];
+            assert!(
+                steady.iter().all(|&s| (s - 0.5).abs() < 1e-6),
+                "{} -> {} Hz, block {}: dropout",
+                main_rate, effect_rate, block_size
+            );
+        }
+    }
+}
+
+#[test]
+fn effect_chain_in_subgraph() {
+    let mut klingt = Klingt::offline(44100);
+    let player = klingt.add(SamplePlayer::new(vec![0.5f32; 44100 * 2], 2, 44100));
+    let first = klingt.add_at_rate(Gain::new(0.5), 48000);
+    let second = klingt.add_at_rate(Gain::new(0.5), 48000);
+
+    klingt.connect(&player, &first);
+    klingt.connect(&first, &second);
+    klingt.output(&second);
+
+    let samples = klingt.render(22050);
+    assert!((peak(&samples[22050..]) - 0.125).abs() < 1e-6);
+}
+
+#[test]
+fn disconnect_main_to_subgraph() {
+    let mut klingt = Klingt::offline(44100);
+    let player = klingt.add(SamplePlayer::new(vec![0.5f32; 44100 * 2], 2, 44100));
+    let gain = klingt.add_at_rate(Gain::new(1.0), 48000);
+    klingt.connect(&player, &gain);
+    klingt.output(&gain);
+    assert!(peak(&klingt.render(4480)) > 0.4);
+
+    // Gain keeps its last output without input, so route a fresh one
+    klingt.disconnect(&player, &gain);
+    klingt.remove(gain);
+    let gain = klingt.add_at_rate(Gain::new(1.0), 48000);
+    klingt.output(&gain);
+    assert_eq!(peak(&klingt.render(4480)), 0.0);
+}
+
+/// Steady part of a rendered constant signal, panicking on dropouts
+fn assert_steady(samples: &[f32], level: f32, what: &str) {
+    let start = samples.iter().position(|&s| s != 0.0).unwrap_or_else(|| panic!("{}: no audio came through", what));
+    // Skip the resamplers ramping in from silence
+    let ramp = samples[start..].iter().position(|&s| (s - level).abs() < 1e-6).unwrap();
+    assert!(
+        samples[start + ramp..].iter().all(|&s| (s - level).abs() < 1e-6),
+        "{}: dropout",
+        what
+    );
+}
+
+#[test]
+fn subgraph_feeds_subgraph() {
+    for &block_size in &[16, 64, 512] {
+        let mut klingt = Klingt::offline(48000).with_block_size(block_size);
+        let player = klingt.add(SamplePlayer::new(vec![0.5f32; 22050 * 2], 2, 22050));
+        let gain = klingt.add_at_rate(Gain::new(1.0), 32000);
+
+        klingt.connect(&player, &gain);
+        klingt.output(&gain);
+
+        assert_steady(&klingt.render(24000), 0.5, &format!("block {}", block_size));
+    }
+}
+
+#[test]
+fn main_graph_feeds_chain_of_subgraphs() {
+    for &block_size in &[16, 64, 512] {
+        let mut klingt = Klingt::offline(44100).with_block_size(block_size);
+        let player = klingt.add(SamplePlayer::new(vec![0.5f32; 44100 * 2], 2, 44100));
+        let first = klingt.add_at_rate(Gain::new(0.5), 96000);
+        let second = klingt.add_at_rate(Gain::new(0.5), 22050);
+
+        klingt.connect(&player, &first);
+        klingt.connect(&first, &second);
+        klingt.output(&second);
+
+        assert_steady(&klingt.render(22050), 0.125, &format!("block {}", block_size));
+    }
+}
+
+#[test]
+fn disconnect_subgraph_from_subgraph() {
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(SamplePlayer::new(vec![0.5f32; 22050 * 2], 2, 22050));
+    let gain = klingt.add_at_rate(Gain::new(1.0), 32000);
+    klingt.connect(&player, &gain);
+    klingt.output(&gain);
+    assert!(peak(&klingt.render(4800)) > 0.4);
+
+    // Gain keeps its last output without input, so route a fresh one
+    klingt.disconnect(&player, &gain);
+    klingt.remove(gain);
+    let gain = klingt.add_at_rate(Gain::new(1.0), 32000);
+    klingt.output(&gain);
+    assert_eq!(peak(&klingt.render(4800)), 0.0);
+}
+
+#[test]
+fn feedback_loop_repeats_every_block() {
+    let mut klingt = Klingt::offline(48000);
+    let mut impulse = vec![0.0f32; 4800];
+    impulse[0] = 1.0;
+    let player = klingt.add(SamplePlayer::new(impulse, 1, 48000));
+    let mixer = klingt.add(Mixer::mono());
+    let decay = klingt.add(Gain::new(0.5).without_smoothing());
+
+    klingt.connect(&player, &mixer);
+    klingt.connect(&mixer, &decay);
+    klingt.connect_feedback(&decay, &mixer);
+    klingt.output(&mixer);
+
+    let left: Vec<f32> = klingt.render(256).iter().step_by(2).copied().collect();
+    for (i, &s) in left.iter().enumerate() {
+        let expected = if i % 64 == 0 { 0.5f32.powi(i as i32 / 64) } else { 0.0 };
+        assert_eq!(s, expected, "frame {}", i);
+    }
+}
+
+#[test]
+fn feedback_into_itself() {
+    let mut klingt = Klingt::offline(48000).with_block_size(16);
+    let mut impulse = vec![0.0f32; 4800];
+    impulse[3] = 1.0;
+    let player = klingt.add(SamplePlayer::new(impulse, 1, 48000));
+    let mixer = klingt.add(Mixer::mono());
+
+    klingt.connect(&player, &mixer);
+    klingt.connect_feedback(&mixer, &mixer);
+    klingt.output(&mixer);
+
+    let left: Vec<f32> = klingt.render(64).iter().step_by(2).copied().collect();
+    for (i, &s) in left.iter().enumerate() {
+        assert_eq!(s, if i % 16 == 3 { 1.0 } else { 0.0 }, "frame {}", i);
+    }
+}
+
+#[test]
+fn connect_rejects_cycles() {
+    let mut klingt = Klingt::offline(48000);
+    let a = klingt.add(Gain::new(1.0));
+    let b = klingt.add(Gain::new(1.0));
+    let c = klingt.add(Gain::new(1.0));
+
+    klingt.connect(&a, &b);
+    klingt.connect(&b, &c);
+    assert!(matches!(klingt.try_connect(&c, &a), Err(KlingtError::Cycle { .. })));
+    assert!(matches!(klingt.try_connect(&a, &a), Err(KlingtError::Cycle { .. })));
+    assert_eq!(klingt.try_connect_feedback(&c, &a), Ok(()));
+
+    // Breaking the chain makes room for the edge
+    klingt.disconnect(&a, &b);
+    assert_eq!(klingt.try_connect(&c, &a), Ok(()));
+}
+
+#[test]
+#[should_panic(expected = "cycle")]
+fn connect_panics_on_cycles() {
+    let mut klingt = Klingt::offline(48000);
+    let a = klingt.add(Gain::new(1.0));
+    let b = klingt.add(Gain::new(1.0));
+
+    klingt.connect(&a, &b);
+    klingt.connect(&b, &a);
+}
+
+#[test]
+fn extra_sink_records_the_output() {
+    let reference = {
+        let mut klingt = Klingt::offline(48000);
+        let sine = klingt.add(Sine::new(440.0));
+        klingt.output(&sine);
+        klingt.render(4800)
+    };
+
+    let mut klingt = Klingt::offline(48000);
+    let sine = klingt.add(Sine::new(440.0));
+    let (producer, mut recording) = rtrb::RingBuffer::new(1 << 16);
+    let recorder = klingt.add_sink(RtrbSink::stereo(producer));
+    klingt.connect(&sine, &recorder);
+    klingt.output(&sine);
+
+    // The shared sine is processed once per block, or the output would differ
+    assert_eq!(klingt.render(4800), reference);
+    assert_eq!(drain(&mut recording), reference);
+
+    klingt.remove(recorder);
+    klingt.render(4800);
+    assert!(drain(&mut recording).is_empty());
+}
+
+#[test]
+fn sinks_run_without_an_output() {
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(SamplePlayer::new(vec![0.5f32; 22050 * 2], 2, 22050));
+    let (producer, mut first) = rtrb::RingBuffer::new(1 << 16);
+    let (other, mut second) = rtrb::RingBuffer::new(1 << 16);
+    let a = klingt.add_sink(RtrbSink::stereo(producer));
+    let b = klingt.add_sink(RtrbSink::stereo(other));
+    klingt.connect(&player, &a);
+    klingt.connect(&player, &b);
+
+    assert!(klingt.render(4800).iter().all(|&s| s == 0.0));
+    let first = drain(&mut first);
+    assert_eq!(first.len(), 4800 * 2);
+    assert!(peak(&first) > 0.4);
+    assert_eq!(first, drain(&mut second));
+}
+
+#[test]
+fn send_at_lands_on_exact_frame() {
+    use klingt::nodes::PlayerMessage;
+
+    for &block_size in &[16, 64, 256] {
+        let mut klingt = Klingt::offline(48000).with_block_size(block_size);
+        let ramp: Vec<f32> = (1..=1000).map(|i| i as f32).collect();
+        let mut player = klingt.add(SamplePlayer::new(ramp, 1, 48000));
+        klingt.output(&player);
+
+        player.send(PlayerMessage::Pause).unwrap();
+        player.send_at(100, PlayerMessage::Play).unwrap();
+        player.send_at(150, PlayerMessage::Pause).unwrap();
+
+        let left: Vec<f32> = klingt.render(512).iter().step_by(2).copied().collect();
+        for (i, &s) in left.iter().enumerate() {
+            let expected = if (100..150).contains(&i) { (i - 99) as f32 } else { 0.0 };
+            assert_eq!(s, expected, "block {}, frame {}", block_size, i);
+        }
+    }
+}
+
+#[test]
+fn send_at_lands_on_time_in_subgraphs() {
+    use klingt::nodes::PlayerMessage;
+
+    for &block_size in &[16, 64, 256] {
+        // Bridged before anything plays, and once the sub-graph is running ahead
+        for &connect_after in &[0, 3000] {
+            let mut klingt = Klingt::offline(48000).with_block_size(block_size);
+            let mut player = klingt.add(SamplePlayer::new(vec![0.5; 44100], 1, 44100));
+            player.send(PlayerMessage::Pause).unwrap();
+            let mut left: Vec<f32> = klingt.render(connect_after).iter().step_by(2).copied().collect();
+
+            klingt.output(&player);
+            player.send_at(6000, PlayerMessage::Play).unwrap();
+            left.extend(klingt.render(8000).iter().step_by(2));
+
+            // Give or take the frame the resampler interpolates over
+            let start = left.iter().position(|&s| s != 0.0).unwrap();
+            assert!((5999..=6000).contains(&start), "block {}, after {}: {}", block_size, connect_after, start);
+        }
+    }
+}
+
+#[test]
+fn send_at_splits_only_the_receiving_node() {
+    use klingt::nodes::GainMessage;
+
+    let frames: Vec<f32> = (0..4800).map(|i| i as f32).collect();
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(SamplePlayer::new(frames, 1, 48000));
+    let mut gain = klingt.add(Gain::new(1.0).without_smoothing());
+    klingt.connect(&player, &gain);
+    klingt.output(&gain);
+
+    // Two changes within one block, sent out of order
+    gain.send_at(90, GainMessage::SetGain(2.0)).unwrap();
+    gain.send_at(70, GainMessage::SetGain(0.0)).unwrap();
+
+    let left: Vec<f32> = klingt.render(256).iter().step_by(2).copied().collect();
+    for (i, &s) in left.iter().enumerate() {
+        let gain = match i {
+            0..=69 => 1.0,
+            70..=89 => 0.0,
+            _ => 2.0,
+        };
+        assert_eq!(s, i as f32 * gain, "frame {}", i);
+    }
+}
+
+#[test]
+fn send_at_in_the_past_applies_next_block() {
+    use klingt::nodes::GainMessage;
+
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(SamplePlayer::new(vec![1.0; 4800], 1, 48000));
+    let mut gain = klingt.add(Gain::new(1.0).without_smoothing());
+    klingt.connect(&player, &gain);
+    klingt.output(&gain);
+
+    klingt.render(128);
+    gain.send_at(10, GainMessage::SetGain(0.5)).unwrap();
+    assert!(klingt.render(64).iter().all(|&s| s == 0.5));
+}
+
+/// Outputs its sample clock, and reports the time of every buffer it processes
+struct Clock(Option<std::sync::mpsc::Sender<f64>>);
+
+impl AudioNode for Clock {
+    type Message = ();
+    type Event = ();
+    type State = ();
+
+    fn process(
+        &mut self,
+        ctx: &ProcessContext,
+        messages: impl Iterator<Item = ()>,
+        _inputs: &[Input],
+        outputs: &mut [Buffer],
+    ) {
+        messages.for_each(drop);
+        for (i, s) in outputs[0].iter_mut().enumerate() {
+            *s = (ctx.frame + i as u64) as Sample;
+        }
+        if let Some(sender) = &self.0 {
+            sender.send(ctx.seconds()).unwrap();
+        }
+    }
+}
+
+#[test]
+fn process_context_counts_frames() {
+    for &block_size in &[16, 64, 1024] {
+        let mut klingt = Klingt::offline(48000).with_block_size(block_size);
+        let mut clock = klingt.add(Clock(None));
+        klingt.output(&clock);
+
+        // Splitting a block doesn't disturb the count
+        clock.send_at(1000, ()).unwrap();
+
+        let left: Vec<f32> = klingt.render(4096).iter().step_by(2).copied().collect();
+        assert!(left.iter().enumerate().all(|(i, &s)| s == i as f32), "block {}", block_size);
+    }
+}
+
+#[test]
+fn subgraph_clock_agrees_with_output() {
+    let (sender, times) = std::sync::mpsc::channel();
+    let mut klingt = Klingt::offline(48000);
+    klingt.render(4800);
+
+    let clock = klingt.add_at_rate(Clock(Some(sender)), 24000);
+    klingt.output(&clock);
+    klingt.render(4800);
+
+    let times: Vec<f64> = times.try_iter().collect();
+    // Created a tenth of a second in
+    assert!((times[0] - 0.1).abs() < 64.0 / 48000.0, "starts at {}", times[0]);
+    for pair in times.windows(2) {
+        assert!((pair[1] - pair[0] - 64.0 / 24000.0).abs() < 1e-9);
+    }
+}
+
+/// Frames at which the metronome starts a click, and whether it's accented
+fn clicks(samples: &[f32]) -> Vec<(usize, bool)> {
+    let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+    left.iter()
+        .enumerate()
+        .filter(|&(_, &s)| s == 0.5)
+        // The accent is higher, so it falls faster
+        .map(|(i, _)| (i, left[i + 1] < 0.5 * (std::f32::consts::TAU * 1200.0 / 48000.0).cos()))
+        .collect()
+}
+
+#[test]
+fn metronome_follows_transport() {
+    use klingt::nodes::Metronome;
+
+    for &block_size in &[64, 112] {
+        let mut klingt = Klingt::offline(48000).with_block_size(block_size);
+        let click = klingt.add(Metronome::new());
+        klingt.output(&click);
+
+        // Silent until started
+        assert_eq!(peak(&klingt.render(4800)), 0.0);
+        klingt.seek_transport(0.0);
+        klingt.start_transport();
+        // The transport starts with the next block
+        let start = 4800_usize.div_ceil(block_size) * block_size;
+        klingt.render(start - 4800);
+
+        let expected: Vec<(usize, bool)> = (0..6).map(|beat| (beat * 24000, beat % 4 == 0)).collect();
+        assert_eq!(clicks(&klingt.render(24000 * 6)), expected, "block {}", block_size);
+    }
+}
+
+#[test]
+fn metronome_in_subgraph_clicks_on_the_beat() {
+    use klingt::nodes::Metronome;
+
+    let mut klingt = Klingt::offline(48000);
+    let click = klingt.add_at_rate(Metronome::new(), 44100);
+    klingt.start_transport();
+
+    // Connected once its graph is running ahead of the output
+    let mut output = klingt.render(4800);
+    klingt.output(&click);
+    output.extend(klingt.render(24000 * 5 + 12000 - 4800));
+
+    // Resampled clicks don't hit 0.5 exactly, so look for where each one starts
+    let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+    let onsets: Vec<usize> = (1..left.len())
+        .filter(|&i| left[i].abs() > 0.1 && left[i.saturating_sub(1000)..i].iter().all(|s| s.abs() <= 0.1))
+        .collect();
+    // The first beat went by before the metronome was connected
+    assert_eq!(onsets.len(), 5, "{:?}", onsets);
+    for (beat, &onset) in (1..).zip(&onsets) {
+        assert!(onset.abs_diff(beat * 24000) <= 1, "beat {}: {}", beat, onset);
+    }
+}
+
+#[test]
+fn metronome_follows_tempo_changes_and_time_signature() {
+    use klingt::nodes::Metronome;
+    use klingt::{TempoMap, TimeSignature};
+
+    let mut klingt = Klingt::offline(48000).with_block_size(48);
+    let click = klingt.add(Metronome::new());
+    klingt.output(&click);
+    // 32000 frames per beat, 12000 after beat 1
+    klingt.set_tempo_map(TempoMap::new(90.0).with_change(1.0, 240.0));
+    klingt.set_time_signature(TimeSignature::new(3, 4));
+    klingt.start_transport();
+
+    let expected = vec![(0, true), (32000, false), (44000, false), (56000, true), (68000, false)];
+    assert_eq!(clicks(&klingt.render(70000)), expected);
+}
+
+/// Outputs the transport position
+struct BeatReporter;
+
+impl AudioNode for BeatReporter {
+    type Message = ();
+    type Event = ();
+    type State = ();
+
+    fn process(
+        &mut self,
+        ctx: &ProcessContext,
+        _messages: impl Iterator<Item = ()>,
+        _inputs: &[Input],
+        outputs: &mut [Buffer],
+    ) {
+        for (i, s) in outputs[0].iter_mut().enumerate() {
+            *s = ctx.transport.beat_at(i) as Sample;
+        }
+    }
+}
+
+#[test]
+fn transport_loops_on_exact_frame() {
+    let mut klingt = Klingt::offline(48000).with_block_size(112);
+    let beats = klingt.add(BeatReporter);
+    klingt.output(&beats);
+    klingt.set_loop(Some(1.0..3.0));
+    klingt.start_transport();
+
+    let left: Vec<f32> = klingt.render(48000 * 3).iter().step_by(2).copied().collect();
+    for (i, &beat) in left.iter().enumerate() {
+        // 24000 frames per beat: straight to beat 3 at frame 72000, then back to 1 every two beats
+        let expected = if i < 72000 { i as f32 / 24000.0 } else { 1.0 + ((i - 72000) % 48000) as f32 / 24000.0 };
+        assert!((beat - expected).abs() < 1e-4, "frame {}: beat {}, expected {}", i, beat, expected);
+    }
+}
+
+#[test]
+fn transport_stop_and_seek() {
+    let mut klingt = Klingt::offline(48000);
+    let beats = klingt.add(BeatReporter);
+    klingt.output(&beats);
+
+    klingt.start_transport();
+    klingt.render(24000);
+    klingt.stop_transport();
+    assert!(klingt.render(640).iter().all(|&b| b == 1.0));
+
+    klingt.seek_transport(8.0);
+    assert!(klingt.render(640).iter().all(|&b| b == 8.0));
+
+    klingt.start_transport();
+    let left: Vec<f32> = klingt.render(640).iter().step_by(2).copied().collect();
+    assert_eq!(left[0], 8.0);
+    assert_eq!(left[480], 8.02);
+}
+
+#[test]
+fn player_reports_when_finished() {
+    for &rate in &[48000, 44100] {
+        let mut klingt = Klingt::offline(48000);
+        let mut player = klingt.add(SamplePlayer::new(vec![0.5; 1000], 1, rate));
+        klingt.output(&player);
+
+        klingt.render(640);
+        assert_eq!(player.poll_events().count(), 0);
+        klingt.render(960);
+        assert_eq!(player.poll_events().collect::<Vec<_>>(), vec![PlayerEvent::Finished]);
+        klingt.render(640);
+        assert_eq!(player.poll_events().count(), 0);
+    }
+}
+
+/// Reports the frame of each buffer it processes
+struct FrameReporter(Option<u64>);
+
+impl AudioNode for FrameReporter {
+    type Message = ();
+    type Event = u64;
+    type State = ();
+
+    fn process(&mut self, ctx: &ProcessContext, _: impl Iterator<Item = ()>, _: &[Input], _: &mut [Buffer]) {
+        self.0 = Some(ctx.frame);
+    }
+
+    fn next_event(&mut self) -> Option<u64> {
+        self.0.take()
+    }
+}
+
+#[test]
+fn events_arrive_in_order_and_wait_while_queue_is_full() {
+    let mut klingt = Klingt::offline(48000);
+    let mut reporter = klingt.add(FrameReporter(None));
+    klingt.output(&reporter);
+
+    klingt.render(640);
+    let frames: Vec<u64> = reporter.poll_events().collect();
+    assert_eq!(frames, (0..10).map(|b| b * 64).collect::<Vec<_>>());
+
+    // Only as many as the queue holds, oldest first
+    klingt.render(6400);
+    let frames: Vec<u64> = reporter.poll_events().collect();
+    assert_eq!(frames.len(), 64);
+    assert_eq!(frames[0], 640);
+}
+
+#[test]
+fn state_follows_playback_and_messages() {
+    let mut klingt = Klingt::offline(48000);
+    let mut player = klingt.add(SamplePlayer::new(vec![0.5; 48000], 1, 48000));
+    let mut sine = klingt.add(Sine::new(440.0));
+    let mut gain = klingt.add(Gain::new(0.5).without_smoothing());
+    let mixer = klingt.add(Mixer::stereo());
+    klingt.connect(&player, &gain);
+    klingt.connect(&gain, &mixer);
+    klingt.connect(&sine, &mixer);
+    klingt.output(&mixer);
+
+    // The initial state, before anything is processed
+    assert_eq!(player.state().unwrap().position_secs, 0.0);
+    assert_eq!(sine.state().unwrap().frequency, 440.0);
+
+    klingt.render(24000);
+    let state = *player.state().unwrap();
+    assert!(state.playing);
+    assert_eq!(state.duration_secs, 1.0);
+    assert!((state.position_secs - 0.5).abs() < 0.01);
+
+    sine.send(SineMessage::SetFrequency(880.0)).unwrap();
+    gain.send(GainMessage::SetGain(0.25)).unwrap();
+    klingt.render(64);
+    assert_eq!(sine.state().unwrap().frequency, 880.0);
+    assert_eq!(gain.state().unwrap().gain, 0.25);
+
+    klingt.render(48000);
+    assert!(!player.state().unwrap().playing);
+}
+
+#[test]
+fn nodes_without_state_have_none() {
+    let mut klingt = Klingt::offline(48000);
+    let mut mixer = klingt.add(Mixer::stereo());
+    assert!(mixer.state().is_none());
+}
+
+/// Publishes how many buffers it processed, twice over
+struct Counter(u64);
+
+impl AudioNode for Counter {
+    type Message = ();
+    type Event = ();
+    type State = (u64, u64);
+
+    fn process(&mut self, _: &ProcessContext, _: impl Iterator<Item = ()>, _: &[Input], _: &mut [Buffer]) {
+        self.0 += 1;
+    }
+
+    fn state(&self) -> Option<(u64, u64)> {
+        Some((self.0, self.0 * 2))
+    }
+}
+
+#[test]
+fn state_reads_are_whole_and_move_forward() {
+    let (mut controller, mut processor) = Klingt::offline(48000).split();
+    let mut counter = controller.add(Counter(0));
+    controller.output(&counter);
+
+    let audio = std::thread::spawn(move || processor.render(64 * 20000));
+
+    let mut last = 0;
+    while last < 20000 {
+        let (count, double) = *counter.state().unwrap();
+        assert_eq!(double, count * 2);
+        assert!(count >= last);
+        last = count;
+    }
+    audio.join().unwrap();
+}
+
+#[test]
+fn to_dot_shows_graphs_and_bridges() {
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(SamplePlayer::new(vec![0.0; 44100], 2, 44100));
+    let gain = klingt.add(Gain::new(0.5));
+    let sine = klingt.add(Sine::new(3.0));
+    let mixer = klingt.add(Mixer::stereo());
+    klingt.connect(&player, &gain);
+    klingt.connect_ports(&sine, 0, &mixer, 1);
+    klingt.connect(&gain, &mixer);
+    klingt.connect_feedback(&mixer, &gain);
+    klingt.output(&mixer);
+
+    let dot = klingt.to_dot();
+    assert!(dot.starts_with("digraph klingt {"));
+    assert!(dot.contains("label=\"main graph (48000 Hz)\""));
+    assert!(dot.contains("label=\"sub-graph (44100 Hz)\""));
+    assert!(dot.contains("label=\"SamplePlayer\\n0 in, 2 out\""));
+    assert!(dot.contains("label=\"Mixer\\nany in, 2 out\""));
+    assert!(dot.contains("label=\"NullSink\\n1 in, 0 out\\noutput\", peripheries=2"));
+    assert!(dot.contains("label=\"RtrbSink\\n2 ch to 48000 Hz\""));
+    assert!(dot.contains("label=\"ResamplingSource\\n2 ch from 44100 Hz\""));
+    assert!(dot.contains("[style=bold, label=\"44100 -> 48000 Hz\"]"));
+    assert!(dot.contains("[label=\"0 -> 1\"]"));
+    assert!(dot.contains("[style=dashed, label=\"feedback\"]"));
+
+    // The bridge goes away with the last node of the sub-graph
+    klingt.remove(player);
+    let dot = klingt.to_dot();
+    assert!(!dot.contains("44100"));
+    assert!(!dot.contains("RtrbSink"));
+}
+
+#[test]
+fn description_builds_named_nodes() {
+    let patch = GraphDescription::new()
+        .with_node(NodeDescription::new("osc", "Sine").with_param("frequency", 440.0).with_param("amplitude", 0.5))
+        .with_node(NodeDescription::new("vol", "Gain").with_param("gain", 0.5).with_param("smoothing", false))
+        .with_connection(ConnectionDescription::new("osc", "vol"))
+        .with_output("vol");
+
+    let mut klingt = Klingt::offline(48000);
+    let mut handles = klingt.from_description(&patch, &NodeRegistry::new());
+    assert!((peak(&klingt.render(4800)) - 0.25).abs() < 1e-3);
+
+    // Handles come back by name, typed
+    assert!(handles.get::<Gain>("osc").is_none());
+    handles.get_mut::<Gain>("vol").unwrap().send(GainMessage::SetGain(1.0)).unwrap();
+    klingt.render(64);
+    assert!((peak(&klingt.render(4800)) - 0.5).abs() < 1e-3);
+
+    let vol = handles.take::<Gain>("vol").unwrap();
+    klingt.remove(vol);
+    assert_eq!(peak(&klingt.render(64)), 0.0);
+    assert_eq!(handles.names().collect::<Vec<_>>(), vec!["osc"]);
+}
+
+/// Outputs the same value on every sample
+struct Constant(Sample);
+
+impl AudioNode for Constant {
+    type Message = ();
+    type Event = ();
+    type State = ();
+
+    fn process(&mut self, _ctx: &ProcessContext, _messages: impl Iterator<Item = ()>, _inputs: &[Input], outputs: &mut [Buffer]) {
+        outputs[0].fill(self.0);
+    }
+
+    fn num_outputs(&self) -> usize { 1 }
+}
+
+#[test]
+fn description_with_custom_nodes_and_ports() {
+    let mut registry = NodeRegistry::new();
+    registry.register("Constant", |params| {
+        let value = params.number("value")? as Sample;
+        Ok(Constant(value))
+    });
+
+    let patch = GraphDescription::new()
+        .with_node(NodeDescription::new("dc", "Constant").with_param("value", 0.25))
+        .with_node(NodeDescription::new("player", "SamplePlayer")
+            .with_param("samples", vec![0.5f32; 44100])
+            .with_param("sample_rate", 44100u32))
+        .with_node(NodeDescription::new("mix", "Mixer"))
+        .with_connection(ConnectionDescription::new("dc", "mix").with_ports(0, 1))
+        .with_connection(ConnectionDescription::new("player", "mix"))
+        .with_output("mix");
+
+    let mut klingt = Klingt::offline(48000);
+    let handles = klingt.from_description(&patch, &registry);
+    let samples = klingt.render(4800);
+    assert!((samples[9000] - 0.75).abs() < 1e-3);
+
+    // Same handle type, different nodes
+    assert!(handles.get::<Constant>("dc").is_some());
+    assert!(handles.get::<Mixer>("dc").is_none());
+    assert!(handles.get::<Constant>("mix").is_none());
+}
+
+#[test]
+fn description_errors_leave_graph_untouched() {
+    let registry = NodeRegistry::new();
+    let sine = |name: &str| NodeDescription::new(name, "Sine").with_param("frequency", 440.0);
+    let mut klingt = Klingt::offline(48000);
+    let before = klingt.to_dot();
+
+    let unknown_type = GraphDescription::new().with_node(NodeDescription::new("a", "Saw"));
+    assert_eq!(klingt.try_from_description(&unknown_type, &registry).err(), Some(KlingtError::UnknownNodeType("Saw".into())));
+
+    let duplicate = GraphDescription::new().with_node(sine("a")).with_node(sine("a"));
+    assert_eq!(klingt.try_from_description(&duplicate, &registry).err(), Some(KlingtError::DuplicateName("a".into())));
+
+    let unknown_name = GraphDescription::new().with_node(sine("a")).with_output("b");
+    assert_eq!(klingt.try_from_description(&unknown_name, &registry).err(), Some(KlingtError::UnknownName("b".into())));
+
+    let missing = GraphDescription::new().with_node(NodeDescription::new("a", "Sine"));
+    assert!(matches!(
+        klingt.try_from_description(&missing, &registry),
+        Err(KlingtError::InvalidParam { ref param, .. }) if param == "frequency"
+    ));
+    let wrong_type = GraphDescription::new().with_node(NodeDescription::new("a", "Sine").with_param("frequency", "high"));
+    assert!(matches!(klingt.try_from_description(&wrong_type, &registry), Err(KlingtError::InvalidParam { .. })));
+
+    // Fails after adding the nodes - they're removed again
+    let cycle = GraphDescription::new()
+        .with_node(NodeDescription::new("a", "Gain"))
+        .with_node(NodeDescription::new("b", "Gain"))
+        .with_connection(ConnectionDescription::new("a", "b"))
+        .with_connection(ConnectionDescription::new("b", "a"));
+    assert!(matches!(klingt.try_from_description(&cycle, &registry), Err(KlingtError::Cycle { .. })));
+    assert_eq!(klingt.to_dot(), before);
+
+    let feedback = GraphDescription::new()
+        .with_node(NodeDescription::new("a", "Gain"))
+        .with_node(NodeDescription::new("b", "Gain"))
+        .with_connection(ConnectionDescription::new("a", "b"))
+        .with_connection(ConnectionDescription::new("b", "a").with_feedback());
+    assert!(klingt.try_from_description(&feedback, &registry).is_ok());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn description_from_json() {
+    let json = r#"{
+        "nodes": [
+            { "name": "osc", "type": "Sine", "params": { "frequency": 440 } },
+            { "name": "vol", "type": "Gain", "params": { "gain": 0.5, "smoothing": false } },
+            { "name": "mix", "type": "Mixer", "params": { "channels": 2 } }
+        ],
+        "connections": [
+            { "from": "osc", "to": "vol" },
+            { "from": "vol", "to": "mix", "ports": [0, 1] }
+        ],
+        "output": ["mix"]
+    }"#;
+    let patch: GraphDescription = serde_json::from_str(json).unwrap();
+    assert_eq!(patch.nodes[0].kind, "Sine");
+    assert_eq!(patch.connections[1].ports, Some((0, 1)));
+
+    let round_trip: GraphDescription = serde_json::from_str(&serde_json::to_string(&patch).unwrap()).unwrap();
+    assert_eq!(round_trip, patch);
+
+    let mut klingt = Klingt::offline(48000);
+    klingt.from_description(&patch, &NodeRegistry::new());
+    assert!((peak(&klingt.render(4800)) - 0.125).abs() < 1e-3);
+}
+
+/// Voices into a mixer, with a bit of everything that makes processing order matter
+#[cfg(feature = "std")]
+fn render_voices(threads: usize) -> (Vec<f32>, Vec<PlayerEvent>) {
+    use klingt::nodes::Metronome;
+    use klingt::TempoMap;
+
+    let mut klingt = Klingt::offline(48000).with_threads(threads);
+    let mixer = klingt.add(Mixer::stereo());
+    let master = klingt.add(Gain::new(0.1).without_smoothing());
+
+    for i in 0..16 {
+        let mut sine = klingt.add(Sine::new(110.0 * (i + 1) as f32));
+        let gain = klingt.add(Gain::new(1.0 / (i + 1) as f32));
+        klingt.connect(&sine, &gain);
+        klingt.connect_ports(&gain, 0, &mixer, i % 2);
+        // Split some voices' blocks
+        sine.send_at(100 * i as u64 + 7, SineMessage::SetFrequency(220.0)).unwrap();
+    }
+
+    // A resampled voice, whose end gets reported
+    let mut player = klingt.add(SamplePlayer::new(vec![0.5; 4410], 1, 44100));
+    klingt.connect(&player, &mixer);
+
+    // A feedback loop
+    let echo = klingt.add(Gain::new(0.5).without_smoothing());
+    klingt.connect(&mixer, &echo);
+    klingt.connect_feedback(&echo, &mixer);
+
+    // Tempo changes split everything now and then
+    let click = klingt.add(Metronome::new());
+    klingt.connect(&click, &mixer);
+    klingt.set_tempo_map(TempoMap::new(300.0).with_change(4.0, 450.0));
+    klingt.start_transport();
+
+    klingt.connect(&mixer, &master);
+    klingt.output(&master);
+
+    let samples = klingt.render(48000);
+    (samples, player.poll_events().collect())
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn parallel_processing_matches_serial() {
+    let (serial, serial_events) = render_voices(1);
+    let (parallel, parallel_events) = render_voices(4);
+
+    assert!(peak(&serial) > 0.1);
+    assert_eq!(serial, parallel);
+    assert_eq!(serial_events, parallel_events);
+    assert_eq!(parallel_events, vec![PlayerEvent::Finished]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn threads_are_configurable() {
+    assert_eq!(Klingt::offline(48000).threads(), 1);
+    assert_eq!(Klingt::offline(48000).with_threads(3).threads(), 3);
+
+    // Works after splitting too, and with nothing to process
+    let (_, mut processor) = Klingt::offline(48000).with_threads(2).split();
+    assert_eq!(processor.threads(), 2);
+    assert_eq!(peak(&processor.render(4800)), 0.0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[should_panic(expected = "node panicked")]
+fn parallel_processing_propagates_panics() {
+    struct Panicky;
+
+    impl AudioNode for Panicky {
+        type Message = ();
+        type Event = ();
+        type State = ();
+
+        fn process(&mut self, _ctx: &ProcessContext, _messages: impl Iterator<Item = ()>, _inputs: &[Input], _outputs: &mut [Buffer]) {
+            panic!("node panicked");
+        }
+
+        fn num_outputs(&self) -> usize { 1 }
+    }
+
+    let mut klingt = Klingt::offline(48000).with_threads(4);
+    let mixer = klingt.add(Mixer::stereo());
+    for _ in 0..8 {
+        let voice = klingt.add(Sine::new(440.0));
+        klingt.connect(&voice, &mixer);
+    }
+    let panicky = klingt.add(Panicky);
+    klingt.connect(&panicky, &mixer);
+    klingt.output(&mixer);
+    klingt.render(64);
+}
+
+#[test]
+fn long_and_short_paths_into_one_node() {
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(SamplePlayer::new(vec![1.0; 48000], 1, 48000));
+    let mixer = klingt.add(Mixer::mono());
+
+    // The player's output is still needed after the chain reuses buffers
+    let mut last = klingt.add(Gain::new(0.5).without_smoothing());
+    klingt.connect(&player, &last);
+    for _ in 0..3 {
+        let gain = klingt.add(Gain::new(0.5).without_smoothing());
+        klingt.connect(&last, &gain);
+        last = gain;
+    }
+    klingt.connect(&last, &mixer);
+    klingt.connect(&player, &mixer);
+
+    // Branches that reach nothing aren't processed
+    let unused = klingt.add(Gain::new(4.0).without_smoothing());
+    klingt.connect(&player, &unused);
+
+    klingt.output(&mixer);
+    assert!(klingt.render(256).iter().all(|&s| s == 1.0625));
+
+    // Changing the graph compiles a new plan
+    klingt.remove(last);
+    klingt.render(64);
+    assert!(klingt.render(256).iter().all(|&s| s == 1.0));
+    klingt.connect(&unused, &mixer);
+    klingt.render(64);
+    assert!(klingt.render(256).iter().all(|&s| s == 5.0));
+}
+
+/// Passes its input through and reports whether it was known to be silent
+struct SilenceProbe(Option<bool>);
+
+impl AudioNode for SilenceProbe {
+    type Message = ();
+    type Event = bool;
+    type State = ();
+
+    fn process(&mut self, _: &ProcessContext, _: impl Iterator<Item = ()>, inputs: &[Input], outputs: &mut [Buffer]) {
+        let input = &inputs[0];
+        self.0 = Some(input.is_silent());
+        for (output, buffer) in outputs.iter_mut().zip(input.buffers()) {
+            output.copy_from_slice(buffer);
+        }
+    }
+
+    fn num_inputs(&self) -> usize { 1 }
+
+    fn next_event(&mut self) -> Option<bool> {
+        self.0.take()
+    }
+}
+
+#[test]
+fn silence_propagates_through_effects() {
+    use klingt::nodes::PlayerMessage;
+
+    let mut klingt = Klingt::offline(48000);
+    let mut player = klingt.add(SamplePlayer::new(vec![0.5; 48000], 1, 48000));
+    let gain = klingt.add(Gain::new(0.5));
+    let mixer = klingt.add(Mixer::mono());
+    let sine = klingt.add(Sine::new(440.0));
+    let sine_mixer = klingt.add(Mixer::mono());
+    let mut probe = klingt.add(SilenceProbe(None));
+    klingt.connect(&player, &gain);
+    klingt.connect(&gain, &mixer);
+    klingt.connect(&mixer, &probe);
+    klingt.output(&probe);
+    klingt.connect(&sine, &sine_mixer);
+    let mut loud = klingt.add(SilenceProbe(None));
+    klingt.connect(&sine_mixer, &loud);
+    klingt.output(&loud);
+
+    player.send(PlayerMessage::Pause).unwrap();
+    klingt.render(256);
+    assert!(probe.poll_events().all(|silent| silent));
+    assert!(loud.poll_events().all(|silent| !silent));
+
+    player.send(PlayerMessage::Play).unwrap();
+    klingt.render(256);
+    assert!(probe.poll_events().all(|silent| !silent));
+}
+
+#[test]
+fn silence_does_not_change_output() {
+    use klingt::nodes::PlayerMessage;
+
+    let render = |pause: bool| {
+        let mut klingt = Klingt::offline(48000);
+        let mut player = klingt.add(SamplePlayer::new(vec![0.5; 4800], 1, 48000));
+        let mut gain = klingt.add(Gain::new(1.0));
+        klingt.connect(&player, &gain);
+        klingt.output(&gain);
+
+        // The gain keeps moving toward its target while it gets silence
+        gain.send(GainMessage::SetGain(0.25)).unwrap();
+        if pause {
+            player.send(PlayerMessage::Pause).unwrap();
+            player.send_at(1000, PlayerMessage::Play).unwrap();
+        } else {
+            player.send(PlayerMessage::SetVolume(0.0)).unwrap();
+            player.send_at(1000, PlayerMessage::SetVolume(1.0)).unwrap();
+        }
+        klingt.render(2048)
+    };
+
+    assert_eq!(render(true), render(false));
+}
+
+/// Outputs ones while awake, sleeps otherwise, and reports the frame of each buffer it processes
+struct Sleeper {
+    awake: bool,
+    processed: Option<u64>,
+}
+
+impl AudioNode for Sleeper {
+    type Message = bool;
+    type Event = u64;
+    type State = ();
+
+    fn process(&mut self, ctx: &ProcessContext, messages: impl Iterator<Item = bool>, _: &[Input], outputs: &mut [Buffer]) {
+        messages.for_each(|awake| self.awake = awake);
+        self.processed = Some(ctx.frame);
+        let value = if self.awake { 1.0 } else { 0.0 };
+        outputs[0].iter_mut().for_each(|s| *s = value);
+    }
+
+    fn next_event(&mut self) -> Option<u64> {
+        self.processed.take()
+    }
+
+    fn is_idle(&self) -> bool {
+        !self.awake
+    }
+}
+
+#[test]
+fn idle_nodes_sleep_until_a_message_is_due() {
+    let mut klingt = Klingt::offline(48000);
+    let mut sleeper = klingt.add(Sleeper { awake: false, processed: None });
+    klingt.output(&sleeper);
+
+    // Never processed while idle
+    assert!(klingt.render(256).iter().all(|&s| s == 0.0));
+    assert_eq!(sleeper.poll_events().count(), 0);
+
+    // Woken in the block the message is due in
+    sleeper.send_at(300, true).unwrap();
+    let left: Vec<f32> = klingt.render(256).iter().step_by(2).copied().collect();
+    for (i, &s) in left.iter().enumerate() {
+        assert_eq!(s, if i + 256 >= 300 { 1.0 } else { 0.0 }, "frame {}", i + 256);
+    }
+    assert_eq!(sleeper.poll_events().collect::<Vec<_>>(), vec![256, 300, 320, 384, 448]);
+
+    // And back to sleep
+    sleeper.send_at(600, false).unwrap();
+    klingt.render(256);
+    assert_eq!(sleeper.poll_events().collect::<Vec<_>>(), vec![512, 576, 600]);
+    assert!(klingt.render(256).iter().all(|&s| s == 0.0));
+    assert_eq!(sleeper.poll_events().count(), 0);
+}
+
+#[test]
+fn gain_smoothing_is_sample_exact() {
+    // Blocks that don't divide into vectors, with a second channel
+    let left: Vec<f32> = (0..4000).map(|i| i as f32 / 4000.0).collect();
+    let samples: Vec<f32> = left.iter().flat_map(|&s| vec![s, -s]).collect();
+    let mut klingt = Klingt::offline(48000).with_block_size(100);
+    let player = klingt.add(SamplePlayer::new(samples, 2, 48000));
+    let mut gain = klingt.add(Gain::new(1.0));
+    klingt.connect(&player, &gain);
+    klingt.output(&gain);
+
+    // Rendered up to the end of the block, so the change lands at frame 200
+    klingt.render(150);
+    gain.send(GainMessage::SetGain(0.25)).unwrap();
+    let output = klingt.render(3000);
+
+    let mut current: Sample = 1.0;
+    for (i, frame) in output.chunks(2).enumerate() {
+        let i = i + 150;
+        if i >= 200 {
+            current = 0.25 + 0.995 * (current - 0.25);
+        }
+        let expected = f32::from_sample(left[i] as Sample * current);
+        assert_eq!(frame, &[expected, -expected][..], "frame {}", i);
+    }
+}
+
+#[test]
+fn slew_limiter_follows_slow_signals_and_limits_fast_ones() {
+    let rate: Sample = 0.01;
+    // A slow ramp, a jump, and a slow ramp again
+    let input: Vec<f32> = (0..3000)
+        .map(|i| match i {
+            0..=999 => i as f32 * 0.005,
+            1000..=1999 => -1.0,
+            _ => -1.0 + (i - 2000) as f32 * 0.001,
+        })
+        .collect();
+    let mut klingt = Klingt::offline(48000).with_block_size(100);
+    let player = klingt.add(SamplePlayer::new(input.clone(), 1, 48000));
+    let slew = klingt.add(SlewLimiter::new(0.01));
+    klingt.connect(&player, &slew);
+    klingt.output(&slew);
+
+    let left: Vec<f32> = klingt.render(3000).iter().step_by(2).copied().collect();
+    let mut last: Sample = 0.0;
+    for (i, (&s, &x)) in left.iter().zip(&input).enumerate() {
+        last += (x as Sample - last).clamp(-rate, rate);
+        assert!((s - f32::from_sample(last)).abs() < 1e-5, "frame {}: {} != {}", i, s, last);
+    }
+}
+
+#[test]
+fn sinks_interleave_across_ring_buffer_wrap() {
+    // A capacity that doesn't hold whole frames, so chunks wrap mid-frame
+    let (producer, mut consumer) = rtrb::RingBuffer::new(1000);
+    let mut klingt = Klingt::new(48000).with_output(RtrbSink::new(producer, 3));
+    let samples: Vec<f32> = (0..10_000).flat_map(|i| vec![i as f32, -(i as f32)]).collect();
+    let player = klingt.add(SamplePlayer::new(samples, 2, 48000));
+    klingt.output(&player);
+
+    let mut output = Vec::new();
+    for _ in 0..50 {
+        klingt.process();
+        output.extend(drain(&mut consumer));
+    }
+    assert_eq!(output.len(), 50 * 64 * 3);
+    for (i, frame) in output.chunks(3).enumerate() {
+        // Stereo has nothing for the third channel
+        assert_eq!(frame, &[i as f32, -(i as f32), 0.0][..], "frame {}", i);
+    }
+}
+
+#[test]
+#[cfg(feature = "f64")]
+fn f64_keeps_full_precision_up_to_the_sink() {
+    // Not representable in f32
+    let value = 1.0 + 1e-12;
+    let (producer, mut consumer) = rtrb::RingBuffer::<f64>::new(1 << 16);
+    let mut klingt = Klingt::new(48000).with_output(RtrbSink::stereo(producer));
+    let direct = klingt.add(Constant(value));
+    // Through a sub-graph and its resampler
+    let resampled = klingt.add_at_rate(Constant(value), 24000);
+    let mixer = klingt.add(Mixer::stereo());
+    let half = klingt.add(Gain::new(0.5).without_smoothing());
+    klingt.connect(&direct, &mixer);
+    klingt.connect(&resampled, &mixer);
+    klingt.connect(&mixer, &half);
+    klingt.output(&half);
+
+    for _ in 0..64 {
+        klingt.process();
+    }
+    let samples: Vec<f64> = std::iter::from_fn(|| consumer.pop().ok()).collect();
+    // Past the resampler's delay
+    assert!(samples[samples.len() / 2..].iter().all(|&s| s == value), "{:?}", &samples[samples.len() - 4..]);
+}
+
+/// A `channels`-channel player whose channel `c` holds `(c + 1) / 32` throughout
+fn channel_player(channels: usize, sample_rate: u32) -> SamplePlayer {
+    let frame: Vec<f32> = (0..channels).map(|c| (c + 1) as f32 / 32.0).collect();
+    SamplePlayer::new(frame.repeat(sample_rate as usize), channels, sample_rate)
+}
+
+#[test]
+fn nodes_get_as_many_channels_as_they_output() {
+    for &channels in &[1, 3, 6, 8, 16] {
+        let mut klingt = Klingt::offline(48000).with_channels(channels);
+        let player = klingt.add(channel_player(channels, 48000));
+        let gain = klingt.add(Gain::new(1.0).with_channels(channels));
+        let slew = klingt.add(SlewLimiter::new(1.0).with_channels(channels));
+        let mixer = klingt.add(Mixer::new(channels));
+        klingt.connect(&player, &gain);
+        klingt.connect(&gain, &slew);
+        klingt.connect(&slew, &mixer);
+        klingt.output(&mixer);
+
+        let output = klingt.render(256);
+        for frame in output.chunks(channels) {
+            for (c, &s) in frame.iter().enumerate() {
+                assert_eq!(s, (c + 1) as f32 / 32.0, "{} channels, channel {}", channels, c);
+            }
+        }
+    }
+}
+
+#[test]
+fn zero_channel_effects_keep_one_channel() {
+    let mut klingt = Klingt::offline(48000).with_channels(1);
+    let player = klingt.add(channel_player(1, 48000));
+    let gain = klingt.add(Gain::new(1.0).with_channels(0));
+    let slew = klingt.add(SlewLimiter::new(1.0).with_channels(0));
+    let mixer = klingt.add(Mixer::new(0));
+    klingt.connect(&player, &gain);
+    klingt.connect(&gain, &slew);
+    klingt.connect(&slew, &mixer);
+    klingt.output(&mixer);
+
+    let output = klingt.render(256);
+    assert!(output.iter().all(|&s| s == 1.0 / 32.0));
+}
+
+#[test]
+fn resampling_bridge_carries_every_channel() {
+    for &channels in &[6, 8, 16] {
+        let mut klingt = Klingt::offline(48000).with_channels(channels);
+        let player = klingt.add(channel_player(channels, 44100));
+        klingt.output(&player);
+
+        let output = klingt.render(4800);
+        // Past the resampler's delay
+        for frame in output[output.len() / 2..].chunks(channels) {
+            for (c, &s) in frame.iter().enumerate() {
+                assert!((s - (c + 1) as f32 / 32.0).abs() < 1e-6, "{} channels, channel {}: {}", channels, c, s);
+            }
+        }
+    }
+}
+
+#[test]
+fn bridges_carry_every_channel_into_a_sub_graph_and_back() {
+    // A stereo engine, but a 5.1 chain feeding a 5.1 sink
+    let (producer, mut consumer) = rtrb::RingBuffer::new(1 << 16);
+    let mut klingt = Klingt::new(48000).with_output(RtrbSink::new(producer, 6));
+    let player = klingt.add(channel_player(6, 48000));
+    let gain = klingt.add_at_rate(Gain::new(1.0).with_channels(6), 44100);
+    klingt.connect(&player, &gain);
+    klingt.output(&gain);
+
+    for _ in 0..75 {
+        klingt.process();
+    }
+    let output = drain(&mut consumer);
+    assert_eq!(output.len(), 4800 * 6);
+    // Past both resamplers' delay
+    for frame in output[output.len() / 2..].chunks(6) {
+        for (c, &s) in frame.iter().enumerate() {
+            assert!((s - (c + 1) as f32 / 32.0).abs() < 1e-6, "channel {}: {}", c, s);
+        }
+    }
+}
+
+#[test]
+fn sinks_interleave_any_channel_count() {
+    let (producer, mut consumer) = rtrb::RingBuffer::new(1 << 16);
+    let mut klingt = Klingt::new(48000).with_channels(8).with_output(RtrbSink::new(producer, 8));
+    let player = klingt.add(channel_player(8, 48000));
+    klingt.output(&player);
+
+    klingt.process();
+    let output = drain(&mut consumer);
+    assert_eq!(output.len(), 64 * 8);
+    for frame in output.chunks(8) {
+        assert_eq!(frame, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0].map(|s: f32| s / 32.0)[..]);
+    }
+}
+
+#[test]
+fn channel_layouts_mix_by_the_book() {
+    use ChannelLayout::*;
+    let h = std::f64::consts::FRAC_1_SQRT_2 as Sample;
+    let close = |a: Vec<Sample>, b: Vec<Sample>| {
+        assert_eq!(a.len(), b.len());
+        assert!(a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-6), "{:?} != {:?}", a, b);
+    };
+
+    assert_eq!(Mono.mix_matrix(Stereo), vec![1.0, 1.0]);
+    assert_eq!(Mono.mix_matrix(Surround51), vec![0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(Stereo.mix_matrix(Mono), vec![0.5, 0.5]);
+    assert_eq!(Quad.mix_matrix(Stereo), vec![0.5, 0.0, 0.5, 0.0, 0.0, 0.5, 0.0, 0.5]);
+    close(Surround51.mix_matrix(Stereo), vec![1.0, 0.0, h, 0.0, h, 0.0, 0.0, 1.0, h, 0.0, 0.0, h]);
+    // Back speakers fold into the sides on the way
+    close(Surround71.mix_matrix(Stereo), vec![1.0, 0.0, h, 0.0, 0.5, 0.0, h, 0.0, 0.0, 1.0, h, 0.0, 0.0, 0.5, 0.0, h]);
+    assert_eq!(Discrete(3).mix_matrix(Stereo), vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+    assert_eq!(Ambisonic(1).mix_matrix(Ambisonic(0)), vec![1.0, 0.0, 0.0, 0.0]);
+
+    // Stereo speakers sit at +-30 degrees: W, Y, Z and X of each
+    let (sin, cos) = (0.5, (3.0 as Sample).sqrt() / 2.0);
+    close(Stereo.mix_matrix(Ambisonic(1)), vec![1.0, 1.0, sin, -sin, 0.0, 0.0, cos, cos]);
+    // A speaker's own direction decodes back to it in full
+    let decoded = Ambisonic(1).mix_matrix(Stereo);
+    let left: Sample = decoded[..4].iter().zip(&[1.0, sin, 0.0, cos]).map(|(d, e)| d * e).sum();
+    assert!((left - 1.0).abs() < 1e-6, "{}", left);
+    // Second order straight ahead: V, T, R, S and U
+    close(Mono.mix_matrix(Ambisonic(2))[4..].to_vec(), vec![0.0, 0.0, -0.5, 0.0, cos]);
+}
+
+#[test]
+fn connected_layouts_are_mixed_to_match() {
+    let h = std::f32::consts::FRAC_1_SQRT_2;
+    // 5.1 into stereo, in the same graph and across a bridge
+    for &rate in &[48000, 44100] {
+        let mut klingt = Klingt::offline(48000);
+        let player = klingt.add(channel_player(6, rate));
+        klingt.output(&player);
+        assert!(klingt.to_dot().contains("label=\"ChannelMixer\\n1 in, 2 out\", style=dashed"));
+
+        let output = klingt.render(4800);
+        let (left, right) = ((1.0 + h * 8.0) / 32.0, (2.0 + h * 9.0) / 32.0);
+        for frame in output[output.len() / 2..].chunks(2) {
+            assert!((frame[0] - left).abs() < 1e-5 && (frame[1] - right).abs() < 1e-5, "{} Hz: {:?}", rate, frame);
+        }
+    }
+
+    // Mono goes to the center of a 5.1 mixer
+    let mut klingt = Klingt::offline(48000).with_channels(6);
+    let constant = klingt.add(Constant(0.25));
+    let mixer = klingt.add(Mixer::new(6));
+    klingt.connect(&constant, &mixer);
+    klingt.output(&mixer);
+    for frame in klingt.render(256).chunks(6) {
+        assert_eq!(frame, &[0.0, 0.0, 0.25, 0.0, 0.0, 0.0][..]);
+    }
+}
+
+#[test]
+fn matching_layouts_and_ports_connect_directly() {
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(channel_player(2, 48000));
+    let gain = klingt.add(Gain::new(1.0));
+    let mixer = klingt.add(Mixer::mono());
+    klingt.connect(&player, &gain);
+    klingt.connect_ports(&gain, 1, &mixer, 0);
+    klingt.output(&gain);
+    assert!(!klingt.to_dot().contains("ChannelMixer"));
+
+    // Picking a channel by hand skips the mixer, connecting them all doesn't
+    klingt.connect(&gain, &mixer);
+    assert!(klingt.to_dot().contains("ChannelMixer"));
+    klingt.disconnect(&gain, &mixer);
+    assert!(!klingt.to_dot().contains("ChannelMixer"));
+}
+
+#[test]
+fn picked_channels_land_on_the_first_channel() {
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(channel_player(2, 48000));
+    let gain = klingt.add(Gain::new(1.0));
+    let slew = klingt.add(SlewLimiter::new(1.0));
+    let mixer = klingt.add(Mixer::stereo());
+    klingt.connect_ports(&player, 1, &gain, 0);
+    klingt.connect_ports(&gain, 0, &slew, 0);
+    klingt.connect_ports(&slew, 0, &mixer, 0);
+    klingt.output(&mixer);
+
+    // Nothing repeats it onto the other channels - that's what layouts are for
+    for frame in klingt.render(64).chunks(2) {
+        assert_eq!(frame, &[2.0 / 32.0, 0.0]);
+    }
+}
+
+#[test]
+fn channel_mixers_go_with_their_connection() {
+    let mut klingt = Klingt::offline(48000);
+    let player = klingt.add(channel_player(6, 44100));
+    let mixer = klingt.add(Mixer::stereo());
+    klingt.connect(&player, &mixer);
+    klingt.output(&mixer);
+    assert_eq!(klingt.to_dot().matches("ChannelMixer").count(), 1);
+
+    klingt.disconnect(&player, &mixer);
+    assert!(!klingt.to_dot().contains("ChannelMixer"));
+    assert!(klingt.to_dot().contains("44100"));
+    assert!(klingt.render(4800).iter().all(|&s| s == 0.0));
+
+    // Removing either end removes the mixer and, with the last node, the sub-graph
+    klingt.connect(&player, &mixer);
+    assert!(klingt.to_dot().contains("ChannelMixer"));
+    klingt.remove(player);
+    let dot = klingt.to_dot();
+    assert!(!dot.contains("ChannelMixer"));
+    assert!(!dot.contains("44100"));
+
+    // A mixer added by hand connects like any other node
+    let constant = klingt.add(Constant(0.5));
+    let upmix = klingt.add(ChannelMixer::new(ChannelLayout::Mono, ChannelLayout::Stereo));
+    klingt.connect(&constant, &upmix);
+    klingt.connect(&upmix, &mixer);
+    assert_eq!(klingt.to_dot().matches("ChannelMixer").count(), 1);
+    assert!(klingt.render(256).iter().all(|&s| s == 0.5));
+}